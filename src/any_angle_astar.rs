@@ -0,0 +1,192 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use ordered_float::NotNan;
+
+use crate::{
+    astar::{AStarFailureReason, AStarModel, AStarResult, AstarNode},
+    binary_heap_item::BinaryHeapItem,
+    hyperparameters::ASTAR_STRIDE,
+    pcb_render_model::PcbRenderModel,
+    routing_cost::euclidean_distance,
+    routing_error::RoutingError,
+    trace_path::{Direction, degree_angle, turn_angle},
+    vec2::FixedVec2,
+};
+
+// Theta*: the same best-first search as the octilinear model, but over a plain grid anchored at the start,
+// and a node may take its grandparent as parent whenever the straight segment between them is collision-free.
+// This straightens the path while searching, so the segments can have arbitrary angles.
+
+fn greatest_common_divisor(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        greatest_common_divisor(b, a % b)
+    }
+}
+
+/// the direction of the segment from start to end, reduced so that parallel segments of any length give the same key
+fn reduced_direction(start: FixedVec2, end: FixedVec2) -> (i32, i32) {
+    let (dx, dy) = ((end.x - start.x).to_bits(), (end.y - start.y).to_bits());
+    let divisor = greatest_common_divisor(dx.unsigned_abs(), dy.unsigned_abs()).max(1) as i32;
+    (dx / divisor, dy / divisor)
+}
+
+/// a node of the search is closed by its position and the direction it was entered from,
+/// because the cost of the next bend depends on that direction; the start has no direction
+type AnyAngleNodeKey = (FixedVec2, Option<(i32, i32)>);
+
+fn any_angle_node_key(node: &AstarNode) -> AnyAngleNodeKey {
+    let direction = node
+        .prev_node
+        .as_ref()
+        .map(|parent| reduced_direction(parent.position, node.position));
+    (node.position, direction)
+}
+
+impl AStarModel {
+    fn is_line_of_sight(&self, start: FixedVec2, end: FixedVec2) -> bool {
+        !self.check_collision(start, end, self.trace_width, self.trace_clearance)
+    }
+
+    /// the segment cost plus the bend cost of extending the path of `parent` to `position`
+    /// returns None if the bend at `parent` is not allowed
+    fn any_angle_step_cost(&self, parent: &AstarNode, position: FixedVec2) -> Option<f64> {
+        let segment_cost = self.routing_cost.segment_cost(parent.position, position);
+        let turn_cost = match &parent.prev_node {
+            Some(grandparent) => self.routing_cost.turn_cost(
+                parent.position,
+                turn_angle(
+                    degree_angle(grandparent.position, parent.position),
+                    degree_angle(parent.position, position),
+                ),
+            )?,
+            None => 0.0,
+        };
+        Some(segment_cost + turn_cost)
+    }
+
+    pub(crate) fn run_any_angle(
+        &self,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<AStarResult, RoutingError> {
+        let start_estimated_cost =
+            self.routing_cost
+                .estimated_cost(self.start, self.end, self.routing_mode);
+        let start_node = AstarNode {
+            position: self.start,
+            direction: None,
+            actual_cost: 0.0,
+            actual_length: 0.0,
+            estimated_cost: start_estimated_cost,
+            total_cost: start_estimated_cost,
+            prev_node: None,
+        };
+        // frontier is a min heap
+        let mut frontier: BinaryHeap<BinaryHeapItem<Reverse<NotNan<f64>>, Rc<AstarNode>>> =
+            BinaryHeap::new();
+        frontier.push(BinaryHeapItem {
+            key: Reverse(NotNan::new(start_node.total_cost).unwrap()),
+            value: Rc::new(start_node),
+        });
+        let mut visited: HashSet<AnyAngleNodeKey> = HashSet::new();
+        // the lowest actual cost pushed for each key, so that worse duplicates are not pushed
+        let mut best_actual_costs: HashMap<AnyAngleNodeKey, f64> = HashMap::new();
+        if self.display {
+            self.display_and_block(pcb_render_model.clone(), &frontier);
+        }
+
+        let mut progress = self.start_search_progress();
+        while let Some(item) = frontier.pop() {
+            let current_node = item.value.clone();
+            if current_node.position == self.end {
+                frontier.push(item); // push the current node back to the frontier, so that it can be displayed
+                if self.display {
+                    self.display_and_block(pcb_render_model.clone(), &frontier);
+                }
+                let trace_path =
+                    current_node.to_trace_path(self.trace_width, self.trace_clearance)?;
+                return Ok(AStarResult { trace_path });
+            }
+            let current_key = any_angle_node_key(&current_node);
+            if visited.contains(&current_key) {
+                continue; // already visited this node
+            }
+            self.record_expansion(&mut progress, current_node.position)?;
+            visited.insert(current_key);
+
+            // the grid neighbors, plus the end itself so that the search does not need to hit it on the grid
+            let mut neighbors: Vec<FixedVec2> = Direction::all_directions()
+                .iter()
+                .map(|direction| current_node.position + direction.to_fixed_vec2(*ASTAR_STRIDE))
+                .collect();
+            neighbors.push(self.end);
+            for neighbor in neighbors {
+                if neighbor == current_node.position {
+                    continue;
+                }
+                // prefer the grandparent as parent if it can see the neighbor
+                let candidate_parents = current_node
+                    .prev_node
+                    .iter()
+                    .cloned()
+                    .chain(std::iter::once(current_node.clone()));
+                let mut chosen_parent: Option<(Rc<AstarNode>, f64)> = None;
+                for parent in candidate_parents {
+                    if !self.is_line_of_sight(parent.position, neighbor) {
+                        continue;
+                    }
+                    if let Some(step_cost) = self.any_angle_step_cost(&parent, neighbor) {
+                        chosen_parent = Some((parent, step_cost));
+                        break;
+                    }
+                }
+                let Some((parent, step_cost)) = chosen_parent else {
+                    continue;
+                };
+                let length = euclidean_distance(&parent.position, &neighbor);
+                if self.exceeds_max_length(neighbor, parent.actual_length + length) {
+                    continue;
+                }
+                let actual_cost = parent.actual_cost + step_cost;
+                let neighbor_key: AnyAngleNodeKey =
+                    (neighbor, Some(reduced_direction(parent.position, neighbor)));
+                if visited.contains(&neighbor_key)
+                    || best_actual_costs
+                        .get(&neighbor_key)
+                        .is_some_and(|best_actual_cost| *best_actual_cost <= actual_cost)
+                {
+                    continue;
+                }
+                best_actual_costs.insert(neighbor_key, actual_cost);
+                let estimated_cost =
+                    self.routing_cost
+                        .estimated_cost(neighbor, self.end, self.routing_mode);
+                let new_node = AstarNode {
+                    position: neighbor,
+                    direction: Direction::from_points(parent.position, neighbor).ok(),
+                    actual_cost,
+                    actual_length: parent.actual_length + length,
+                    estimated_cost,
+                    total_cost: actual_cost + estimated_cost,
+                    prev_node: Some(parent),
+                };
+                frontier.push(BinaryHeapItem {
+                    key: Reverse(NotNan::new(new_node.total_cost).unwrap()),
+                    value: Rc::new(new_node),
+                });
+            }
+            if self.display {
+                self.display_and_block(pcb_render_model.clone(), &frontier);
+            }
+        }
+        Err(self
+            .search_failure(&progress, AStarFailureReason::FrontierExhausted)
+            .into())
+    }
+}
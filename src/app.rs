@@ -25,6 +25,17 @@ pub struct App {
     pub context: Context,
 }
 
+impl App {
+    /// cancels the solver and waits until the working thread has returned its result
+    fn stop_working_thread(&self) {
+        self.context.cancel_token.cancel();
+        let mut working_thread = self.context.working_thread.lock().unwrap();
+        if let Some(working_thread) = working_thread.take() {
+            working_thread.join().unwrap();
+        }
+    }
+}
+
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let attributes = WindowAttributes::default()
@@ -37,9 +48,10 @@ impl ApplicationHandler for App {
         state.init();
         self.window = Some(window);
         let pcb_render_model = self.context.pcb_render_model.clone();
+        let cancel_token = self.context.cancel_token.clone();
         let mut working_thread = self.context.working_thread.lock().unwrap();
         *working_thread = Some(std::thread::spawn(move || {
-            working_thread_fn::working_thread_fn(pcb_render_model);
+            working_thread_fn::working_thread_fn(pcb_render_model, cancel_token);
         }));
     }
     fn device_event(
@@ -59,6 +71,7 @@ impl ApplicationHandler for App {
             WindowEvent::CloseRequested => {
                 println!("The close button was pressed; stopping");
                 // std::process::abort();
+                self.stop_working_thread();
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
@@ -76,9 +89,7 @@ impl ApplicationHandler for App {
                     // The system is out of memory, we should probably quit
                     Err(wgpu::SurfaceError::OutOfMemory | wgpu::SurfaceError::Other) => {
                         log::error!("OutOfMemory");
-                        let mut working_thread = self.context.working_thread.lock().unwrap();
-                        let working_thread = working_thread.take().unwrap();
-                        working_thread.join().unwrap();
+                        self.stop_working_thread();
                         event_loop.exit();
                     }
 
@@ -2,8 +2,10 @@ use std::{
     cell::RefCell,
     cmp::Reverse,
     collections::{BinaryHeap, HashMap, HashSet},
+    fmt,
     rc::Rc,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use fixed::traits::Fixed;
@@ -12,13 +14,88 @@ use ordered_float::NotNan;
 use crate::{
     binary_heap_item::BinaryHeapItem,
     block_or_sleep::{block_or_sleep, block_thread},
-    hyperparameters::{ASTAR_STRIDE, DISPLAY_ASTAR, ESTIMATE_COEFFICIENT, TURN_PENALTY},
+    hyperparameters::{ASTAR_MAX_DURATION_MILLIS, ASTAR_MAX_EXPANSIONS, ASTAR_STRIDE},
     pcb_render_model::{PcbRenderModel, RenderableBatch, ShapeRenderable, UpdatePcbRenderModel},
     prim_shape::{CircleShape, PrimShape, RectangleShape},
-    trace_path::{Direction, TraceAnchors, TracePath, TraceSegment},
+    routing_cost::{RoutingCost, euclidean_distance},
+    routing_error::{RoutingError, ensure_invariant},
+    stop_condition::{StopCondition, StopReason},
+    trace_path::{Direction, TracePath, TraceSegment, turn_angle},
     vec2::{FixedPoint, FixedVec2, FloatVec2},
 };
 
+/// the set of segment angles a trace is allowed to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RoutingMode {
+    #[default]
+    Octilinear, // 0°, 45°, 90°, ... on the stride grid
+    Manhattan,  // 0°, 90°, 180°, 270° only, on the stride grid
+    AnyAngle,   // arbitrary segment angles, searched with Theta*
+}
+
+/// the limits of a single A* search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchBudget {
+    pub max_expansions: usize,         // number of expanded (not merely pushed) nodes
+    pub max_duration: Option<Duration>, // wall time, None for no limit
+}
+
+impl Default for SearchBudget {
+    fn default() -> Self {
+        SearchBudget {
+            max_expansions: ASTAR_MAX_EXPANSIONS,
+            max_duration: ASTAR_MAX_DURATION_MILLIS.map(Duration::from_millis),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AStarFailureReason {
+    ExpansionLimitReached,
+    TimeLimitReached,
+    Cancelled,         // the solve was cancelled through its CancelToken
+    FrontierExhausted, // every reachable position was expanded without reaching the end
+}
+
+/// why and how far an A* search failed, so that the caller can decide whether retrying makes sense
+#[derive(Debug, Clone)]
+pub struct AStarFailure {
+    pub reason: AStarFailureReason,
+    pub closest_position: FixedVec2, // the expanded position closest to the end
+    pub closest_distance: f64,       // euclidean distance from closest_position to the end
+    pub expansions: usize,
+    pub elapsed: Duration,
+    pub target_enclosed: bool, // the end cannot be left by one grid step in any allowed direction
+}
+
+impl fmt::Display for AStarFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} after {} expansions in {:?}, closest position ({}, {}) at distance {:.3} from the end{}",
+            self.reason,
+            self.expansions,
+            self.elapsed,
+            self.closest_position.x,
+            self.closest_position.y,
+            self.closest_distance,
+            if self.target_enclosed {
+                ", the end is enclosed by obstacles"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// bookkeeping of a running search, shared by the grid and the any-angle search
+pub(crate) struct SearchProgress {
+    start_time: Instant,
+    expansions: usize,
+    closest_position: FixedVec2,
+    closest_distance: f64,
+}
+
 pub struct AStarModel {
     pub width: f32,
     pub height: f32,
@@ -28,12 +105,54 @@ pub struct AStarModel {
     pub end: FixedVec2,
     pub trace_width: f32,
     pub trace_clearance: f32,
+    pub routing_mode: RoutingMode,
+    pub routing_cost: Arc<dyn RoutingCost>,
+    pub search_budget: SearchBudget,
+    pub stop_condition: StopCondition, // the cancellation and the deadline of the whole solve
     pub border_cache: RefCell<Option<Rc<Vec<PrimShape>>>>,
+    pub max_length: Option<f64>, // the paths that cannot reach the end within this length are pruned, None for no limit
+    pub display: bool, // render every expansion, see SolverConfig::display_astar
 }
 
 
 
 impl AStarModel {
+    /// the directions that the grid search may expand in
+    pub fn allowed_directions(&self) -> Vec<Direction> {
+        match self.routing_mode {
+            RoutingMode::Manhattan => vec![
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ],
+            RoutingMode::Octilinear | RoutingMode::AnyAngle => Direction::all_directions(),
+        }
+    }
+
+    /// converts a pad position to a start or end point that satisfies the parity requirements of the grid search
+    pub fn to_search_point(&self, position: FloatVec2) -> FixedVec2 {
+        let mut point = position.to_fixed();
+        match self.routing_mode {
+            RoutingMode::Octilinear => {
+                if (point.x - point.y).to_bits() & 1 == 1 {
+                    point.x += FixedPoint::DELTA; // Ensure the difference between x and y is even
+                }
+            }
+            RoutingMode::Manhattan => {
+                // both coordinates even, not only their difference, so that the corners where the alignments of the start and the end cross also have an even difference
+                if point.x.to_bits() & 1 == 1 {
+                    point.x += FixedPoint::DELTA;
+                }
+                if point.y.to_bits() & 1 == 1 {
+                    point.y += FixedPoint::DELTA;
+                }
+            }
+            RoutingMode::AnyAngle => {}
+        }
+        point
+    }
+
     fn get_border_shapes(&self) -> Rc<Vec<PrimShape>> {
         if let Some(border_shapes) = self.border_cache.borrow().as_ref() {
             return border_shapes.clone();
@@ -101,8 +220,15 @@ impl AStarModel {
         false
     }
 
-    pub fn clamp_by_collision(&self, start_pos: FixedVec2, end_pos: FixedVec2) -> Option<FixedVec2>{
-        assert!(Direction::is_two_points_valid_direction(start_pos, end_pos));
+    /// whether a path through position, with the length up to it, must be longer than the max length
+    pub(crate) fn exceeds_max_length(&self, position: FixedVec2, actual_length: f64) -> bool {
+        self.max_length.is_some_and(|max_length| {
+            actual_length + euclidean_distance(&position, &self.end) > max_length
+        })
+    }
+
+    pub fn clamp_by_collision(&self, start_pos: FixedVec2, end_pos: FixedVec2) -> Result<Option<FixedVec2>, RoutingError> {
+        ensure_invariant!(Direction::is_two_points_valid_direction(start_pos, end_pos));
         if self.check_collision(
             start_pos,
             end_pos,
@@ -111,21 +237,18 @@ impl AStarModel {
         ) {
             self.binary_approach_to_obstacles(start_pos, end_pos)
         } else {
-            Some(end_pos)
+            Ok(Some(end_pos))
         }
     }
 
-    fn check_collision(
+    pub(crate) fn check_collision(
         &self,
         start_position: FixedVec2,
         end_position: FixedVec2,
         trace_width: f32,
         trace_clearance: f32,
     ) -> bool {
-        assert_ne!(
-            start_position, end_position,
-            "Start and end positions should not be the same"
-        );
+        // a zero-length segment is checked as its end circles
         let trace_segment = TraceSegment {
             start: start_position,
             end: end_position,
@@ -154,12 +277,70 @@ impl AStarModel {
         }
         false // no collision
     }
-    fn octile_distance(start: &FixedVec2, end: &FixedVec2) -> f64 {
-        let start = start.to_float();
-        let end = end.to_float();
-        let dx = (end.x - start.x).abs() as f64;
-        let dy = (end.y - start.y).abs() as f64;
-        f64::max(dx, dy) + (f64::sqrt(2.0) - 1.0) * f64::min(dx, dy)
+    pub(crate) fn start_search_progress(&self) -> SearchProgress {
+        SearchProgress {
+            start_time: Instant::now(),
+            expansions: 0,
+            closest_position: self.start,
+            closest_distance: euclidean_distance(&self.start, &self.end),
+        }
+    }
+
+    /// counts the expansion of position, fails if the search budget is used up
+    pub(crate) fn record_expansion(
+        &self,
+        progress: &mut SearchProgress,
+        position: FixedVec2,
+    ) -> Result<(), AStarFailure> {
+        progress.expansions += 1;
+        let distance = euclidean_distance(&position, &self.end);
+        if distance < progress.closest_distance {
+            progress.closest_distance = distance;
+            progress.closest_position = position;
+        }
+        if progress.expansions > self.search_budget.max_expansions {
+            return Err(self.search_failure(progress, AStarFailureReason::ExpansionLimitReached));
+        }
+        if self
+            .search_budget
+            .max_duration
+            .is_some_and(|max_duration| progress.start_time.elapsed() > max_duration)
+        {
+            return Err(self.search_failure(progress, AStarFailureReason::TimeLimitReached));
+        }
+        match self.stop_condition.check() {
+            Some(StopReason::Cancelled) => Err(self.search_failure(progress, AStarFailureReason::Cancelled)),
+            Some(_) => Err(self.search_failure(progress, AStarFailureReason::TimeLimitReached)),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn search_failure(
+        &self,
+        progress: &SearchProgress,
+        reason: AStarFailureReason,
+    ) -> AStarFailure {
+        AStarFailure {
+            reason,
+            closest_position: progress.closest_position,
+            closest_distance: progress.closest_distance,
+            expansions: progress.expansions,
+            elapsed: progress.start_time.elapsed(),
+            target_enclosed: self.is_end_enclosed(),
+        }
+    }
+
+    /// whether every allowed one-stride step away from the end collides
+    fn is_end_enclosed(&self) -> bool {
+        self.allowed_directions().iter().all(|direction| {
+            let step_end = self.end + direction.to_fixed_vec2(*ASTAR_STRIDE);
+            self.check_collision(self.end, step_end, self.trace_width, self.trace_clearance)
+        })
+    }
+
+    fn estimated_cost(&self, position: FixedVec2) -> f64 {
+        self.routing_cost
+            .estimated_cost(position, self.end, self.routing_mode)
     }
 
     fn is_grid_point(&self, position: &FixedVec2) -> bool {
@@ -184,7 +365,7 @@ impl AStarModel {
 
     /// outputs the pairs of direction and the grid point that the direction leads to
     /// not implemented the collision check yet
-    fn directions_to_grid_points(&self, position: FixedVec2) -> Vec<(Direction, FixedVec2)> {
+    fn directions_to_grid_points(&self, position: FixedVec2) -> Result<Vec<(Direction, FixedVec2)>, RoutingError> {
         
         let mut result: Vec<(Direction, FixedVec2)> = Vec::new();
         // horizontal directions
@@ -194,10 +375,10 @@ impl AStarModel {
             let right_grid_point_x = Self::clamp_up(position.x);
             let left_grid_point = FixedVec2::new(left_grid_point_x, position.y);
             let right_grid_point = FixedVec2::new(right_grid_point_x, position.y);
-            assert_ne!(position, left_grid_point, "Left grid point should not be the same as position");
-            assert_ne!(position, right_grid_point, "Right grid point should not be the same as position");
-            assert!(Direction::is_two_points_valid_direction(position, left_grid_point));
-            assert!(Direction::is_two_points_valid_direction(position, right_grid_point));
+            ensure_invariant!(position != left_grid_point, "Left grid point should not be the same as position");
+            ensure_invariant!(position != right_grid_point, "Right grid point should not be the same as position");
+            ensure_invariant!(Direction::is_two_points_valid_direction(position, left_grid_point));
+            ensure_invariant!(Direction::is_two_points_valid_direction(position, right_grid_point));
             result.push((
                 Direction::Left,
                 left_grid_point,
@@ -214,16 +395,19 @@ impl AStarModel {
             let down_grid_point_y = Self::clamp_down(position.y);
             let up_grid_point = FixedVec2::new(position.x, up_grid_point_y);
             let down_grid_point = FixedVec2::new(position.x, down_grid_point_y);
-            assert_ne!(position, up_grid_point, "Up grid point should not be the same as position");
-            assert_ne!(position, down_grid_point, "Down grid point should not be the same as position");
-            assert!(Direction::is_two_points_valid_direction(position, up_grid_point));
-            assert!(Direction::is_two_points_valid_direction(position, down_grid_point));
+            ensure_invariant!(position != up_grid_point, "Up grid point should not be the same as position");
+            ensure_invariant!(position != down_grid_point, "Down grid point should not be the same as position");
+            ensure_invariant!(Direction::is_two_points_valid_direction(position, up_grid_point));
+            ensure_invariant!(Direction::is_two_points_valid_direction(position, down_grid_point));
             result.push((Direction::Up, up_grid_point));
             result.push((
                 Direction::Down,
                 down_grid_point,
             ));
         }
+        if self.routing_mode == RoutingMode::Manhattan {
+            return Ok(result);
+        }
         // top left to bottom right diagonal
         if (position.x + position.y).rem_euclid(*ASTAR_STRIDE) == FixedPoint::ZERO {
             let top_left_grid_point = FixedVec2::new(
@@ -234,19 +418,18 @@ impl AStarModel {
                 Self::clamp_up(position.x),
                 Self::clamp_down(position.y),
             );
-            assert_ne!(position, top_left_grid_point, "Top left grid point should not be the same as position");
-            assert_ne!(position, bottom_right_grid_point, "Bottom right grid point should not be the same as position");
+            ensure_invariant!(position != top_left_grid_point, "Top left grid point should not be the same as position");
+            ensure_invariant!(position != bottom_right_grid_point, "Bottom right grid point should not be the same as position");
             // assert!(Direction::is_two_points_valid_direction(position, top_left_grid_point),
             //     "old position: {:?}, new position: {:?}, dx: {}, dy: {}, direction: TopLeft",
             //     position, top_left_grid_point, top_left_grid_point.x - position.x, top_left_grid_point.y - position.y);
-            if !Direction::is_two_points_valid_direction(position, top_left_grid_point){
-                println!("Invalid TopLeft direction: old position: {:?}, new position: {:?}, dx: {}, dy: {}, direction: TopLeft",
-                position, top_left_grid_point, top_left_grid_point.x - position.x, top_left_grid_point.y - position.y);
-                println!("x % ASTAR_STRIDE: {}, y % ASTAR_STRIDE: {}", position.x.rem_euclid(*ASTAR_STRIDE).to_bits(), position.y.rem_euclid(*ASTAR_STRIDE).to_bits());
-                println!("ASTAR_STRIDE: {}", ASTAR_STRIDE.to_bits());
-                panic!("Invalid TopLeft direction");
-            }
-            assert!(Direction::is_two_points_valid_direction(position, bottom_right_grid_point));
+            ensure_invariant!(
+                Direction::is_two_points_valid_direction(position, top_left_grid_point),
+                "Invalid TopLeft direction: old position: {:?}, new position: {:?}, dx: {}, dy: {}, x % ASTAR_STRIDE: {}, y % ASTAR_STRIDE: {}, ASTAR_STRIDE: {}",
+                position, top_left_grid_point, top_left_grid_point.x - position.x, top_left_grid_point.y - position.y,
+                position.x.rem_euclid(*ASTAR_STRIDE).to_bits(), position.y.rem_euclid(*ASTAR_STRIDE).to_bits(), ASTAR_STRIDE.to_bits()
+            );
+            ensure_invariant!(Direction::is_two_points_valid_direction(position, bottom_right_grid_point));
             result.push((Direction::TopLeft, top_left_grid_point));
             result.push((Direction::BottomRight, bottom_right_grid_point));
         }
@@ -260,14 +443,14 @@ impl AStarModel {
                 Self::clamp_down(position.x),
                 Self::clamp_down(position.y),
             );
-            assert_ne!(position, top_right_grid_point, "Top right grid point should not be the same as position");
-            assert_ne!(position, bottom_left_grid_point, "Bottom left grid point should not be the same as position");
-            assert!(Direction::is_two_points_valid_direction(position, top_right_grid_point));
-            assert!(Direction::is_two_points_valid_direction(position, bottom_left_grid_point));
+            ensure_invariant!(position != top_right_grid_point, "Top right grid point should not be the same as position");
+            ensure_invariant!(position != bottom_left_grid_point, "Bottom left grid point should not be the same as position");
+            ensure_invariant!(Direction::is_two_points_valid_direction(position, top_right_grid_point));
+            ensure_invariant!(Direction::is_two_points_valid_direction(position, bottom_left_grid_point));
             result.push((Direction::TopRight, top_right_grid_point));
             result.push((Direction::BottomLeft, bottom_left_grid_point));
         }
-        result
+        Ok(result)
     }
     fn radial_directions_wrt_obstacles(&self, position: &FixedVec2) -> Vec<Direction> {
         let mut directions: Vec<Direction> = Vec::new();
@@ -275,7 +458,6 @@ impl AStarModel {
         let twice_delta = FixedPoint::DELTA * 2;
         for direction in Direction::all_directions() {
             let end_position = *position + direction.to_fixed_vec2(twice_delta);
-            assert_ne!(*position, end_position, "assert 1");
             let collides = self.check_collision(
                 *position,
                 end_position,
@@ -292,14 +474,22 @@ impl AStarModel {
              right_90_dir: Direction| {
                 // check if the direction is valid, i.e., it is not a 45-degree direction
                 // or it is a 45-degree direction but both left and right directions are not valid
-                let left_blocked =
-                    collides_at_direction[&left_90_dir] && collides_at_direction[&left_45_dir];
-                let right_blocked =
-                    collides_at_direction[&right_90_dir] && collides_at_direction[&right_45_dir];
+                // without diagonals, only the perpendicular directions can be blocked
+                let (left_blocked, right_blocked) = if self.routing_mode == RoutingMode::Manhattan {
+                    (
+                        collides_at_direction[&left_90_dir],
+                        collides_at_direction[&right_90_dir],
+                    )
+                } else {
+                    (
+                        collides_at_direction[&left_90_dir] && collides_at_direction[&left_45_dir],
+                        collides_at_direction[&right_90_dir] && collides_at_direction[&right_45_dir],
+                    )
+                };
                 let front_blocked = collides_at_direction[&dir];
                 !front_blocked && (left_blocked || right_blocked)
             };
-        for direction in Direction::all_directions() {
+        for direction in self.allowed_directions() {
             let left_90_dir = direction.left_90_dir();
             let left_45_dir = direction.left_45_dir();
             let right_45_dir = direction.right_45_dir();
@@ -317,11 +507,11 @@ impl AStarModel {
         directions
     }
     /// 将浮动点移动到稍微好一点的点
-    fn to_nearest_one_step_point(&self, position: &FixedVec2, direction: Direction) -> FixedVec2 {
+    fn to_nearest_one_step_point(&self, position: &FixedVec2, direction: Direction) -> Result<FixedVec2, RoutingError> {
         let is_difference_even = (position.x - position.y).to_bits() % 2 == 0;
-        assert!(is_difference_even, "The difference between x and y should be even, x:{}, y:{}, direction: {:?}", position.x, position.y, direction);
+        ensure_invariant!(is_difference_even, "The difference between x and y should be even, x:{}, y:{}, direction: {:?}", position.x, position.y, direction);
         // an odd odd point cannot move non-diagonally
-        assert!(direction.is_diagonal() || !position.is_x_odd_y_odd());
+        ensure_invariant!(direction.is_diagonal() || !position.is_x_odd_y_odd());
         let result = match direction {
             Direction::Up => {
                 let new_y =
@@ -408,42 +598,41 @@ impl AStarModel {
                 FixedVec2::new(new_x, new_y)
             }
         };
-        assert!(Direction::is_two_points_valid_direction(*position, result));
-        assert!(result.is_sum_even(), "Result position should be even, but got odd: {:?}", result);
-        result
+        ensure_invariant!(Direction::is_two_points_valid_direction(*position, result));
+        ensure_invariant!(result.is_sum_even(), "Result position should be even, but got odd: {:?}", result);
+        Ok(result)
     }
     /// 判断当前点是否与目标点对齐，返回对齐的方向
-    fn is_aligned_with_end(&self, position: FixedVec2) -> Option<Direction> {
-        assert_ne!(
-            position, self.end,
-            "调用该函数前应确保已经处理与end重合的情况"
-        );
+    fn is_aligned_with_end(&self, position: FixedVec2) -> Result<Option<Direction>, RoutingError> {
+        ensure_invariant!(position != self.end, "调用该函数前应确保已经处理与end重合的情况");
         if position.x == self.end.x {
             if position.y < self.end.y {
-                return Some(Direction::Up);
+                return Ok(Some(Direction::Up));
             } else {
-                return Some(Direction::Down);
+                return Ok(Some(Direction::Down));
             }
         } else if position.y == self.end.y {
             if position.x < self.end.x {
-                return Some(Direction::Right);
+                return Ok(Some(Direction::Right));
             } else {
-                return Some(Direction::Left);
+                return Ok(Some(Direction::Left));
             }
+        } else if self.routing_mode == RoutingMode::Manhattan {
+            return Ok(None); // diagonal alignments cannot be used
         } else if (position.x + position.y) == (self.end.x + self.end.y) {
             if position.x < self.end.x {
-                return Some(Direction::BottomRight);
+                return Ok(Some(Direction::BottomRight));
             } else {
-                return Some(Direction::TopLeft);
+                return Ok(Some(Direction::TopLeft));
             }
         } else if (position.x - position.y) == (self.end.x - self.end.y) {
             if position.x < self.end.x {
-                return Some(Direction::TopRight);
+                return Ok(Some(Direction::TopRight));
             } else {
-                return Some(Direction::BottomLeft);
+                return Ok(Some(Direction::BottomLeft));
             }
         }
-        None // not aligned with end
+        Ok(None) // not aligned with end
     }
     /// line 1 is finite, line 2 is infinite
     fn line_intersection_infinite(
@@ -452,10 +641,10 @@ impl AStarModel {
         line1_end: FixedVec2,
         line2_start: FixedVec2,
         line2_end: FixedVec2,
-    ) -> Option<FixedVec2> {
-        assert!(line1_start.is_sum_even());
-        assert!(line1_end.is_sum_even());
-        assert!(line2_start.is_sum_even());
+    ) -> Result<Option<FixedVec2>, RoutingError> {
+        ensure_invariant!(line1_start.is_sum_even());
+        ensure_invariant!(line1_end.is_sum_even());
+        ensure_invariant!(line2_start.is_sum_even());
         let (dx1, dy1) = (line1_end.x - line1_start.x, line1_end.y - line1_start.y);
         let (dx2, dy2) = (line2_end.x - line2_start.x, line2_end.y - line2_start.y);
 
@@ -469,7 +658,10 @@ impl AStarModel {
         } else if dy1 == -dx1 {
             (Some(-1), line1_start.y + line1_start.x) // -45°
         } else {
-            panic!("Line 1 is not aligned with the grid, dx1: {}, dy1: {}", dx1, dy1);
+            return Err(RoutingError::InternalInvariant(format!(
+                "Line 1 is not aligned with the grid, dx1: {}, dy1: {}",
+                dx1, dy1
+            )));
         };
 
         // Line 2 coefficients
@@ -482,13 +674,16 @@ impl AStarModel {
         } else if dy2 == -dx2 {
             (Some(-1), line2_start.y + line2_start.x)
         } else {
-            panic!("Line 2 is not aligned with the grid, dx2: {}, dy2: {}", dx2, dy2);
+            return Err(RoutingError::InternalInvariant(format!(
+                "Line 2 is not aligned with the grid, dx2: {}, dy2: {}",
+                dx2, dy2
+            )));
         };
 
         // Intersection logic
         match (m1, m2) {
             (Some(m1), Some(m2)) => {
-                assert_ne!(m1, m2, "Lines are parallel, no intersection");
+                ensure_invariant!(m1 != m2, "Lines are parallel, no intersection");
                 // m1 * x + c1 = m2 * x + c2 -> x = (c2 - c1) / (m1 - m2)
                 let x = (c2 - c1) / (m1 - m2);
                 let y = m1 * x + c1;
@@ -496,9 +691,9 @@ impl AStarModel {
                 let x_min = FixedPoint::min(line1_start.x, line1_end.x);
                 let x_max = FixedPoint::max(line1_start.x, line1_end.x);
                 if x >= x_min && x <= x_max {
-                    Some(FixedVec2 { x, y })
+                    Ok(Some(FixedVec2 { x, y }))
                 } else {
-                    None
+                    Ok(None)
                 }
             }
             (None, Some(m2)) => {
@@ -508,9 +703,9 @@ impl AStarModel {
                 let y_min = FixedPoint::min(line1_start.y, line1_end.y);
                 let y_max = FixedPoint::max(line1_start.y, line1_end.y);
                 if y >= y_min && y <= y_max {
-                    Some(FixedVec2 { x, y })
+                    Ok(Some(FixedVec2 { x, y }))
                 } else {
-                    None
+                    Ok(None)
                 }
             }
             (Some(m1), None) => {
@@ -519,12 +714,14 @@ impl AStarModel {
                 let y_min = FixedPoint::min(line2_start.y, line2_end.y);
                 let y_max = FixedPoint::max(line2_start.y, line2_end.y);
                 if y >= y_min && y <= y_max {
-                    Some(FixedVec2 { x, y })
+                    Ok(Some(FixedVec2 { x, y }))
                 } else {
-                    None
+                    Ok(None)
                 }
             }
-            (None, None) => panic!("Both lines are vertical, which is not expected in this context"),
+            (None, None) => Err(RoutingError::InternalInvariant(
+                "Both lines are vertical, which is not expected in this context".to_string(),
+            )),
         }
     }
 
@@ -533,59 +730,58 @@ impl AStarModel {
         &self,
         start_pos: FixedVec2,
         end_pos: FixedVec2,
-    ) -> Option<FixedVec2> {
-        assert_ne!(
-            start_pos, self.end,
-            "调用该函数前应确保已经处理与end重合的情况"
-        );
-        assert!(
-            self.is_aligned_with_end(start_pos).is_none(),
-            "调用该函数前应确保当前点不与end对齐"
-        );
-        assert!(start_pos.is_sum_even());
-        assert!(end_pos.is_sum_even());
+    ) -> Result<Option<FixedVec2>, RoutingError> {
+        ensure_invariant!(start_pos != self.end, "调用该函数前应确保已经处理与end重合的情况");
+        ensure_invariant!(self.is_aligned_with_end(start_pos)?.is_none(), "调用该函数前应确保当前点不与end对齐");
+        ensure_invariant!(start_pos.is_sum_even());
+        ensure_invariant!(end_pos.is_sum_even());
         
         
         let mut min_distance = FixedPoint::MAX;
         let mut best_intersection: Option<FixedVec2> = None;
-        let current_direction = Direction::from_points(start_pos, end_pos).unwrap();
-        let end_directions = [
-            current_direction.left_45_dir(),
-            current_direction.right_45_dir(),
-        ];
+        let current_direction = Direction::from_points(start_pos, end_pos).map_err(RoutingError::InternalInvariant)?;
+        let end_directions = if self.routing_mode == RoutingMode::Manhattan {
+            // both perpendicular directions lie on the same line through the end
+            vec![current_direction.left_90_dir()]
+        } else {
+            vec![
+                current_direction.left_45_dir(),
+                current_direction.right_45_dir(),
+            ]
+        };
         for end_direction in end_directions {
             // if end_direction == current_direction || end_direction == current_direction.opposite() {
             //     continue; // skip the same direction or its opposite
             // }
-            assert_ne!(end_direction, current_direction);
-            assert_ne!(end_direction, end_direction.opposite());
+            ensure_invariant!(end_direction != current_direction);
+            ensure_invariant!(end_direction != end_direction.opposite());
             if let Some(intersection) = self.line_intersection_infinite(
                 start_pos,
                 end_pos,
                 self.end,
                 self.end + end_direction.to_fixed_vec2(FixedPoint::DELTA),
-            ) {
-                assert!(intersection.is_sum_even());
+            )? {
+                ensure_invariant!(intersection.is_sum_even());
                 let dx = intersection.x - start_pos.x;
                 let dy = intersection.y - start_pos.y;
                 let distance = FixedPoint::max(dx.abs(), dy.abs());
-                assert!(distance != FixedPoint::ZERO, "Distance should not be zero");
+                ensure_invariant!(distance != FixedPoint::ZERO, "Distance should not be zero");
                 if distance < min_distance {
                     min_distance = distance;
                     best_intersection = Some(intersection);
                 }
             }
         }
-        best_intersection
+        Ok(best_intersection)
     }
 
     fn binary_approach_to_obstacles(
         &self,
         start_position: FixedVec2,
         end_position: FixedVec2,
-    ) -> Option<FixedVec2> {
+    ) -> Result<Option<FixedVec2>, RoutingError> {
         // println!("binary_approach_to_obstacles");
-        let direction = Direction::from_points(start_position, end_position).unwrap();
+        let direction = Direction::from_points(start_position, end_position).map_err(RoutingError::InternalInvariant)?;
         let mut lower_bound = FixedPoint::from_num(0.0);
         let mut upper_bound = FixedPoint::max(
             (start_position.x - end_position.x).abs(),
@@ -594,7 +790,7 @@ impl AStarModel {
         while lower_bound + FixedPoint::DELTA < upper_bound {
             let mid_length = (lower_bound + upper_bound) / 2;
             let temp_end = start_position + direction.to_fixed_vec2(mid_length);
-            assert_ne!(start_position, temp_end, "assert 2");
+            ensure_invariant!(start_position != temp_end, "assert 2");
             if self.check_collision(
                 start_position,
                 temp_end,
@@ -607,161 +803,22 @@ impl AStarModel {
             }
         }
         // assert_eq!(lower_bound, upper_bound, "Binary search should converge to a single point");
-        assert!(
-            (upper_bound - lower_bound).abs() <= FixedPoint::DELTA,
-            "Binary search should converge to a single point"
-        );
+        ensure_invariant!((upper_bound - lower_bound).abs() <= FixedPoint::DELTA, "Binary search should converge to a single point");
         let mut result_length = lower_bound;
         let end_position = start_position + direction.to_fixed_vec2(result_length);
         if !end_position.is_sum_even() || end_position.is_x_odd_y_odd() {
             result_length -= FixedPoint::DELTA; // ensure the result length is even
         }
         if result_length == FixedPoint::ZERO{
-            return None;
+            return Ok(None);
         }
         let end_position = start_position + direction.to_fixed_vec2(result_length);
-        assert!(end_position.is_sum_even(), "End position should be even, but got: {:?}", end_position);
-        assert!(!end_position.is_x_odd_y_odd(), "End position should not be odd-odd, but got: {:?}", end_position);
-        Some(end_position)
+        ensure_invariant!(end_position.is_sum_even(), "End position should be even, but got: {:?}", end_position);
+        ensure_invariant!(!end_position.is_x_odd_y_odd(), "End position should not be odd-odd, but got: {:?}", end_position);
+        Ok(Some(end_position))
     }
 
     
-    fn is_axis(&self, d: (FixedPoint, FixedPoint)) -> bool {
-        (d.0 == 0.0 && d.1 != 0.0) || (d.0 != 0.0 && d.1 == 0.0)
-    }
-
-    fn is_diagonal(&self, d: (FixedPoint, FixedPoint)) -> bool {
-        d.0 != 0.0 && d.1 != 0.0 && d.0.abs() == d.1.abs()
-    }
-
-    fn rebuild_segments(&self,anchors: &Vec<FixedVec2>, width: f32, clearance: f32) -> Vec<TraceSegment> {
-        let mut segments = Vec::new();
-        for i in 0..anchors.len() - 1 {
-            let start = anchors[i];
-            let end = anchors[i + 1];
-            assert_ne!(start, end, "Start and end positions should not be the same");
-            let segment = TraceSegment {
-                start,
-                end,
-                width,
-                clearance,
-            };
-            segments.push(segment);
-        }
-        segments
-    }
-
-    fn optimize_path(
-        &self,
-        trace_path: &TracePath,
-    ) -> TracePath {
-        let path = &trace_path.anchors.0;
-        let length = trace_path.length;
-        if path.len() < 4 {
-            return trace_path.clone();
-        }
-
-        let mut optimized = path.clone();
-        let mut i = 0;
-        while i < optimized.len() - 3 { // trace shifting
-            let seg1 = (&optimized[i], &optimized[i + 1]);
-            let seg2 = (&optimized[i + 2], &optimized[i + 3]);
-
-            let dx1 = seg1.1.x - seg1.0.x;
-            let dy1 = seg1.1.y - seg1.0.y;
-            let dx2 = seg2.1.x - seg2.0.x;
-            let dy2 = seg2.1.y - seg2.0.y;
-
-            if dx1 * dy2 == dx2 * dy1 {
-                let mut success = false;
-                let new_point1 = FixedVec2 {
-                    x: seg1.0.x + seg2.0.x - seg1.1.x,
-                    y: seg1.0.y + seg2.0.y - seg1.1.y,
-                };
-                let new_point2 = FixedVec2 {
-                    x: seg2.1.x - seg2.0.x + seg1.1.x,
-                    y: seg2.1.y - seg2.0.y + seg1.1.y,
-                };
-
-                let flag1 = !self.check_collision(optimized[i], new_point1, self.trace_width, self.trace_clearance)
-                    && !self.check_collision(new_point1, optimized[i + 2], self.trace_width, self.trace_clearance);
-                let flag2 = !self.check_collision(optimized[i + 1], new_point2, self.trace_width, self.trace_clearance)
-                    && !self.check_collision(new_point2, optimized[i + 3], self.trace_width, self.trace_clearance);
-
-                if flag1 {
-                    optimized[i + 1] = new_point1;
-                    success = true;
-                } else if flag2 {
-                    optimized[i + 2] = new_point2;
-                    success = true;
-                }
-
-                if success {
-                    i += 3;
-                    continue;
-                }
-            }
-            i += 1;
-        }
-
-        i = 1;
-        while i < optimized.len() - 2 { // tight wrapping
-            let p0 = optimized[i - 1];
-            let p1 = optimized[i];
-            let p2 = optimized[i + 1];
-            let p3 = optimized[i + 2];
-
-            let d01 = (p1.x - p0.x, p1.y - p0.y);
-            let d12 = (p2.x - p1.x, p2.y - p1.y);
-            let d23 = (p3.x - p2.x, p3.y - p2.y);
-
-            if (self.is_axis(d01) && self.is_diagonal(d12) && self.is_axis(d23) && (d01.0 * d23.0 == FixedPoint::ZERO)) ||  // vertical-diagonal-horizontal or horizontal-diagonal-vertical
-           (self.is_diagonal(d01) && self.is_axis(d12) && self.is_diagonal(d23) && ((d01.0.signum() != d23.0.signum()) || (d01.1.signum() != d23.1.signum()))){  // diagonal-axis-diagonal with different directions {
-
-                let len_d01 = FixedPoint::max(d01.0.abs(), d01.1.abs());
-                let len_d23 = FixedPoint::max(d23.0.abs(), d23.1.abs());
-                let max_length = FixedPoint::min(len_d01, len_d23);
-                let num_steps = (max_length / FixedPoint::DELTA).ceil().to_num();
-
-
-                for step_idx in 0..=num_steps {
-                    let step = FixedPoint::from_num(step_idx) * FixedPoint::DELTA;
-                    let step = FixedPoint::min(step, max_length);
-                    
-                    let new_point1 = FixedVec2 {
-                        x: p1.x - d01.0 * (max_length - step) / len_d01,
-                        y: p1.y - d01.1 * (max_length - step) / len_d01,
-                    };
-                    let new_point2 = FixedVec2 {
-                        x: p2.x + d23.0 * (max_length - step) / len_d23,
-                        y: p2.y + d23.1 * (max_length - step) / len_d23,
-                    };
-
-                    if !self.check_collision(p0, new_point1, self.trace_width, self.trace_clearance)
-                        && !self.check_collision(new_point1, new_point2, self.trace_width, self.trace_clearance)
-                        && !self.check_collision(new_point2, p3, self.trace_width, self.trace_clearance)
-                    {
-                        // todo: edit length
-                        optimized[i] = new_point1;
-                        optimized[i + 1] = new_point2;
-                        break;
-                    }
-                }
-            }
-            i += 1;
-        }
-        let segments = self.rebuild_segments(&optimized, self.trace_width, self.trace_clearance);
-        TracePath {
-            anchors: TraceAnchors(optimized),
-            segments,
-            length: length,
-        }
-    }
-
-
-
-
-
     // 1. 整点/走一步到整点 -> 整点，或被障碍物挡住
     // 2. 走两步到整点+贴着障碍物 -> 对每个方向，走到最近的“走一步到整点”，或被障碍物挡住
     // 3. 是否align with end，如果是，并且align成功了的话，将end放入frontier
@@ -785,7 +842,7 @@ impl AStarModel {
     // 将所有的expand的点放入frontier
 
 
-    fn display_and_block(&self, pcb_render_model: Arc<Mutex<PcbRenderModel>>, frontier: &BinaryHeap<BinaryHeapItem<Reverse<NotNan<f64>>, Rc<AstarNode>>>) {
+    pub(crate) fn display_and_block(&self, pcb_render_model: Arc<Mutex<PcbRenderModel>>, frontier: &BinaryHeap<BinaryHeapItem<Reverse<NotNan<f64>>, Rc<AstarNode>>>) {
         let mut frontier_vec: Vec<BinaryHeapItem<Reverse<NotNan<f64>>, Rc<AstarNode>>> =
             frontier.clone().drain().collect();
         frontier_vec.reverse();
@@ -899,26 +956,28 @@ impl AStarModel {
         render_model.pad_shape_renderables.push(start_renderable);
         render_model.pad_shape_renderables.push(end_renderable);
         pcb_render_model.update_pcb_render_model(render_model);
-        block_or_sleep();
+        block_or_sleep(&self.stop_condition);
+    }
+
+    pub fn run(&self, pcb_render_model: Arc<Mutex<PcbRenderModel>>) -> Result<AStarResult, RoutingError> {
+        match self.routing_mode {
+            RoutingMode::Octilinear | RoutingMode::Manhattan => {
+                let result = self.run_on_grid(pcb_render_model)?;
+                if self.routing_mode == RoutingMode::Manhattan {
+                    ensure_invariant!(result.trace_path.segments.iter().all(|segment| segment.is_axis_aligned()), "Manhattan routing produced a segment that is not axis aligned: {:?}", result.trace_path.anchors);
+                }
+                Ok(result)
+            }
+            RoutingMode::AnyAngle => self.run_any_angle(pcb_render_model),
+        }
     }
 
-    pub fn run(&self, pcb_render_model: Arc<Mutex<PcbRenderModel>>) -> Result<AStarResult, String> {
+    fn run_on_grid(&self, pcb_render_model: Arc<Mutex<PcbRenderModel>>) -> Result<AStarResult, RoutingError> {
         let is_start_difference_even = (self.start.x - self.start.y).to_bits() % 2 == 0;
-        assert!(
-            is_start_difference_even,
-            "Start position should have an even difference between x and y, x: {}, y: {}",
-            self.start.x,
-            self.start.y
-        );
+        ensure_invariant!(is_start_difference_even, "Start position should have an even difference between x and y, x: {}, y: {}", self.start.x, self.start.y);
         let is_end_difference_even = (self.end.x - self.end.y).to_bits() % 2 == 0;
-        assert!(
-            is_end_difference_even,
-            "End position should have an even difference between x and y, x: {}, y: {}",
-            self.end.x,
-            self.end.y
-        );
-        let start_estimated_cost =
-            Self::octile_distance(&self.start, &self.end) * ESTIMATE_COEFFICIENT;
+        ensure_invariant!(is_end_difference_even, "End position should have an even difference between x and y, x: {}, y: {}", self.end.x, self.end.y);
+        let start_estimated_cost = self.estimated_cost(self.start);
 
         let start_node = AstarNode {
             position: self.start,
@@ -938,12 +997,13 @@ impl AStarModel {
             value: Rc::new(start_node),
         });
         let mut visited: HashSet<AstarNodeKey> = HashSet::new();
-        if DISPLAY_ASTAR {
+        // the lowest actual cost pushed for each key, so that worse duplicates are not pushed
+        let mut best_actual_costs: HashMap<AstarNodeKey, f64> = HashMap::new();
+        if self.display {
             self.display_and_block(pcb_render_model.clone(), &frontier); // display the initial state of the frontier
         }
 
-        let max_trials: usize = 200;
-        let mut trial_count = 0;
+        let mut progress = self.start_search_progress();
         while !frontier.is_empty() {
             
 
@@ -952,50 +1012,72 @@ impl AStarModel {
             let current_node = item.value.clone();
             if current_node.position == self.end {
                 frontier.push(item); // push the current node back to the frontier, so that it can be displayed
-                if DISPLAY_ASTAR {
+                if self.display {
                     self.display_and_block(pcb_render_model.clone(), &frontier); // display the initial state of the frontier
                 }
                 // Reached the end node, construct the trace path
-                let trace_path = current_node.to_trace_path(self.trace_width, self.trace_clearance);
+                let trace_path = current_node.to_trace_path(self.trace_width, self.trace_clearance)?;
                 return Ok(AStarResult { trace_path });
             }
 
             // move to the visited set
             let current_key = AstarNodeKey {
                 position: current_node.position,
+                direction: current_node.direction,
             };
             if visited.contains(&current_key) {
                 continue; // already visited this node
             }
-            // don't consider visited nodes as trials
-            trial_count += 1;
-            if trial_count > max_trials {
-                return Err("A* search exceeded maximum trials".to_string());
-            }
+            // don't consider visited nodes as expansions
+            self.record_expansion(&mut progress, current_node.position)?;
             visited.insert(current_key.clone());
             // expand
 
             // new:
             // hoist the closure out of the directions loop for the aligned_with_end condition
-            let mut try_push_node_to_frontier = |direction: Direction, end_position: FixedVec2| {
-                assert!(!end_position.is_x_odd_y_odd() || !self.directions_to_grid_points(end_position).is_empty(), "The end position should not be an odd-odd point if there are no directions to grid points");
+            let mut try_push_node_to_frontier = |direction: Direction, end_position: FixedVec2| -> Result<(), RoutingError> {
+                ensure_invariant!(!end_position.is_x_odd_y_odd() || !self.directions_to_grid_points(end_position)?.is_empty(), "The end position should not be an odd-odd point if there are no directions to grid points");
                 let end_position_difference_even = 
                     (end_position.x - end_position.y).to_bits() % 2 == 0;
-                assert!(end_position_difference_even, "The difference between x and y should be even, x:{}, y:{}, direction: {:?}", end_position.x, end_position.y, direction);
+                ensure_invariant!(end_position_difference_even, "The difference between x and y should be even, x:{}, y:{}, direction: {:?}", end_position.x, end_position.y, direction);
                 
                 let astar_node_key = AstarNodeKey {
                     position: end_position,
+                    direction: Some(direction),
                 };
-                // check if the new position is already visited
+                // check if the new position is already visited from this direction
                 if visited.contains(&astar_node_key) {
-                    return;
+                    return Ok(());
                 }
+                // bends are charged by their angle, acute bends are not expanded
+                let turn_cost = match current_node.direction {
+                    Some(current_direction) => match self.routing_cost.turn_cost(
+                        current_node.position,
+                        turn_angle(current_direction.to_degree_angle(), direction.to_degree_angle()),
+                    ) {
+                        Some(turn_cost) => turn_cost,
+                        None => return Ok(()),
+                    },
+                    None => 0.0,
+                };
                 // let length: f64 = (direction.to_fixed_vec2().length() * length).to_num();
                 let length: f64 = (end_position - current_node.position).length().to_num();
-                let actual_cost = current_node.actual_cost + length; // to do: add turn penalty
+                let segment_cost = self
+                    .routing_cost
+                    .segment_cost(current_node.position, end_position);
+                let actual_cost = current_node.actual_cost + segment_cost + turn_cost;
                 let actual_length = current_node.actual_length + length;
-                let estimated_cost =
-                    AStarModel::octile_distance(&end_position, &self.end) * ESTIMATE_COEFFICIENT;
+                if self.exceeds_max_length(end_position, actual_length) {
+                    return Ok(());
+                }
+                if best_actual_costs
+                    .get(&astar_node_key)
+                    .is_some_and(|best_actual_cost| *best_actual_cost <= actual_cost)
+                {
+                    return Ok(());
+                }
+                best_actual_costs.insert(astar_node_key, actual_cost);
+                let estimated_cost = self.estimated_cost(end_position);
                 let total_cost = actual_cost + estimated_cost;
                 let new_node = AstarNode {
                     position: end_position,
@@ -1011,18 +1093,19 @@ impl AStarModel {
                     key: Reverse(NotNan::new(new_node.total_cost).unwrap()), // use Reverse to make it a min heap
                     value: Rc::new(new_node),
                 });
+                Ok(())
             };
 
-            assert!(!current_node.position.is_x_odd_y_odd() || !self.directions_to_grid_points(current_node.position).is_empty(), "The current position should not be an odd-odd point if there are no directions to grid points");
+            ensure_invariant!(!current_node.position.is_x_odd_y_odd() || !self.directions_to_grid_points(current_node.position)?.is_empty(), "The current position should not be an odd-odd point if there are no directions to grid points");
 
             let mut current_node_handled = false;
             let mut condition_count = 0;
 
 
             
-            let end_direction = self.is_aligned_with_end(current_node.position);
+            let end_direction = self.is_aligned_with_end(current_node.position)?;
             if let Some(end_direction) = end_direction {
-                assert_ne!(current_node.position, self.end, "assert 3");
+                ensure_invariant!(current_node.position != self.end, "assert 3");
                 if !self.check_collision(
                     current_node.position,
                     self.end,
@@ -1034,23 +1117,23 @@ impl AStarModel {
                     //     current_node.position.x, current_node.position.y, self.end.x, self.end.y
                     // );
                     condition_count = condition_count + 1;
-                    try_push_node_to_frontier(end_direction, self.end);
+                    try_push_node_to_frontier(end_direction, self.end)?;
                 }
             }
 
             // process grid points or one-step-to-grid-points
-            let directions = self.directions_to_grid_points(current_node.position);
-            assert!(directions.len() != 8 || self.is_grid_point(&current_node.position), "There should not be 8 directions to grid points if the current position is not a grid point");
+            let directions = self.directions_to_grid_points(current_node.position)?;
+            ensure_invariant!(directions.len() != 8 || self.is_grid_point(&current_node.position), "There should not be 8 directions to grid points if the current position is not a grid point");
             for (direction, end_position) in &directions {
                 current_node_handled = true;
-                assert_ne!(current_node.position, *end_position, "assert 5");
+                ensure_invariant!(current_node.position != *end_position, "assert 5");
 
-                let end_position = match self.clamp_by_collision(current_node.position, *end_position){
+                let end_position = match self.clamp_by_collision(current_node.position, *end_position)?{
                     Some(pos) => pos,
                     None => continue, // if clamping fails, skip this direction
                 };
                 condition_count = condition_count + 1;
-                try_push_node_to_frontier(*direction, end_position);
+                try_push_node_to_frontier(*direction, end_position)?;
                 // let max_length = FixedPoint::max(
                 //     (current_node.position.x - end_position.x).abs(),
                 //     (current_node.position.y - end_position.y).abs(),
@@ -1071,10 +1154,10 @@ impl AStarModel {
                 //         try_push_node_to_frontier(direction, temp_end.unwrap());
                 //     }
                 // }
-                if let None = self.is_aligned_with_end(current_node.position) {
-                    if let Some(intersection) = self.get_intersection_with_end_alignments(current_node.position, end_position){
+                if self.is_aligned_with_end(current_node.position)?.is_none() {
+                    if let Some(intersection) = self.get_intersection_with_end_alignments(current_node.position, end_position)? {
                         condition_count = condition_count + 1;
-                        try_push_node_to_frontier(*direction, intersection);
+                        try_push_node_to_frontier(*direction, intersection)?;
                     }
                 }
             }
@@ -1084,21 +1167,21 @@ impl AStarModel {
                 current_node_handled = true;
             }
             for direction in radial_directions {
-                assert!(current_node.position.is_sum_even());
+                ensure_invariant!(current_node.position.is_sum_even());
                 if !direction.is_diagonal() && current_node.position.is_x_odd_y_odd(){
                     // 如果当前点是奇数点，且方向不是对角线方向，则不考虑该方向
                     continue;
                 }
                 let end_position =
-                    self.to_nearest_one_step_point(&current_node.position, direction);
-                assert_ne!(current_node.position, end_position, "assert 6");
+                    self.to_nearest_one_step_point(&current_node.position, direction)?;
+                ensure_invariant!(current_node.position != end_position, "assert 6");
 
-                let end_position = match self.clamp_by_collision(current_node.position, end_position){
+                let end_position = match self.clamp_by_collision(current_node.position, end_position)?{
                     Some(pos) => pos,
                     None => continue, // if clamping fails, skip this direction
                 };
                 condition_count = condition_count + 1;
-                try_push_node_to_frontier(direction, end_position);
+                try_push_node_to_frontier(direction, end_position)?;
                 // let max_length = FixedPoint::max(
                 //     (current_node.position.x - end_position.x).abs(),
                 //     (current_node.position.y - end_position.y).abs(),
@@ -1119,21 +1202,21 @@ impl AStarModel {
                 //         try_push_node_to_frontier(direction, temp_end.unwrap());
                 //     }
                 // }
-                if let None = self.is_aligned_with_end(current_node.position) {
-                    if let Some(intersection) = self.get_intersection_with_end_alignments(current_node.position, end_position){
+                if self.is_aligned_with_end(current_node.position)?.is_none() {
+                    if let Some(intersection) = self.get_intersection_with_end_alignments(current_node.position, end_position)? {
                         condition_count = condition_count + 1;
-                        try_push_node_to_frontier(direction, intersection);
+                        try_push_node_to_frontier(direction, intersection)?;
                     }
                 }
             }
 
             if !current_node_handled {
                 let mut found_point = false;
-                for direction in Direction::all_directions() {
-                    assert!(!current_node.position.is_x_odd_y_odd());
+                for direction in self.allowed_directions() {
+                    ensure_invariant!(!current_node.position.is_x_odd_y_odd());
                     let end_position =
-                        self.to_nearest_one_step_point(&current_node.position, direction);
-                    assert_ne!(current_node.position, end_position, "assert 7");
+                        self.to_nearest_one_step_point(&current_node.position, direction)?;
+                    ensure_invariant!(current_node.position != end_position, "assert 7");
 
                     if !self.check_collision(
                         current_node.position,
@@ -1143,7 +1226,7 @@ impl AStarModel {
                     ) {
                         // println!("4: {}, {}", end_position.x, end_position.y);
                         condition_count = condition_count + 1;
-                        try_push_node_to_frontier(direction, end_position);
+                        try_push_node_to_frontier(direction, end_position)?;
                         found_point = true;
                         break;
                     }
@@ -1159,25 +1242,25 @@ impl AStarModel {
                         None => Direction::Up, // default direction if not set
                     };
                     let end_position =
-                        self.to_nearest_one_step_point(&current_node.position, direction);
-                    if let Some(end_position) = self.clamp_by_collision(current_node.position, end_position) {
+                        self.to_nearest_one_step_point(&current_node.position, direction)?;
+                    if let Some(end_position) = self.clamp_by_collision(current_node.position, end_position)? {
                         // println!("4.1: {}, {}", temp_end.unwrap().x, temp_end.unwrap().y);
                         condition_count = condition_count + 1;
-                        try_push_node_to_frontier(direction, end_position);
+                        try_push_node_to_frontier(direction, end_position)?;
                     } else {
                         // remove the tried direction
-                        let directions = Direction::all_directions().iter()
+                        let directions = self.allowed_directions().iter()
                             .filter(|&&d| d != direction && d != direction.opposite())
                             .cloned()
                             .collect::<Vec<_>>();
                         let mut found_point = false;
                         for direction in directions {
                             let end_position =
-                                self.to_nearest_one_step_point(&current_node.position, direction);
-                            if let Some(end_position) = self.clamp_by_collision(current_node.position, end_position) {
+                                self.to_nearest_one_step_point(&current_node.position, direction)?;
+                            if let Some(end_position) = self.clamp_by_collision(current_node.position, end_position)? {
                                 // println!("4.2: {}, {}", end_position.x, end_position.y);
                                 condition_count = condition_count + 1;
-                                try_push_node_to_frontier(direction, end_position);
+                                try_push_node_to_frontier(direction, end_position)?;
                                 found_point = true;
                                 break; // only try one direction
                             }
@@ -1195,17 +1278,20 @@ impl AStarModel {
             //     condition_count,
             //     frontier.len()
             // );
-            if DISPLAY_ASTAR {
+            if self.display {
                 self.display_and_block(pcb_render_model.clone(), &frontier); // display the initial state of the frontier
             }
         }
-        Err("No path found".to_string()) // no path found
+        Err(self.search_failure(&progress, AStarFailureReason::FrontierExhausted).into()) // no path found
     }
 }
 
+/// a node of the grid search is closed by its position and the direction it was entered from,
+/// because the cost and the legality of the next bend depend on that direction; the start has no direction
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct AstarNodeKey {
     pub position: FixedVec2,
+    pub direction: Option<Direction>,
 }
 
 pub struct AstarNode {
@@ -1219,52 +1305,33 @@ pub struct AstarNode {
 }
 
 impl AstarNode {
-    pub fn to_trace_path(&self, width: f32, clearance: f32) -> TracePath {
+    pub fn to_trace_path(&self, width: f32, clearance: f32) -> Result<TracePath, RoutingError> {
         let mut anchors = vec![self.position];
-        let mut directions = vec![self.direction.as_ref().unwrap().clone()]; // start with the direction of the first segment
+        let mut directions = vec![self.direction]; // start with the direction of the last segment
         let mut current_node = self.prev_node.clone();
         while let Some(node) = current_node {
             anchors.push(node.position);
-            if let Some(direction) = &node.direction {
-                directions.push(direction.clone());
+            if node.prev_node.is_some() {
+                directions.push(node.direction);
             }
             current_node = node.prev_node.clone();
         }
-        assert!(
-            anchors.len() == directions.len() + 1,
-            "The number of anchors should be one more than the number of directions"
-        );
+        ensure_invariant!(anchors.len() == directions.len() + 1, "The number of anchors should be one more than the number of directions");
         anchors.reverse(); // reverse the anchors to get the correct order
         directions.reverse(); // reverse the directions to get the correct order
-        let mut segments: Vec<TraceSegment> = Vec::new();
-        for i in 0..directions.len() {
-            let start = anchors[i];
-            let end = anchors[i + 1];
-            let direction = directions[i].clone();
-            assert_ne!(start, end, "Start and end positions should not be the same");
-            let segment = TraceSegment {
-                start,
-                end,
-                width,
-                clearance,
-            };
-            assert_eq!(
-                segment.get_direction(),
-                direction,
-                "The direction of the segment should match the direction of the node"
-            );
-            segments.push(segment);
+        let trace_path = TracePath::from_anchors(anchors, width, clearance)?;
+        for (segment, direction) in trace_path.segments.iter().zip(directions.iter()) {
+            // nodes of the any-angle search do not carry a direction
+            if let Some(direction) = direction {
+                ensure_invariant!(segment.get_direction() == Some(*direction), "The direction of the segment should match the direction of the node");
+            }
         }
-        let anchors = TraceAnchors(anchors);
-        assert!(
-            self.estimated_cost == 0.0,
-            "The estimated cost should be 0.0 for the trace path"
-        );
-        TracePath {
-            anchors,
-            segments,
+        // RoutingCost::estimated_cost must be 0.0 at the end
+        ensure_invariant!(self.estimated_cost == 0.0, "The estimated cost should be 0.0 for the trace path, got {}", self.estimated_cost);
+        Ok(TracePath {
             length: self.actual_length,
-        }
+            ..trace_path
+        })
     }
     pub fn to_renderables(
         &self,
@@ -1276,10 +1343,10 @@ impl AstarNode {
         // It assumes that the node has a direction and a position
         let opaque_color = [color[0], color[1], color[2], 1.0]; // make the color opaque
         let transparent_color = [color[0], color[1], color[2], 0.5]; // make the color transparent
-        if let Some(direction) = &self.direction {
-            // If the node has a direction, we can create a TraceSegment
+        if let Some(prev_node) = &self.prev_node {
+            // If the node has a previous node, we can create a TraceSegment
             let trace_segment = TraceSegment {
-                start: self.prev_node.as_ref().unwrap().position,
+                start: prev_node.position,
                 end: self.position,
                 width,
                 clearance,
@@ -0,0 +1,198 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    pcb_problem::{
+        ConnectionID, FixedTrace, Node, PcbProblem, PcbSolution, UnroutedReason,
+        reason_without_candidates, unroutable_without_candidates,
+    },
+    pcb_render_model::PcbRenderModel,
+    routing_error::RoutingError,
+    solver_config::SolverConfig,
+    stop_condition::{StopCondition, StopReason},
+};
+
+/// the posteriors of the fixed traces plus the best remaining posterior of every other connection,
+/// only the candidates that fit next to the fixed traces count
+fn beam_score(node: &Node, problem: &PcbProblem) -> f64 {
+    let mut best_posteriors: BTreeMap<ConnectionID, f64> = BTreeMap::new();
+    for candidate in node.remaining_trace_candidates.iter() {
+        let proba_trace = &candidate.value;
+        let posterior = candidate.key.into_inner();
+        if best_posteriors
+            .get(&proba_trace.connection_id)
+            .is_some_and(|best_posterior| *best_posterior >= posterior)
+        {
+            continue;
+        }
+        let collides = node.fixed_traces.values().any(|fixed_trace| {
+            fixed_trace.net_id != proba_trace.net_id
+                && proba_trace.trace_path.collides_with_clearance(
+                    &fixed_trace.trace_path,
+                    problem.clearance_between_nets(proba_trace.net_id, fixed_trace.net_id),
+                )
+        });
+        if !collides {
+            best_posteriors.insert(proba_trace.connection_id, posterior);
+        }
+    }
+    node.fixed_score + best_posteriors.values().sum::<f64>()
+}
+
+/// at most beam_width children of the node, each fixes the next candidate that fits
+fn expand(node: &Node, problem: &PcbProblem, beam_width: usize) -> Vec<Node> {
+    // the popped candidates stay popped, so every child excludes the candidates of its older siblings
+    let mut remaining_node = node.clone();
+    let mut children: Vec<Node> = Vec::new();
+    while children.len() < beam_width {
+        match remaining_node.try_fix_any_trace(problem) {
+            Some(child) => children.push(child),
+            None => break,
+        }
+    }
+    children
+}
+
+impl PcbProblem {
+    /// the breadth-first search that keeps the beam_width best nodes of every depth
+    pub(crate) fn solve_with_beam_search(
+        &self,
+        locked_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
+        beam_width: usize,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<PcbSolution, RoutingError> {
+        if beam_width == 0 {
+            return Err(RoutingError::InvalidInput(
+                "The beam search needs a beam width of at least one".to_string(),
+            ));
+        }
+        let first_node = Node::from_fixed_traces(
+            self,
+            locked_traces,
+            config,
+            stop_condition,
+            pcb_render_model.clone(),
+        )?;
+        // the candidates of a stopped sampling are incomplete, so the connections without candidates are unknown,
+        // the candidates that fit are the best effort
+        if let Some(stop_reason) = stop_condition.check() {
+            let fixed_traces = first_node.greedy_fixed_traces(self);
+            return self.stopped_search(config, fixed_traces, BTreeMap::new(), stop_reason);
+        }
+        let unroutable_connection_ids = first_node.connections_without_candidates(self);
+        if !unroutable_connection_ids.is_empty() && !config.allow_partial {
+            return Err(unroutable_without_candidates(
+                unroutable_connection_ids,
+                &first_node.astar_failures,
+            ));
+        }
+        let unrouted_connections: BTreeMap<ConnectionID, UnroutedReason> =
+            unroutable_connection_ids
+                .iter()
+                .map(|connection_id| {
+                    let reason =
+                        reason_without_candidates(connection_id, &first_node.astar_failures);
+                    (*connection_id, reason)
+                })
+                .collect();
+        let unroutable_connection_ids: BTreeSet<ConnectionID> =
+            unroutable_connection_ids.into_iter().collect();
+        // the best node of the deepest beam explains which connections could not be routed
+        let mut max_fixed_traces: BTreeMap<ConnectionID, FixedTrace> = BTreeMap::new();
+        // the nodes of a beam fix the same number of traces, sorted by their scores
+        let mut beam: Vec<Node> = vec![first_node];
+
+        let mut depth: usize = 0;
+        while !beam.is_empty() {
+            if let Some(solution_node) = beam
+                .iter()
+                .find(|node| node.is_solution_except(self, &unroutable_connection_ids))
+            {
+                println!("Found a solution at depth {} of the beam search", depth);
+                return Ok(PcbSolution {
+                    determined_traces: solution_node.fixed_traces.clone(),
+                    unrouted_connections,
+                });
+            }
+            depth += 1;
+            let stop_reason = stop_condition.check().or_else(|| {
+                config
+                    .max_solver_iterations
+                    .filter(|max_iterations| depth > *max_iterations)
+                    .map(|_| StopReason::IterationLimitReached)
+            });
+            if let Some(stop_reason) = stop_reason {
+                println!("Stopping the beam search: {:?}", stop_reason);
+                return self.stopped_search(
+                    config,
+                    max_fixed_traces,
+                    unrouted_connections,
+                    stop_reason,
+                );
+            }
+            let mut scored_children: Vec<(f64, Node)> = Vec::new();
+            for node in beam.iter() {
+                for child in expand(node, self, beam_width) {
+                    // the same traces may be fixed in another order by another node
+                    if scored_children
+                        .iter()
+                        .any(|(_, other_child)| other_child.fixed_traces == child.fixed_traces)
+                    {
+                        continue;
+                    }
+                    scored_children.push((beam_score(&child, self), child));
+                }
+            }
+            scored_children.sort_by(|(score1, _), (score2, _)| score2.total_cmp(score1));
+            scored_children.truncate(beam_width);
+            let Some((best_score, best_child)) = scored_children.first() else {
+                println!("No node of the beam can fix another trace");
+                break;
+            };
+            println!(
+                "Beam search depth {}: {} nodes, best score {:.3}",
+                depth,
+                scored_children.len(),
+                best_score
+            );
+            max_fixed_traces = best_child.fixed_traces.clone();
+
+            // resample the model of the survivors with their fixed traces as obstacles
+            let mut scored_beam: Vec<(f64, Node)> = Vec::new();
+            for (score, child) in scored_children {
+                if child.is_solution_except(self, &unroutable_connection_ids) {
+                    scored_beam.push((score, child));
+                    continue;
+                }
+                let updated_node = child
+                    .try_update_proba_model(self, config, stop_condition, pcb_render_model.clone())?
+                    .unwrap_or(child);
+                if let Some(stop_reason) = stop_condition.check() {
+                    return self.stopped_search(
+                        config,
+                        max_fixed_traces,
+                        unrouted_connections,
+                        stop_reason,
+                    );
+                }
+                // a connection that lost all of its candidates cannot be routed from this node
+                if updated_node
+                    .connections_without_candidates(self)
+                    .iter()
+                    .any(|connection_id| !unroutable_connection_ids.contains(connection_id))
+                {
+                    println!("Dropping a node that left a connection without candidates");
+                    continue;
+                }
+                scored_beam.push((beam_score(&updated_node, self), updated_node));
+            }
+            scored_beam.sort_by(|(score1, _), (score2, _)| score2.total_cmp(score1));
+            beam = scored_beam.into_iter().map(|(_, node)| node).collect();
+        }
+        self.failed_search(config, max_fixed_traces, unrouted_connections)
+    }
+}
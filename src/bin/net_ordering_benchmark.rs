@@ -0,0 +1,86 @@
+//! Compares the success rates of the net orderings on random problems.
+//! usage: net_ordering_benchmark [--display] [num_problems] [num_nets] [negotiation iterations]
+//! --display renders every A* expansion as SolverConfig::display_astar does, which makes the runs slow.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use pcb_routing_wgpu3::{
+    net_ordering::NetOrdering,
+    pcb_render_model::PcbRenderModel,
+    solver_config::{SolverConfig, SolverStrategy},
+    test_pcb_problem::random_pcb_problem,
+};
+
+fn main() {
+    let display = std::env::args().any(|arg| arg == "--display");
+    let args: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter(|arg| arg != "--display")
+        .map(|arg| {
+            arg.parse()
+                .expect("the arguments must be non-negative integers")
+        })
+        .collect();
+    let num_problems = args.first().cloned().unwrap_or(20);
+    let num_nets = args.get(1).cloned().unwrap_or(6);
+    let max_iterations = args.get(2).cloned().unwrap_or(10);
+    println!(
+        "{} random problems with {} nets, negotiation with {} iterations",
+        num_problems, num_nets, max_iterations
+    );
+
+    let mut rows: Vec<String> = Vec::new();
+    for net_ordering in NetOrdering::ALL {
+        let mut num_complete = 0;
+        let mut num_routed_connections = 0;
+        let mut num_connections = 0;
+        let mut total_duration = Duration::ZERO;
+        for seed in 0..num_problems as u64 {
+            let problem = random_pcb_problem(seed, num_nets);
+            let config = SolverConfig {
+                strategy: SolverStrategy::Negotiation { max_iterations },
+                net_ordering,
+                allow_partial: true,
+                seed: Some(seed),
+                display_astar: display,
+                ..SolverConfig::default()
+            };
+            let start = Instant::now();
+            let result =
+                problem.solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())));
+            total_duration += start.elapsed();
+            let total_connections: usize = problem
+                .nets
+                .values()
+                .map(|net_info| net_info.connections.len())
+                .sum();
+            num_connections += total_connections;
+            match result {
+                Ok(solution) => {
+                    if solution.is_complete() {
+                        num_complete += 1;
+                    }
+                    num_routed_connections += solution.determined_traces.len();
+                }
+                Err(error) => println!("{:?} failed on problem {}: {}", net_ordering, seed, error),
+            }
+        }
+        rows.push(format!(
+            "{:<22}{:>9.1}%{:>9.1}%{:>12.3?}",
+            format!("{:?}", net_ordering),
+            100.0 * num_complete as f64 / num_problems.max(1) as f64,
+            100.0 * num_routed_connections as f64 / num_connections.max(1) as f64,
+            total_duration / num_problems.max(1) as u32,
+        ));
+    }
+    println!(
+        "{:<22}{:>10}{:>10}{:>12}",
+        "ordering", "complete", "routed", "mean time"
+    );
+    for row in rows {
+        println!("{}", row);
+    }
+}
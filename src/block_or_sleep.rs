@@ -1,20 +1,56 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::hyperparameters::BLOCK_POLL_MILLIS;
 use crate::hyperparameters::BLOCK_THREAD;
 use crate::hyperparameters::DISPLAY_PERIOD_MILLIS;
+use crate::stop_condition::StopCondition;
 
+// one thread reads stdin for every blocked thread, so that a blocked thread can stop waiting when the solve stops
+static ENTER_PRESSES: OnceLock<Mutex<Receiver<()>>> = OnceLock::new();
 
+/// starts the thread that reads the Enter presses from stdin, which block_thread waits for
+/// only the app starts it, without it block_thread returns at once
+pub fn start_enter_reader() {
+    ENTER_PRESSES.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            let mut input = String::new();
+            match std::io::stdin().read_line(&mut input) {
+                Ok(0) | Err(_) => break, // the end of the input, e.g. stdin is not a terminal
+                Ok(_) => {
+                    if sender.send(()).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Mutex::new(receiver)
+    });
+}
 
-pub fn block_or_sleep(){
+pub fn block_or_sleep(stop_condition: &StopCondition){
     if BLOCK_THREAD{
-        block_thread();
+        block_thread(stop_condition);
     } else {
         // Sleep for a short duration
         std::thread::sleep(std::time::Duration::from_millis(DISPLAY_PERIOD_MILLIS));
     }
 }
 
-pub fn block_thread(){
-    // Block the thread until a key is pressed
-    let mut input = String::new();
+/// blocks the thread until Enter is pressed or the solve stops, e.g. because the window was closed
+pub fn block_thread(stop_condition: &StopCondition){
+    let Some(enter_presses) = ENTER_PRESSES.get() else {
+        return; // nobody reads stdin, see start_enter_reader
+    };
     println!("Press Enter to continue...");
-    std::io::stdin().read_line(&mut input).unwrap();
+    let enter_presses = enter_presses.lock().unwrap();
+    while stop_condition.check().is_none() {
+        match enter_presses.recv_timeout(Duration::from_millis(BLOCK_POLL_MILLIS)) {
+            Ok(()) => break,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break, // nobody can press Enter
+        }
+    }
 }
\ No newline at end of file
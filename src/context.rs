@@ -5,7 +5,7 @@ use std::{
 
 use crate::{
     input_context::InputContext, pcb_render_model::PcbRenderModel, render_context::RenderContext,
-    state::State,
+    state::State, stop_condition::CancelToken,
 };
 
 #[derive(Default)]
@@ -15,4 +15,5 @@ pub struct Context {
     pub input_context: RefCell<InputContext>,
     pub pcb_render_model: Arc<Mutex<PcbRenderModel>>,
     pub working_thread: Arc<Mutex<Option<std::thread::JoinHandle<()>>>>,
+    pub cancel_token: CancelToken, // stops the solver of the working thread
 }
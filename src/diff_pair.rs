@@ -0,0 +1,489 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    astar::{AStarFailure, AStarModel},
+    pcb_problem::{Connection, ConnectionID, FixedTrace, PcbProblem, PcbSolution, UnroutedReason},
+    pcb_render_model::PcbRenderModel,
+    routing_error::RoutingError,
+    solver_config::SolverConfig,
+    stop_condition::StopCondition,
+    trace_path::TracePath,
+    vec2::{FixedPoint, FixedVec2, FloatVec2},
+};
+
+/// why a differential pair could not be routed together
+#[derive(Debug, Clone)]
+enum DiffPairFailure {
+    SearchFailed(AStarFailure), // the A* search of the centerline failed
+    Conflicting,                // the offset traces collide or exceed a max length
+    Error(RoutingError),        // an error that aborts the whole solve
+}
+
+impl From<RoutingError> for DiffPairFailure {
+    fn from(error: RoutingError) -> Self {
+        DiffPairFailure::Error(error)
+    }
+}
+
+#[derive(Copy, Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
+pub struct DiffPairID(pub usize);
+
+/// two connections of different nets, e.g. USB D+ and D-, routed side by side at a fixed gap
+#[derive(Debug, Clone)]
+pub struct DiffPair {
+    pub diff_pair_id: DiffPairID,
+    pub positive_connection_id: ConnectionID,
+    pub negative_connection_id: ConnectionID,
+    pub trace_width: f32, // the width of both traces, instead of the widths of the connections
+    pub gap: f32,         // the distance between the edges of the two traces
+}
+
+impl DiffPair {
+    /// the width of both traces and the gap between them, the width of the centerline search
+    pub fn envelope_width(&self) -> f32 {
+        2.0 * self.trace_width + self.gap
+    }
+
+    /// the distance of each trace from the centerline
+    pub fn half_pitch(&self) -> f32 {
+        (self.trace_width + self.gap) / 2.0
+    }
+}
+
+fn midpoint(position1: FloatVec2, position2: FloatVec2) -> FloatVec2 {
+    FloatVec2 {
+        x: (position1.x + position2.x) / 2.0,
+        y: (position1.y + position2.y) / 2.0,
+    }
+}
+
+fn add(position: FloatVec2, offset: FloatVec2, scale: f32) -> FloatVec2 {
+    FloatVec2 {
+        x: position.x + offset.x * scale,
+        y: position.y + offset.y * scale,
+    }
+}
+
+fn cross(vector1: FloatVec2, vector2: FloatVec2) -> f32 {
+    vector1.x * vector2.y - vector1.y * vector2.x
+}
+
+/// the middle of the two pads of the pair and the point where the pair splits to reach them,
+/// as far from the middle as the pads are apart, perpendicular to the line between the pads and towards the target
+fn breakout(
+    position1: FloatVec2,
+    position2: FloatVec2,
+    target: FloatVec2,
+) -> (FloatVec2, FloatVec2) {
+    let middle = midpoint(position1, position2);
+    let pad_axis = position1.sub(position2);
+    let mut direction = pad_axis.normalize().perp();
+    if direction.dot(target.sub(middle)) < 0.0 {
+        direction = FloatVec2 {
+            x: -direction.x,
+            y: -direction.y,
+        };
+    }
+    let pad_distance = pad_axis.dot(pad_axis).sqrt();
+    (middle, add(middle, direction, pad_distance))
+}
+
+/// the anchors without those in the middle of a straight run, which the offset would turn into needlessly short segments
+fn without_collinear_anchors(anchors: Vec<FloatVec2>) -> Vec<FloatVec2> {
+    let mut kept_anchors: Vec<FloatVec2> = Vec::new();
+    for anchor in anchors {
+        if let [.., before, last] = kept_anchors[..] {
+            let direction_before = last.sub(before);
+            let direction_after = anchor.sub(last);
+            let is_straight = cross(direction_before, direction_after).abs()
+                <= 1e-3 * direction_before.dot(direction_before).sqrt()
+                && direction_before.dot(direction_after) > 0.0;
+            if is_straight {
+                kept_anchors.pop();
+            }
+        }
+        kept_anchors.push(anchor);
+    }
+    kept_anchors
+}
+
+/// the anchors of the centerline moved by distance to its left, a negative distance moves them to the right
+/// the corners are mitered, so that every segment stays parallel to its centerline segment at the same distance
+/// None if the centerline turns back on itself or a segment is too short for the corners at its ends
+fn offset_anchors(centerline: &[FloatVec2], distance: f32) -> Option<Vec<FloatVec2>> {
+    let normals: Vec<FloatVec2> = centerline
+        .windows(2)
+        .map(|pair| pair[1].sub(pair[0]).normalize().perp())
+        .collect();
+    let mut anchors: Vec<FloatVec2> = Vec::new();
+    for (index, anchor) in centerline.iter().enumerate() {
+        let normal_before = normals[index.saturating_sub(1)];
+        let normal_after = normals[index.min(normals.len() - 1)];
+        let miter = FloatVec2 {
+            x: normal_before.x + normal_after.x,
+            y: normal_before.y + normal_after.y,
+        };
+        let denominator = 1.0 + normal_before.dot(normal_after);
+        if denominator < f32::EPSILON {
+            return None;
+        }
+        anchors.push(add(*anchor, miter, distance / denominator));
+    }
+    // the offset segment points the other way when the miters of its ends overlap
+    let reversed = anchors
+        .windows(2)
+        .zip(centerline.windows(2))
+        .any(|(offset_pair, pair)| {
+            offset_pair[1].sub(offset_pair[0]).dot(pair[1].sub(pair[0])) <= 0.0
+        });
+    (!reversed).then_some(anchors)
+}
+
+/// the anchors from the pad to the start of the offset line, along it, and from its end to the other pad
+fn breakout_anchors(
+    source: FloatVec2,
+    offset_anchors: Vec<FloatVec2>,
+    sink: FloatVec2,
+) -> Vec<FixedVec2> {
+    let mut anchors: Vec<FixedVec2> = Vec::new();
+    for anchor in [source].into_iter().chain(offset_anchors).chain([sink]) {
+        let anchor = anchor.to_fixed();
+        if anchors.last() != Some(&anchor) {
+            anchors.push(anchor);
+        }
+    }
+    anchors
+}
+
+impl PcbProblem {
+    /// declares that the two connections are routed as a differential pair with the trace width and gap
+    pub fn add_diff_pair(
+        &mut self,
+        positive_connection_id: ConnectionID,
+        negative_connection_id: ConnectionID,
+        trace_width: f32,
+        gap: f32,
+    ) -> Result<DiffPairID, RoutingError> {
+        let positive_connection = self.connection(positive_connection_id)?;
+        let negative_connection = self.connection(negative_connection_id)?;
+        if positive_connection.net_id == negative_connection.net_id {
+            return Err(RoutingError::InvalidInput(format!(
+                "The connections {:?} and {:?} of a differential pair must belong to different nets",
+                positive_connection_id, negative_connection_id
+            )));
+        }
+        for connection_id in [positive_connection_id, negative_connection_id] {
+            if let Some(diff_pair) = self.diff_pair_of(connection_id) {
+                return Err(RoutingError::InvalidInput(format!(
+                    "ConnectionID {:?} already belongs to the differential pair {:?}",
+                    connection_id, diff_pair.diff_pair_id
+                )));
+            }
+        }
+        let diff_pair_id = self
+            .diff_pair_id_generator
+            .next()
+            .expect("DiffPairID generator exhausted");
+        self.diff_pairs.insert(
+            diff_pair_id,
+            DiffPair {
+                diff_pair_id,
+                positive_connection_id,
+                negative_connection_id,
+                trace_width,
+                gap,
+            },
+        );
+        Ok(diff_pair_id)
+    }
+
+    /// the differential pair that the connection belongs to, if any
+    pub fn diff_pair_of(&self, connection_id: ConnectionID) -> Option<&DiffPair> {
+        self.diff_pairs.values().find(|diff_pair| {
+            diff_pair.positive_connection_id == connection_id
+                || diff_pair.negative_connection_id == connection_id
+        })
+    }
+
+    pub(crate) fn connection(
+        &self,
+        connection_id: ConnectionID,
+    ) -> Result<&Arc<Connection>, RoutingError> {
+        self.nets
+            .values()
+            .find_map(|net_info| net_info.connections.get(&connection_id))
+            .ok_or_else(|| {
+                RoutingError::InvalidInput(format!("ConnectionID {:?} not found", connection_id))
+            })
+    }
+
+    /// routes the differential pairs before the other connections, each pair after the locked traces and the pairs before it
+    /// the determined traces of the result are the locked traces and those of the pairs, its unrouted connections
+    /// are those of the pairs that failed, which stay unrouted in the partial mode instead of being routed apart
+    pub(crate) fn route_diff_pairs(
+        &self,
+        locked_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<PcbSolution, RoutingError> {
+        let mut routed_traces = locked_traces.clone();
+        let mut unrouted_connections: BTreeMap<ConnectionID, UnroutedReason> = BTreeMap::new();
+        for diff_pair in self.diff_pairs.values() {
+            let connection_ids = [
+                diff_pair.positive_connection_id,
+                diff_pair.negative_connection_id,
+            ];
+            if connection_ids
+                .iter()
+                .any(|connection_id| locked_traces.contains_key(connection_id))
+            {
+                println!(
+                    "Differential pair {:?} has a locked trace, its connections are not routed together",
+                    diff_pair.diff_pair_id
+                );
+                continue;
+            }
+            let reason = if let Some(stop_reason) = stop_condition.check() {
+                if !config.allow_partial {
+                    return self.stopped_search(
+                        config,
+                        routed_traces,
+                        unrouted_connections,
+                        stop_reason,
+                    );
+                }
+                UnroutedReason::Stopped(stop_reason)
+            } else {
+                match self.route_diff_pair(
+                    diff_pair,
+                    &routed_traces,
+                    config,
+                    stop_condition,
+                    pcb_render_model.clone(),
+                ) {
+                    Ok((positive_trace, negative_trace)) => {
+                        println!("Routed differential pair {:?}", diff_pair.diff_pair_id);
+                        routed_traces.insert(diff_pair.positive_connection_id, positive_trace);
+                        routed_traces.insert(diff_pair.negative_connection_id, negative_trace);
+                        continue;
+                    }
+                    Err(DiffPairFailure::Conflicting) if !config.allow_partial => {
+                        return Err(RoutingError::UnroutableConnections {
+                            connection_ids: connection_ids.to_vec(),
+                            reason: format!(
+                                "the traces of differential pair {:?} collide with each other or an obstacle after the offset from the centerline, or exceed a max length",
+                                diff_pair.diff_pair_id
+                            ),
+                        });
+                    }
+                    Err(DiffPairFailure::Conflicting) => UnroutedReason::Conflicting,
+                    Err(DiffPairFailure::Error(error)) => return Err(error),
+                    Err(DiffPairFailure::SearchFailed(failure)) => {
+                        if let Some(stop_reason) = stop_condition.check() {
+                            if !config.allow_partial {
+                                return self.stopped_search(
+                                    config,
+                                    routed_traces,
+                                    unrouted_connections,
+                                    stop_reason,
+                                );
+                            }
+                            UnroutedReason::Stopped(stop_reason)
+                        } else if config.allow_partial {
+                            UnroutedReason::SearchFailed(failure.reason)
+                        } else {
+                            return Err(RoutingError::UnroutableConnections {
+                                connection_ids: connection_ids.to_vec(),
+                                reason: format!(
+                                    "the centerline of differential pair {:?}: {}",
+                                    diff_pair.diff_pair_id, failure
+                                ),
+                            });
+                        }
+                    }
+                }
+            };
+            println!(
+                "Differential pair {:?} is not routed: {}",
+                diff_pair.diff_pair_id, reason
+            );
+            for connection_id in connection_ids {
+                unrouted_connections.insert(connection_id, reason.clone());
+            }
+        }
+        Ok(PcbSolution {
+            determined_traces: routed_traces,
+            unrouted_connections,
+        })
+    }
+
+    /// runs A* for the centerline of the pair, as wide as both traces and the gap, from the middle of the source pads
+    /// to the middle of the sink pads, then offsets it to both sides and connects the offset lines to the pads
+    /// the pair is conflicting if the offset traces collide, e.g. where the pads of the pair are swapped between source and sink,
+    /// or if one of them is longer than the max length of its connection
+    fn route_diff_pair(
+        &self,
+        diff_pair: &DiffPair,
+        routed_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<(FixedTrace, FixedTrace), DiffPairFailure> {
+        let positive_connection = self.connection(diff_pair.positive_connection_id)?;
+        let negative_connection = self.connection(diff_pair.negative_connection_id)?;
+        let net_ids = [positive_connection.net_id, negative_connection.net_id];
+        let (mut obstacle_shapes, mut obstacle_clearance_shapes) =
+            self.pad_obstacle_shapes(&net_ids);
+        let (trace_shapes, trace_clearance_shapes) =
+            self.trace_obstacle_shapes(&net_ids, routed_traces.values());
+        obstacle_shapes.extend(trace_shapes);
+        obstacle_clearance_shapes.extend(trace_clearance_shapes);
+        let routing_mode = self
+            .nets
+            .get(&positive_connection.net_id)
+            .ok_or_else(|| {
+                RoutingError::InternalInvariant(format!(
+                    "NetID {:?} not found in nets",
+                    positive_connection.net_id
+                ))
+            })?
+            .routing_mode;
+        let mut astar_model = AStarModel {
+            width: self.width,
+            height: self.height,
+            obstacle_shapes,
+            obstacle_clearance_shapes,
+            start: FixedVec2::new(FixedPoint::ZERO, FixedPoint::ZERO), // set below
+            end: FixedVec2::new(FixedPoint::ZERO, FixedPoint::ZERO),
+            trace_width: diff_pair.envelope_width(),
+            trace_clearance: self
+                .trace_clearance(positive_connection)
+                .max(self.trace_clearance(negative_connection)),
+            routing_mode,
+            routing_cost: self.routing_cost.clone(),
+            search_budget: config.astar_search_budget,
+            stop_condition: stop_condition.clone(),
+            border_cache: RefCell::new(None),
+            // the centerline is about as long as the traces, which are checked against their own limits below
+            max_length: [positive_connection, negative_connection]
+                .iter()
+                .filter_map(|connection| connection.length_limits.max_length)
+                .reduce(f32::min)
+                .map(|max_length| max_length as f64),
+            display: config.display_astar,
+        };
+        let (source_middle, source_breakout) = breakout(
+            positive_connection.source.position,
+            negative_connection.source.position,
+            midpoint(
+                positive_connection.sink.position,
+                negative_connection.sink.position,
+            ),
+        );
+        let (sink_middle, sink_breakout) = breakout(
+            positive_connection.sink.position,
+            negative_connection.sink.position,
+            source_middle,
+        );
+        astar_model.start = astar_model.to_search_point(source_breakout);
+        astar_model.end = astar_model.to_search_point(sink_breakout);
+        let centerline = match astar_model.run(pcb_render_model) {
+            Ok(astar_result) => astar_result.trace_path,
+            Err(
+                RoutingError::SearchBudgetExhausted(failure) | RoutingError::NoPathFound(failure),
+            ) => return Err(DiffPairFailure::SearchFailed(failure)),
+            Err(error) => return Err(DiffPairFailure::Error(error)),
+        };
+        // the pair leaves the pads side by side before the search takes over
+        let centerline: Vec<FloatVec2> = without_collinear_anchors(
+            [source_middle]
+                .into_iter()
+                .chain(centerline.anchors.0.iter().map(|anchor| anchor.to_float()))
+                .chain([sink_middle])
+                .collect(),
+        );
+
+        // the positive trace runs on the side of its source pad, the pair cannot cross to reach swapped sink pads
+        let side = |middle: FloatVec2, next: FloatVec2, position: FloatVec2| {
+            cross(next.sub(middle), position.sub(middle)) >= 0.0
+        };
+        let positive_on_left = side(
+            centerline[0],
+            centerline[1],
+            positive_connection.source.position,
+        );
+        let last_index = centerline.len() - 1;
+        if side(
+            centerline[last_index],
+            centerline[last_index - 1],
+            positive_connection.sink.position,
+        ) == positive_on_left
+        {
+            return Err(DiffPairFailure::Conflicting);
+        }
+        let distance = if positive_on_left {
+            diff_pair.half_pitch()
+        } else {
+            -diff_pair.half_pitch()
+        };
+        let (Some(positive_anchors), Some(negative_anchors)) = (
+            offset_anchors(&centerline, distance),
+            offset_anchors(&centerline, -distance),
+        ) else {
+            return Err(DiffPairFailure::Conflicting);
+        };
+        let to_fixed_trace = |connection: &Connection, offset_anchors: Vec<FloatVec2>| {
+            Ok::<_, RoutingError>(FixedTrace {
+                net_id: connection.net_id,
+                connection_id: connection.connection_id,
+                trace_path: TracePath::from_anchors(
+                    breakout_anchors(
+                        connection.source.position,
+                        offset_anchors,
+                        connection.sink.position,
+                    ),
+                    diff_pair.trace_width,
+                    self.trace_clearance(connection),
+                )?,
+            })
+        };
+        let positive_trace = to_fixed_trace(positive_connection, positive_anchors)?;
+        let negative_trace = to_fixed_trace(negative_connection, negative_anchors)?;
+
+        if [
+            (positive_connection, &positive_trace),
+            (negative_connection, &negative_trace),
+        ]
+        .iter()
+        .any(|(connection, pair_trace)| {
+            connection
+                .length_limits
+                .exceeds_max_length(pair_trace.trace_path.length)
+        }) {
+            return Err(DiffPairFailure::Conflicting);
+        }
+        // the breakouts and the outer corners leave the envelope of the centerline search
+        let collides = positive_trace.trace_path.collides_with_clearance(
+            &negative_trace.trace_path,
+            self.clearance_between_nets(net_ids[0], net_ids[1]),
+        ) || [&positive_trace, &negative_trace].iter().any(|pair_trace| {
+            self.trace_collides_with_pads(pair_trace.net_id, &pair_trace.trace_path)
+                || routed_traces.values().any(|routed_trace| {
+                    routed_trace.net_id != pair_trace.net_id
+                        && pair_trace.trace_path.collides_with_clearance(
+                            &routed_trace.trace_path,
+                            self.clearance_between_nets(pair_trace.net_id, routed_trace.net_id),
+                        )
+                })
+        });
+        if collides {
+            return Err(DiffPairFailure::Conflicting);
+        }
+        Ok((positive_trace, negative_trace))
+    }
+}
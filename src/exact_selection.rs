@@ -0,0 +1,327 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    pcb_problem::{ConnectionID, ProbaModel, ProbaTrace, ProbaTraceID, Traces},
+    routing_error::RoutingError,
+    stop_condition::StopCondition,
+};
+
+/// the outcome of choosing one candidate trace per connection from the pool of a ProbaModel
+#[derive(Debug, Clone)]
+pub enum ExactSelection {
+    /// a collision-free candidate for every connection, with the smallest total cost if optimal
+    /// if unrouted connections are allowed, as many connections as possible are routed first
+    Assignment {
+        traces: BTreeMap<ConnectionID, Arc<ProbaTrace>>,
+        unrouted: Vec<ConnectionID>, // empty unless unrouted connections are allowed
+        total_cost: f64,             // the sum of the trace costs, see trace_cost
+        optimal: bool,               // false if the node budget ran out before the search finished
+        explored_nodes: usize,
+    },
+    /// no collision-free assignment exists within the candidate pool
+    Infeasible(InfeasibilityProof),
+    /// the node budget ran out before any assignment was found
+    BudgetExhausted { explored_nodes: usize },
+}
+
+/// A set of connections whose candidates cannot be chosen without a collision,
+/// verified by an exhaustive search over the candidates of only these connections.
+/// If minimal, removing any of the connections makes the rest assignable.
+#[derive(Debug, Clone)]
+pub struct InfeasibilityProof {
+    pub connection_ids: Vec<ConnectionID>,
+    pub minimal: bool, // false if the node budget ran out while shrinking the set
+    pub explored_nodes: usize, // nodes of all searches, including the minimization of the set
+}
+
+/// the cost that the selection minimizes, the length plus the bend penalties of the trace, and the penalty of a trace below its min length
+pub fn trace_cost(proba_trace: &ProbaTrace) -> Result<f64, RoutingError> {
+    // the score decays exponentially with the cost, so its negative logarithm is proportional to the cost
+    Ok(-proba_trace
+        .trace_path
+        .get_score(&proba_trace.length_limits)?
+        .ln())
+}
+
+/// the candidates of a connection, sorted by cost
+struct Variable {
+    candidates: Vec<usize>, // indices into BranchAndBound::candidates
+}
+
+struct Candidate {
+    connection_id: ConnectionID,
+    proba_trace: Option<Arc<ProbaTrace>>, // None for leaving the connection unrouted
+    cost: f64,
+    conflicts: Vec<usize>, // candidates of other variables that collide with this one
+}
+
+/// depth-first branch-and-bound over the variables, choosing the most constrained variable first
+/// the bound adds the cheapest still compatible candidate of every unassigned variable
+struct BranchAndBound {
+    variables: Vec<Variable>,
+    candidates: Vec<Candidate>,
+    max_nodes: usize,
+    stop_at_first: bool,           // only decide whether an assignment exists
+    stop_condition: StopCondition, // stops the search like the node budget
+    explored_nodes: usize,
+    budget_exhausted: bool,
+    blocked_by: Vec<usize>, // per candidate, the number of assigned candidates colliding with it
+    assignment: Vec<Option<usize>>, // per variable, the assigned candidate
+    best: Option<(f64, Vec<usize>)>,
+}
+
+impl BranchAndBound {
+    fn new(
+        proba_model: &ProbaModel,
+        connection_ids: &[ConnectionID],
+        max_nodes: usize,
+        stop_at_first: bool,
+        allow_unrouted: bool,
+        stop_condition: &StopCondition,
+    ) -> Result<Self, RoutingError> {
+        let proba_traces = proba_model.probabilistic_traces();
+        let mut candidate_indices: BTreeMap<ProbaTraceID, usize> = BTreeMap::new();
+        let mut candidates: Vec<Candidate> = Vec::new();
+        let mut variables: Vec<Variable> = Vec::new();
+        for connection_id in connection_ids.iter() {
+            let mut connection_traces: Vec<(f64, &Arc<ProbaTrace>)> = proba_traces
+                .values()
+                .filter(|proba_trace| proba_trace.connection_id == *connection_id)
+                .map(|proba_trace| Ok((trace_cost(proba_trace)?, proba_trace)))
+                .collect::<Result<_, RoutingError>>()?;
+            connection_traces.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            let mut variable_candidates: Vec<usize> = Vec::new();
+            for (cost, proba_trace) in connection_traces {
+                candidate_indices.insert(proba_trace.proba_trace_id, candidates.len());
+                variable_candidates.push(candidates.len());
+                candidates.push(Candidate {
+                    connection_id: *connection_id,
+                    proba_trace: Some(proba_trace.clone()),
+                    cost,
+                    conflicts: Vec::new(),
+                });
+            }
+            variables.push(Variable {
+                candidates: variable_candidates,
+            });
+        }
+        if allow_unrouted {
+            // more than the routed costs of all connections together, so that routing one more connection always wins
+            let unrouted_cost = 1.0
+                + variables
+                    .iter()
+                    .filter_map(|variable| variable.candidates.last())
+                    .map(|candidate| candidates[*candidate].cost)
+                    .sum::<f64>();
+            for (variable, connection_id) in variables.iter_mut().zip(connection_ids.iter()) {
+                // the most expensive value, so it stays last in the sorted candidates
+                variable.candidates.push(candidates.len());
+                candidates.push(Candidate {
+                    connection_id: *connection_id,
+                    proba_trace: None,
+                    cost: unrouted_cost,
+                    conflicts: Vec::new(),
+                });
+            }
+        }
+        for candidate in candidates.iter_mut() {
+            let Some(proba_trace) = &candidate.proba_trace else {
+                continue; // leaving a connection unrouted collides with nothing
+            };
+            let proba_trace_id = proba_trace.proba_trace_id;
+            let colliding_trace_ids = proba_model
+                .collision_adjacency
+                .get(&proba_trace_id)
+                .ok_or_else(|| {
+                    RoutingError::InternalInvariant(format!(
+                        "ProbaTraceID {:?} not found in collision_adjacency",
+                        proba_trace_id
+                    ))
+                })?;
+            // collisions with candidates of connections outside the subset are ignored
+            candidate.conflicts = colliding_trace_ids
+                .iter()
+                .filter_map(|colliding_trace_id| candidate_indices.get(colliding_trace_id))
+                .cloned()
+                .collect();
+        }
+        let num_candidates = candidates.len();
+        let num_variables = variables.len();
+        Ok(BranchAndBound {
+            variables,
+            candidates,
+            max_nodes,
+            stop_at_first,
+            stop_condition: stop_condition.clone(),
+            explored_nodes: 0,
+            budget_exhausted: false,
+            blocked_by: vec![0; num_candidates],
+            assignment: vec![None; num_variables],
+            best: None,
+        })
+    }
+
+    fn available_candidates(&self, variable_index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.variables[variable_index]
+            .candidates
+            .iter()
+            .cloned()
+            .filter(|candidate| self.blocked_by[*candidate] == 0)
+    }
+
+    fn set_assigned(&mut self, candidate: usize, assigned: bool) {
+        for conflict_index in 0..self.candidates[candidate].conflicts.len() {
+            let conflict = self.candidates[candidate].conflicts[conflict_index];
+            if assigned {
+                self.blocked_by[conflict] += 1;
+            } else {
+                self.blocked_by[conflict] -= 1;
+            }
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.budget_exhausted || (self.stop_at_first && self.best.is_some())
+    }
+
+    fn search(&mut self, cost: f64) {
+        if self.is_finished() {
+            return;
+        }
+        if self.explored_nodes >= self.max_nodes || self.stop_condition.check().is_some() {
+            self.budget_exhausted = true;
+            return;
+        }
+        self.explored_nodes += 1;
+        // the most constrained unassigned variable, and the bound over all unassigned variables
+        let mut branch_variable: Option<(usize, usize)> = None; // (variable index, available candidates)
+        let mut lower_bound = cost;
+        for variable_index in 0..self.variables.len() {
+            if self.assignment[variable_index].is_some() {
+                continue;
+            }
+            let mut num_available = 0;
+            let mut cheapest: Option<f64> = None;
+            for candidate in self.available_candidates(variable_index) {
+                num_available += 1;
+                // candidates are sorted by cost, so the first one is the cheapest
+                cheapest.get_or_insert(self.candidates[candidate].cost);
+            }
+            let Some(cheapest) = cheapest else {
+                return; // the variable cannot be assigned anymore
+            };
+            lower_bound += cheapest;
+            if branch_variable.is_none_or(|(_, fewest)| num_available < fewest) {
+                branch_variable = Some((variable_index, num_available));
+            }
+        }
+        if let Some((best_cost, _)) = &self.best
+            && lower_bound >= *best_cost
+        {
+            return;
+        }
+        let Some((variable_index, _)) = branch_variable else {
+            // every variable is assigned
+            let assignment = self.assignment.iter().flatten().cloned().collect();
+            self.best = Some((cost, assignment));
+            return;
+        };
+        let available: Vec<usize> = self.available_candidates(variable_index).collect();
+        for candidate in available {
+            self.assignment[variable_index] = Some(candidate);
+            self.set_assigned(candidate, true);
+            self.search(cost + self.candidates[candidate].cost);
+            self.set_assigned(candidate, false);
+            self.assignment[variable_index] = None;
+            if self.is_finished() {
+                return;
+            }
+        }
+    }
+}
+
+/// Chooses the collision-free candidate per connection with the smallest total cost
+/// among the probabilistic traces of the model, by branch and bound over at most max_nodes search nodes.
+/// The search is anytime: when the budget runs out or the stop condition holds, the best assignment found so far is returned.
+/// Connections with a fixed trace are not part of the selection.
+/// With allow_unrouted, the assignment may leave connections unrouted, so the selection is never infeasible.
+pub fn select_exactly(
+    proba_model: &ProbaModel,
+    max_nodes: usize,
+    allow_unrouted: bool,
+    stop_condition: &StopCondition,
+) -> Result<ExactSelection, RoutingError> {
+    let connection_ids: Vec<ConnectionID> = proba_model
+        .connection_to_traces
+        .iter()
+        .filter(|(_, traces)| matches!(traces, Traces::Probabilistic(_)))
+        .map(|(connection_id, _)| *connection_id)
+        .collect();
+    let mut search = BranchAndBound::new(
+        proba_model,
+        &connection_ids,
+        max_nodes,
+        false,
+        allow_unrouted,
+        stop_condition,
+    )?;
+    search.search(0.0);
+    let explored_nodes = search.explored_nodes;
+    if let Some((total_cost, assignment)) = search.best {
+        let mut traces: BTreeMap<ConnectionID, Arc<ProbaTrace>> = BTreeMap::new();
+        let mut unrouted: Vec<ConnectionID> = Vec::new();
+        for candidate in assignment {
+            let candidate = &search.candidates[candidate];
+            match &candidate.proba_trace {
+                Some(proba_trace) => {
+                    traces.insert(candidate.connection_id, proba_trace.clone());
+                }
+                None => unrouted.push(candidate.connection_id),
+            }
+        }
+        return Ok(ExactSelection::Assignment {
+            traces,
+            unrouted,
+            total_cost,
+            optimal: !search.budget_exhausted,
+            explored_nodes,
+        });
+    }
+    if search.budget_exhausted {
+        return Ok(ExactSelection::BudgetExhausted { explored_nodes });
+    }
+    // shrink the infeasible set one connection at a time, keeping it infeasible
+    let mut core: Vec<ConnectionID> = connection_ids;
+    let mut total_explored_nodes = explored_nodes;
+    let mut minimal = true;
+    let mut index = 0;
+    while index < core.len() {
+        let mut subset = core.clone();
+        subset.remove(index);
+        let remaining_nodes = max_nodes.saturating_sub(total_explored_nodes);
+        let mut feasibility_search = BranchAndBound::new(
+            proba_model,
+            &subset,
+            remaining_nodes,
+            true,
+            false,
+            stop_condition,
+        )?;
+        feasibility_search.search(0.0);
+        total_explored_nodes += feasibility_search.explored_nodes;
+        if feasibility_search.budget_exhausted {
+            minimal = false; // the current set is still infeasible
+            break;
+        }
+        if feasibility_search.best.is_none() {
+            core = subset; // still infeasible without this connection
+        } else {
+            index += 1;
+        }
+    }
+    Ok(ExactSelection::Infeasible(InfeasibilityProof {
+        connection_ids: core,
+        minimal,
+        explored_nodes: total_explored_nodes,
+    }))
+}
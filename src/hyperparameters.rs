@@ -20,19 +20,54 @@ pub const THIRD_ITERATION_NUM_TRACES: usize = 4;
 pub const FOURTH_ITERATION_NUM_TRACES: usize = 2;
 
 pub const BLOCK_THREAD: bool = false; // Whether to block the thread when waiting for a trace to be generated
-pub const DISPLAY_ASTAR: bool = true; // Whether to display the A* search process
+pub const DISPLAY_ASTAR: bool = true; // Whether the app displays the A* search process, see SolverConfig::display_astar
 pub const DISPLAY_PERIOD_MILLIS: u64 = 10;
+pub const BLOCK_POLL_MILLIS: u64 = 50; // how often a thread that waits for Enter checks whether the solve stopped
 
 pub const MAX_ITERATION: NonZeroUsize =
     NonZeroUsize::new(4).expect("MAX_ITERATION must be non-zero");
+pub const POSTERIOR_TOLERANCE: f64 = 1e-4; // the posterior updates of an iteration stop when no posterior changes more
+pub const MAX_POSTERIOR_UPDATES: usize = 10; // Maximum number of posterior updates per iteration
 
 pub const LINEAR_LEARNING_RATE: f64 = 0.2;
 pub const CONSTANT_LEARNING_RATE: f64 = 0.01;
 
-pub const TURN_PENALTY: f64 = 1.0;
+// loopy belief propagation over the conflict graph
+pub const BP_MAX_ROUNDS: usize = 50; // Maximum number of message passing rounds per posterior update
+pub const BP_TOLERANCE: f64 = 1e-6; // message passing stops when no message changes more
+pub const BP_DAMPING: f64 = 0.5; // weight of the old message, against oscillation on loops
+pub const BP_UNROUTED_POTENTIAL: f64 = 0.01; // unary potential of leaving a connection without any of its candidates
+
+pub const EXACT_MAX_NODES: usize = 1_000_000; // Default search node budget of the exact candidate selection
+
+// negotiated congestion routing (PathFinder)
+pub const NEGOTIATION_MAX_ITERATIONS: usize = 30; // Default number of rip-up and reroute passes
+pub const CONGESTION_CELL_SIZE: f32 = 0.5; // side length of the grid cells that congestion is tracked on
+pub const PRESENT_CONGESTION_FACTOR: f64 = 0.5; // cost factor per other net in a cell, in the first pass
+pub const PRESENT_CONGESTION_GROWTH: f64 = 1.5; // the present congestion factor grows by this after every pass
+pub const HISTORY_CONGESTION_INCREMENT: f64 = 0.5; // added to the history cost of a cell per extra net after every pass
+
+pub const BEAM_WIDTH: usize = 4; // Default number of nodes the beam search keeps at every depth
+
+// length matching
+pub const MAX_MEANDER_AMPLITUDE: f32 = 1.0; // the largest distance of a meander bump from the segment it is inserted into
+pub const MEANDER_AMPLITUDE_ATTEMPTS: usize = 4; // a blocked meander is retried with half the amplitude this many times
+pub const SHORT_TRACE_PENALTY: f64 = 1.0; // a trace below the min length of its connection scores as if it were longer by this times the missing length
+pub const MIN_LENGTH_MARGIN: f64 = 0.05; // the meanders aim this far above a min length, which covers the rounding of the fixed point segment lengths
+
+pub const TURN_PENALTY: f64 = 1.0; // cost of a 45° bend
+pub const RIGHT_ANGLE_TURN_PENALTY: f64 = 3.0; // cost of a 90° bend, bends sharper than 90° are forbidden
+pub const TURN_ANGLE_TOLERANCE: f32 = 0.01; // in degrees, turns below this are treated as going straight
 
 pub const ESTIMATE_COEFFICIENT: f64 = 1.0;
 
+pub const ASTAR_MAX_EXPANSIONS: usize = 200; // Default number of node expansions before an A* search gives up
+pub const ASTAR_MAX_DURATION_MILLIS: Option<u64> = None; // Default wall time of an A* search, None for no limit
+
+// rules of the Default net class
+pub const DEFAULT_TRACE_WIDTH: f32 = 0.5;
+pub const DEFAULT_TRACE_CLEARANCE: f32 = 0.05;
+
 lazy_static! {
     pub static ref ASTAR_STRIDE: FixedPoint = {
         let result = FixedPoint::from_num(1.27) + FixedPoint::DELTA;
@@ -0,0 +1,354 @@
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+
+use crate::{
+    hyperparameters::{
+        BP_DAMPING, BP_MAX_ROUNDS, BP_TOLERANCE, BP_UNROUTED_POTENTIAL, CONSTANT_LEARNING_RATE,
+        LINEAR_LEARNING_RATE, OPPORTUNITY_COST_WEIGHT, SCORE_WEIGHT,
+    },
+    pcb_problem::{ConnectionID, ProbaModel, ProbaTrace, ProbaTraceID},
+    routing_error::{RoutingError, ensure_invariant},
+};
+
+/// Computes the posteriors of the candidate traces of a ProbaModel from their scores and collisions.
+/// An engine is called repeatedly until the largest change of a posterior falls under the tolerance of the solver config.
+pub trait InferenceEngine: Send + Sync + Debug {
+    /// updates the posterior of every probabilistic trace, returns the largest change of a posterior
+    fn update_posteriors(&self, proba_model: &ProbaModel) -> Result<f64, RoutingError>;
+}
+
+/// the hand-tuned fixed-point update: each step moves a posterior towards
+/// prior × score^SCORE_WEIGHT × opportunity_cost^OPPORTUNITY_COST_WEIGHT,
+/// where the opportunity cost is the probability that no colliding trace is chosen
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FixedPointInference;
+
+impl InferenceEngine for FixedPointInference {
+    fn update_posteriors(&self, proba_model: &ProbaModel) -> Result<f64, RoutingError> {
+        let proba_traces = proba_model.probabilistic_traces();
+        // Update the posterior probabilities for all traces in the model
+        for (proba_trace_id, proba_trace) in proba_traces.iter() {
+            let adjacent_traces = proba_model
+                .collision_adjacency
+                .get(proba_trace_id)
+                .ok_or_else(|| {
+                    RoutingError::InternalInvariant(format!(
+                        "No adjacent traces for ProbaTraceID {:?}",
+                        proba_trace_id
+                    ))
+                })?;
+            let mut proba_product = 1.0;
+            for adjacent_trace_id in adjacent_traces.iter() {
+                let adjacent_trace = proba_traces.get(adjacent_trace_id).ok_or_else(|| {
+                    RoutingError::InternalInvariant(format!(
+                        "No ProbaTraceID {:?} found in proba_traces",
+                        adjacent_trace_id
+                    ))
+                })?;
+                let posterior = adjacent_trace.get_posterior_with_fallback()?;
+                // to do: update this
+                let one_minus_posterior = (1.0 - posterior).max(0.0); // Ensure non-negative
+                proba_product *= one_minus_posterior;
+            }
+            let target_posterior = proba_product;
+            ensure_invariant!(
+                (0.0..=1.0).contains(&target_posterior),
+                "Target posterior must be between 0 and 1"
+            );
+            // get num traces in the same iteration
+            let current_posterior = proba_trace.get_posterior_with_fallback()?;
+            let opportunity_cost = target_posterior / current_posterior;
+            let score = proba_trace.trace_path.get_score(&proba_trace.length_limits)?;
+            let score_weight = *SCORE_WEIGHT.lock().unwrap();
+            let opportunity_cost_weight = *OPPORTUNITY_COST_WEIGHT.lock().unwrap();
+            let target_posterior_unnormalized = 1.0
+                * f64::powf(score, score_weight)
+                * f64::powf(opportunity_cost, opportunity_cost_weight);
+            let target_posterior_normalized =
+                proba_trace.get_normalized_prior()? * target_posterior_unnormalized;
+            let mut temp_posterior = proba_trace.temp_posterior.lock().unwrap();
+            let target_greater_than_current = target_posterior_normalized > current_posterior;
+            let constant_offset = if target_greater_than_current {
+                CONSTANT_LEARNING_RATE
+            } else {
+                -CONSTANT_LEARNING_RATE
+            };
+            let new_posterior = current_posterior
+                + (target_posterior_normalized - current_posterior) * LINEAR_LEARNING_RATE
+                + constant_offset;
+            // the constant offset must not step over the target, or the posterior never settles
+            let new_posterior = if target_greater_than_current {
+                new_posterior.min(target_posterior_normalized)
+            } else {
+                new_posterior.max(target_posterior_normalized)
+            };
+            *temp_posterior = Some(new_posterior);
+        }
+        // move temp_posterior to posterior
+        let mut max_posterior_change: f64 = 0.0;
+        for (_, proba_trace) in proba_traces.iter() {
+            let mut posterior = proba_trace.posterior.lock().unwrap();
+            let mut temp_posterior = proba_trace.temp_posterior.lock().unwrap();
+            let temp_posterior_val = temp_posterior.ok_or_else(|| {
+                RoutingError::InternalInvariant(format!(
+                    "No temporary posterior for ProbaTraceID {:?}",
+                    proba_trace.proba_trace_id
+                ))
+            })?;
+            let previous_posterior = match *posterior {
+                Some(posterior) => posterior,
+                None => proba_trace.get_normalized_prior()?,
+            };
+            max_posterior_change =
+                max_posterior_change.max((temp_posterior_val - previous_posterior).abs());
+            *posterior = Some(temp_posterior_val);
+            // reset temp_posterior
+            *temp_posterior = None;
+        }
+        Ok(max_posterior_change)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BeliefPropagationMode {
+    SumProduct, // marginal probabilities of the candidates
+    MaxProduct, // max-marginals, which favor the candidates of the best joint assignment
+}
+
+/// loopy belief propagation over the conflict graph:
+/// every connection is a variable whose values are its candidate traces or none of them,
+/// the scores weighted by the priors are the unary potentials,
+/// and the collisions between the candidates of two connections are pairwise hard constraints
+#[derive(Debug, Clone, Copy)]
+pub struct BeliefPropagation {
+    pub mode: BeliefPropagationMode,
+    pub max_rounds: usize, // message passing rounds per update_posteriors call
+    pub tolerance: f64,    // message passing stops when no message changes more
+    pub damping: f64,      // weight of the old message in [0, 1)
+    pub unrouted_potential: f64, // unary potential of the value "none of the candidates"
+}
+
+impl Default for BeliefPropagation {
+    fn default() -> Self {
+        BeliefPropagation {
+            mode: BeliefPropagationMode::SumProduct,
+            max_rounds: BP_MAX_ROUNDS,
+            tolerance: BP_TOLERANCE,
+            damping: BP_DAMPING,
+            unrouted_potential: BP_UNROUTED_POTENTIAL,
+        }
+    }
+}
+
+/// a connection with probabilistic traces, value i < traces.len() is traces[i], value traces.len() is none of them
+struct Variable {
+    traces: Vec<Arc<ProbaTrace>>,
+    potentials: Vec<f64>, // unary potential of each value
+    incoming_edges: Vec<usize>,
+}
+
+/// a directed edge of the conflict graph, which carries the message from one variable to the other
+struct Edge {
+    from: usize,
+    to: usize,
+    reverse: usize,            // the index of the edge in the opposite direction
+    conflicts: Vec<Vec<bool>>, // conflicts[a][b]: value a of from collides with value b of to
+}
+
+struct ConflictGraph {
+    variables: Vec<Variable>,
+    edges: Vec<Edge>,
+}
+
+impl ConflictGraph {
+    fn new(proba_model: &ProbaModel, unrouted_potential: f64) -> Result<Self, RoutingError> {
+        let mut connection_traces: BTreeMap<ConnectionID, Vec<Arc<ProbaTrace>>> = BTreeMap::new();
+        for proba_trace in proba_model.probabilistic_traces().into_values() {
+            connection_traces
+                .entry(proba_trace.connection_id)
+                .or_default()
+                .push(proba_trace);
+        }
+        // ProbaTraceID to (variable index, value index)
+        let mut trace_values: BTreeMap<ProbaTraceID, (usize, usize)> = BTreeMap::new();
+        let mut variables: Vec<Variable> = Vec::new();
+        for (variable_index, traces) in connection_traces.into_values().enumerate() {
+            let mut potentials: Vec<f64> = Vec::new();
+            for (value_index, proba_trace) in traces.iter().enumerate() {
+                trace_values.insert(proba_trace.proba_trace_id, (variable_index, value_index));
+                potentials.push(
+                    proba_trace.get_normalized_prior()?
+                        * proba_trace.trace_path.get_score(&proba_trace.length_limits)?,
+                );
+            }
+            potentials.push(unrouted_potential);
+            variables.push(Variable {
+                traces,
+                potentials,
+                incoming_edges: Vec::new(),
+            });
+        }
+        // (from, to) to edge index, every colliding pair of variables gets an edge in both directions
+        let mut edge_indices: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+        let mut edges: Vec<Edge> = Vec::new();
+        for (proba_trace_id, colliding_trace_ids) in proba_model.collision_adjacency.iter() {
+            let &(from, a) = trace_values.get(proba_trace_id).ok_or_else(|| {
+                RoutingError::InternalInvariant(format!(
+                    "ProbaTraceID {:?} of the collision adjacency is not probabilistic",
+                    proba_trace_id
+                ))
+            })?;
+            for colliding_trace_id in colliding_trace_ids.iter() {
+                let &(to, b) = trace_values.get(colliding_trace_id).ok_or_else(|| {
+                    RoutingError::InternalInvariant(format!(
+                        "ProbaTraceID {:?} collides with the unknown ProbaTraceID {:?}",
+                        proba_trace_id, colliding_trace_id
+                    ))
+                })?;
+                ensure_invariant!(
+                    from != to,
+                    "Candidates of the same connection {:?} and {:?} must not be in the collision adjacency",
+                    proba_trace_id,
+                    colliding_trace_id
+                );
+                let edge_index = *edge_indices.entry((from, to)).or_insert_with(|| {
+                    edges.push(Edge {
+                        from,
+                        to,
+                        reverse: usize::MAX, // set below
+                        conflicts: vec![
+                            vec![false; variables[to].potentials.len()];
+                            variables[from].potentials.len()
+                        ],
+                    });
+                    edges.len() - 1
+                });
+                edges[edge_index].conflicts[a][b] = true;
+            }
+        }
+        for ((from, to), edge_index) in edge_indices.iter() {
+            let reverse = *edge_indices.get(&(*to, *from)).ok_or_else(|| {
+                RoutingError::InternalInvariant(format!(
+                    "The collision adjacency is not symmetric between variables {} and {}",
+                    from, to
+                ))
+            })?;
+            edges[*edge_index].reverse = reverse;
+            variables[*to].incoming_edges.push(*edge_index);
+        }
+        Ok(ConflictGraph { variables, edges })
+    }
+}
+
+/// scales the values to sum to 1, the values must not all be zero
+fn normalize(values: &mut [f64]) {
+    let sum: f64 = values.iter().sum();
+    for value in values.iter_mut() {
+        *value /= sum;
+    }
+}
+
+impl BeliefPropagation {
+    /// the potentials of a variable times all incoming messages except the one along skipped_edge
+    fn incoming_product(
+        graph: &ConflictGraph,
+        messages: &[Vec<f64>],
+        variable_index: usize,
+        skipped_edge: Option<usize>,
+    ) -> Vec<f64> {
+        let variable = &graph.variables[variable_index];
+        let mut product = variable.potentials.clone();
+        for edge_index in variable.incoming_edges.iter() {
+            if Some(*edge_index) == skipped_edge {
+                continue;
+            }
+            for (value, message_value) in product.iter_mut().zip(messages[*edge_index].iter()) {
+                *value *= message_value;
+            }
+        }
+        product
+    }
+
+    /// the message along the edge, computed from the messages of the previous round
+    fn compute_message(
+        &self,
+        graph: &ConflictGraph,
+        messages: &[Vec<f64>],
+        edge_index: usize,
+    ) -> Vec<f64> {
+        let edge = &graph.edges[edge_index];
+        let product = Self::incoming_product(graph, messages, edge.from, Some(edge.reverse));
+        let num_to_values = graph.variables[edge.to].potentials.len();
+        let mut message: Vec<f64> = (0..num_to_values)
+            .map(|b| {
+                let compatible = product
+                    .iter()
+                    .enumerate()
+                    .filter(|(a, _)| !edge.conflicts[*a][b])
+                    .map(|(_, value)| *value);
+                match self.mode {
+                    BeliefPropagationMode::SumProduct => compatible.sum(),
+                    BeliefPropagationMode::MaxProduct => compatible.fold(0.0, f64::max),
+                }
+            })
+            .collect();
+        // the value none is compatible with everything, so the message is never all zero
+        normalize(&mut message);
+        message
+    }
+}
+
+impl InferenceEngine for BeliefPropagation {
+    fn update_posteriors(&self, proba_model: &ProbaModel) -> Result<f64, RoutingError> {
+        let graph = ConflictGraph::new(proba_model, self.unrouted_potential)?;
+        // messages start uniform
+        let mut messages: Vec<Vec<f64>> = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                let num_to_values = graph.variables[edge.to].potentials.len();
+                vec![1.0 / num_to_values as f64; num_to_values]
+            })
+            .collect();
+        for _ in 0..self.max_rounds {
+            let mut max_message_change: f64 = 0.0;
+            let new_messages: Vec<Vec<f64>> = (0..graph.edges.len())
+                .map(|edge_index| {
+                    let new_message = self.compute_message(&graph, &messages, edge_index);
+                    new_message
+                        .iter()
+                        .zip(messages[edge_index].iter())
+                        .map(|(new_value, old_value)| {
+                            let damped =
+                                (1.0 - self.damping) * new_value + self.damping * old_value;
+                            max_message_change = max_message_change.max((damped - old_value).abs());
+                            damped
+                        })
+                        .collect()
+                })
+                .collect();
+            messages = new_messages;
+            if max_message_change < self.tolerance {
+                break;
+            }
+        }
+        // the belief of each candidate becomes its posterior
+        let mut max_posterior_change: f64 = 0.0;
+        for variable_index in 0..graph.variables.len() {
+            let mut belief = Self::incoming_product(&graph, &messages, variable_index, None);
+            normalize(&mut belief);
+            for (proba_trace, trace_belief) in
+                graph.variables[variable_index].traces.iter().zip(belief)
+            {
+                let mut posterior = proba_trace.posterior.lock().unwrap();
+                let previous_posterior = match *posterior {
+                    Some(posterior) => posterior,
+                    None => proba_trace.get_normalized_prior()?,
+                };
+                max_posterior_change =
+                    max_posterior_change.max((trace_belief - previous_posterior).abs());
+                *posterior = Some(trace_belief);
+            }
+        }
+        Ok(max_posterior_change)
+    }
+}
@@ -0,0 +1,354 @@
+use std::{cell::RefCell, collections::BTreeMap, fmt};
+
+use crate::{
+    astar::{AStarModel, SearchBudget},
+    hyperparameters::{MAX_MEANDER_AMPLITUDE, MEANDER_AMPLITUDE_ATTEMPTS, MIN_LENGTH_MARGIN},
+    pcb_problem::{ConnectionID, FixedTrace, PcbProblem, PcbSolution},
+    routing_error::RoutingError,
+    stop_condition::{CancelToken, StopCondition},
+    trace_path::TracePath,
+    vec2::{FixedPoint, FixedVec2, FloatVec2},
+};
+
+/// connections whose traces must have the same length, e.g. a DDR byte lane or the two traces of a differential pair
+#[derive(Debug, Clone)]
+pub struct LengthMatchGroup {
+    pub name: String,
+    pub connection_ids: Vec<ConnectionID>,
+    pub tolerance: f32, // the largest allowed difference between the longest and the shortest trace
+}
+
+/// the lengths of the routed traces of a group, the unrouted connections of the group are left out
+#[derive(Debug, Clone)]
+pub struct LengthMatchReport {
+    pub group_name: String,
+    pub lengths: BTreeMap<ConnectionID, f64>,
+    pub target_length: f64, // the length of the longest trace, which the other traces are tuned to
+    pub skew: f64,          // the difference between the longest and the shortest trace
+    pub tolerance: f32,
+}
+
+impl LengthMatchReport {
+    pub fn is_within_tolerance(&self) -> bool {
+        self.skew <= self.tolerance as f64
+    }
+}
+
+impl fmt::Display for LengthMatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "length match group {:?}: skew {:.3} with tolerance {}, target length {:.3} of {} traces",
+            self.group_name,
+            self.skew,
+            self.tolerance,
+            self.target_length,
+            self.lengths.len()
+        )?;
+        if !self.is_within_tolerance() {
+            write!(f, ", the tolerance is not met")?;
+        }
+        Ok(())
+    }
+}
+
+fn add(position: FloatVec2, offset: FloatVec2, scale: f32) -> FloatVec2 {
+    FloatVec2 {
+        x: position.x + offset.x * scale,
+        y: position.y + offset.y * scale,
+    }
+}
+
+/// the anchors of a segment from start to end with bumps on the side of normal, in the middle of the segment
+/// every bump goes out by amplitude, pitch along the segment and back, then pitch along the segment to the next bump,
+/// so it adds twice the amplitude to the length
+fn meander_anchors(
+    start: FloatVec2,
+    end: FloatVec2,
+    normal: FloatVec2,
+    pitch: f32,
+    num_bumps: usize,
+    amplitude: f32,
+) -> Vec<FloatVec2> {
+    let direction = end.sub(start).normalize();
+    let segment_length = end.sub(start).dot(direction);
+    let first_bump = (segment_length - 2.0 * pitch * num_bumps as f32) / 2.0;
+    let mut anchors = vec![start];
+    for bump_index in 0..num_bumps {
+        let base = add(
+            start,
+            direction,
+            first_bump + 2.0 * pitch * bump_index as f32,
+        );
+        let top = add(base, normal, amplitude);
+        anchors.push(base);
+        anchors.push(top);
+        anchors.push(add(top, direction, pitch));
+        anchors.push(add(base, direction, pitch));
+    }
+    anchors.push(end);
+    anchors
+}
+
+impl PcbProblem {
+    /// declares that the traces of the connections are tuned to the same length within the tolerance
+    pub fn add_length_match_group(
+        &mut self,
+        name: &str,
+        connection_ids: Vec<ConnectionID>,
+        tolerance: f32,
+    ) -> Result<(), RoutingError> {
+        if self.length_match_groups.contains_key(name) {
+            return Err(RoutingError::InvalidInput(format!(
+                "Length match group with name {:?} already exists",
+                name
+            )));
+        }
+        for connection_id in connection_ids.iter() {
+            self.connection(*connection_id)?;
+            if let Some(group) = self.length_match_group_of(*connection_id) {
+                return Err(RoutingError::InvalidInput(format!(
+                    "ConnectionID {:?} already belongs to the length match group {:?}",
+                    connection_id, group.name
+                )));
+            }
+        }
+        self.length_match_groups.insert(
+            name.to_string(),
+            LengthMatchGroup {
+                name: name.to_string(),
+                connection_ids,
+                tolerance,
+            },
+        );
+        Ok(())
+    }
+
+    /// the length match group that the connection belongs to, if any
+    pub fn length_match_group_of(&self, connection_id: ConnectionID) -> Option<&LengthMatchGroup> {
+        self.length_match_groups
+            .values()
+            .find(|group| group.connection_ids.contains(&connection_id))
+    }
+
+    /// the achieved skew of every length match group in the solution
+    pub fn length_match_reports(&self, solution: &PcbSolution) -> Vec<LengthMatchReport> {
+        self.length_match_groups
+            .values()
+            .map(|group| {
+                let lengths: BTreeMap<ConnectionID, f64> = group
+                    .connection_ids
+                    .iter()
+                    .filter_map(|connection_id| {
+                        let fixed_trace = solution.determined_traces.get(connection_id)?;
+                        Some((*connection_id, fixed_trace.trace_path.length))
+                    })
+                    .collect();
+                let target_length = lengths.values().cloned().fold(0.0, f64::max);
+                let shortest_length = lengths.values().cloned().fold(target_length, f64::min);
+                LengthMatchReport {
+                    group_name: group.name.clone(),
+                    lengths,
+                    target_length,
+                    skew: target_length - shortest_length,
+                    tolerance: group.tolerance,
+                }
+            })
+            .collect()
+    }
+
+    /// the post-pass of the length matching: the traces that are shorter than the min length of their connection,
+    /// then the traces of every group that are shorter than its longest trace by more than the tolerance,
+    /// get meanders in their longest straight segments, as far as the clearances and the max lengths allow
+    /// the locked traces are kept as given, but count as the longest trace of their group
+    pub fn match_lengths(
+        &self,
+        solution: &mut PcbSolution,
+        locked_traces: &BTreeMap<ConnectionID, FixedTrace>,
+    ) -> Result<Vec<LengthMatchReport>, RoutingError> {
+        // the min lengths first, so that the groups are matched to the lengthened traces
+        let connection_ids: Vec<ConnectionID> =
+            solution.determined_traces.keys().cloned().collect();
+        for connection_id in connection_ids {
+            let length_limits = self.connection(connection_id)?.length_limits;
+            let length = solution.determined_traces[&connection_id].trace_path.length;
+            let missing_length = length_limits.missing_length(length);
+            if missing_length <= 0.0 || locked_traces.contains_key(&connection_id) {
+                continue;
+            }
+            let fixed_trace = &solution.determined_traces[&connection_id];
+            let trace_path =
+                self.tune_trace(fixed_trace, missing_length + MIN_LENGTH_MARGIN, solution)?;
+            println!(
+                "Min length of {:?}: {:.3} -> {:.3}, min {:.3}",
+                connection_id,
+                length,
+                trace_path.length,
+                length + missing_length
+            );
+            if length_limits.missing_length(trace_path.length) > 0.0 {
+                println!(
+                    "The trace of {:?} is still shorter than its min length",
+                    connection_id
+                );
+            }
+            if let Some(fixed_trace) = solution.determined_traces.get_mut(&connection_id) {
+                fixed_trace.trace_path = trace_path;
+            }
+        }
+        for report in self.length_match_reports(solution) {
+            let tolerance = report.tolerance as f64;
+            for (connection_id, length) in report.lengths.iter() {
+                if report.target_length - length <= tolerance
+                    || locked_traces.contains_key(connection_id)
+                {
+                    continue;
+                }
+                let fixed_trace = &solution.determined_traces[connection_id];
+                let trace_path =
+                    self.tune_trace(fixed_trace, report.target_length - length, solution)?;
+                println!(
+                    "Length matching of {:?} in group {:?}: {:.3} -> {:.3}, target {:.3}",
+                    connection_id,
+                    report.group_name,
+                    length,
+                    trace_path.length,
+                    report.target_length
+                );
+                if let Some(fixed_trace) = solution.determined_traces.get_mut(connection_id) {
+                    fixed_trace.trace_path = trace_path;
+                }
+            }
+        }
+        Ok(self.length_match_reports(solution))
+    }
+
+    /// the trace lengthened by up to missing_length, with meanders that keep the clearance to the pads and traces of the other nets
+    /// and no longer than the max length of the connection
+    fn tune_trace(
+        &self,
+        fixed_trace: &FixedTrace,
+        missing_length: f64,
+        solution: &PcbSolution,
+    ) -> Result<TracePath, RoutingError> {
+        let length_limits = self.connection(fixed_trace.connection_id)?.length_limits;
+        let missing_length = length_limits
+            .max_length
+            .map_or(missing_length, |max_length| {
+                missing_length.min(max_length as f64 - fixed_trace.trace_path.length)
+            });
+        if missing_length <= 0.0 {
+            return Ok(fixed_trace.trace_path.clone());
+        }
+        let Some(first_segment) = fixed_trace.trace_path.segments.first() else {
+            return Ok(fixed_trace.trace_path.clone());
+        };
+        let (trace_width, trace_clearance) = (first_segment.width, first_segment.clearance);
+        let (mut obstacle_shapes, mut obstacle_clearance_shapes) =
+            self.pad_obstacle_shapes(&[fixed_trace.net_id]);
+        let (trace_shapes, trace_clearance_shapes) =
+            self.trace_obstacle_shapes(&[fixed_trace.net_id], solution.determined_traces.values());
+        obstacle_shapes.extend(trace_shapes);
+        obstacle_clearance_shapes.extend(trace_clearance_shapes);
+        let routing_mode = self
+            .nets
+            .get(&fixed_trace.net_id)
+            .ok_or_else(|| {
+                RoutingError::InternalInvariant(format!(
+                    "NetID {:?} not found in nets",
+                    fixed_trace.net_id
+                ))
+            })?
+            .routing_mode;
+        // only the collision check of the model is used, there is no search
+        let astar_model = AStarModel {
+            width: self.width,
+            height: self.height,
+            obstacle_shapes,
+            obstacle_clearance_shapes,
+            start: FixedVec2::new(FixedPoint::ZERO, FixedPoint::ZERO),
+            end: FixedVec2::new(FixedPoint::ZERO, FixedPoint::ZERO),
+            trace_width,
+            trace_clearance,
+            routing_mode,
+            routing_cost: self.routing_cost.clone(),
+            search_budget: SearchBudget::default(),
+            stop_condition: StopCondition::new(CancelToken::new(), None),
+            border_cache: RefCell::new(None),
+            max_length: None,
+            display: false,
+        };
+        // the legs of neighboring bumps keep the clearance between them
+        let pitch = trace_width + 2.0 * trace_clearance;
+
+        // the anchors that replace each segment, the untouched segments keep their start only
+        let segments = &fixed_trace.trace_path.segments;
+        let mut segment_anchors: Vec<Vec<FixedVec2>> =
+            segments.iter().map(|segment| vec![segment.start]).collect();
+        let mut segment_indices: Vec<usize> = (0..segments.len()).collect();
+        segment_indices.sort_by(|index1, index2| {
+            segments[*index2]
+                .length()
+                .total_cmp(&segments[*index1].length())
+        });
+        let mut remaining_length = missing_length as f32;
+        for segment_index in segment_indices {
+            if remaining_length <= 0.0 {
+                break;
+            }
+            let segment = &segments[segment_index];
+            // a pitch of straight segment at both ends keeps the bumps away from the corners
+            let max_bumps = ((segment.length() as f32 - 2.0 * pitch) / (2.0 * pitch))
+                .floor()
+                .max(0.0) as usize;
+            if max_bumps == 0 {
+                continue;
+            }
+            let (start, end) = (segment.start.to_float(), segment.end.to_float());
+            let normal = end.sub(start).normalize().perp();
+            let opposite_normal = FloatVec2 {
+                x: -normal.x,
+                y: -normal.y,
+            };
+            let mut max_amplitude = MAX_MEANDER_AMPLITUDE;
+            'attempts: for _ in 0..MEANDER_AMPLITUDE_ATTEMPTS {
+                let num_bumps = ((remaining_length / (2.0 * max_amplitude)).ceil() as usize)
+                    .clamp(1, max_bumps);
+                let amplitude = max_amplitude.min(remaining_length / (2.0 * num_bumps as f32));
+                for side in [normal, opposite_normal] {
+                    let mut anchors: Vec<FixedVec2> =
+                        meander_anchors(start, end, side, pitch, num_bumps, amplitude)
+                            .iter()
+                            .map(|anchor| anchor.to_fixed())
+                            .collect();
+                    anchors.pop(); // the end is the start of the next segment
+                    let collides = anchors
+                        .iter()
+                        .zip(anchors.iter().skip(1).chain([&segment.end]))
+                        .any(|(anchor, next_anchor)| {
+                            anchor == next_anchor
+                                || astar_model.check_collision(
+                                    *anchor,
+                                    *next_anchor,
+                                    trace_width,
+                                    trace_clearance,
+                                )
+                        });
+                    if !collides {
+                        remaining_length -= 2.0 * amplitude * num_bumps as f32;
+                        segment_anchors[segment_index] = anchors;
+                        break 'attempts;
+                    }
+                }
+                max_amplitude /= 2.0;
+            }
+        }
+        let mut anchors: Vec<FixedVec2> = segment_anchors.into_iter().flatten().collect();
+        anchors.extend(segments.last().map(|segment| segment.end));
+        let trace_path = TracePath::from_anchors(anchors, trace_width, trace_clearance)?;
+        if length_limits.exceeds_max_length(trace_path.length) {
+            return Ok(fixed_trace.trace_path.clone());
+        }
+        Ok(trace_path)
+    }
+}
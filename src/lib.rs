@@ -1,23 +1,39 @@
 pub mod app;
+pub mod beam_search;
 pub mod camera_uniform;
 pub mod context;
+pub mod diff_pair;
+pub mod exact_selection;
 pub mod hyperparameters;
+pub mod inference_engine;
 pub mod input_context;
+pub mod length_matching;
+pub mod locked_traces;
 pub mod my_texture;
+pub mod negotiated_routing;
+pub mod net_class;
+pub mod net_ordering;
 pub mod orthographic_camera;
 pub mod pad;
 pub mod pcb_problem;
 pub mod pcb_render_model;
 pub mod prim_shape;
+pub mod problem_validation;
 pub mod render_context;
+pub mod routing_cost;
+pub mod routing_error;
 pub mod shape_instance;
 pub mod shape_mesh;
+pub mod solver_config;
 pub mod state;
+pub mod stop_condition;
+pub mod trace_file;
 pub mod trace_path;
 pub mod transparent_pipeline;
 pub mod vec2;
 pub mod vertex;
 pub mod astar;
+pub mod any_angle_astar;
 pub mod binary_heap_item;
 pub mod working_thread_fn;
 pub mod test_pcb_problem;
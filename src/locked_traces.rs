@@ -0,0 +1,173 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    pcb_problem::{ConnectionID, FixedTrace, NetID, PcbProblem, PcbSolution},
+    pcb_render_model::PcbRenderModel,
+    routing_error::RoutingError,
+    solver_config::SolverConfig,
+    trace_path::TracePath,
+    vec2::FloatVec2,
+};
+
+fn is_same_position(position1: FloatVec2, position2: FloatVec2) -> bool {
+    position1.x == position2.x && position1.y == position2.y
+}
+
+impl PcbProblem {
+    /// the connection of the net that joins the pads at the two positions, in either direction
+    pub fn connection_between(
+        &self,
+        net_id: NetID,
+        position1: FloatVec2,
+        position2: FloatVec2,
+    ) -> Option<ConnectionID> {
+        let net_info = self.nets.get(&net_id)?;
+        net_info
+            .connections
+            .values()
+            .find(|connection| {
+                let (source, sink) = (connection.source.position, connection.sink.position);
+                (is_same_position(source, position1) && is_same_position(sink, position2))
+                    || (is_same_position(source, position2) && is_same_position(sink, position1))
+            })
+            .map(|connection| connection.connection_id)
+    }
+
+    /// a hand-routed trace of the connection through the anchors, with the trace width and clearance of the connection
+    pub fn locked_trace(
+        &self,
+        connection_id: ConnectionID,
+        anchors: &[FloatVec2],
+    ) -> Result<FixedTrace, RoutingError> {
+        let connection = self.connection(connection_id)?;
+        let anchors: Vec<_> = anchors.iter().map(|anchor| anchor.to_fixed()).collect();
+        if anchors.len() < 2 || anchors.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(RoutingError::InvalidInput(format!(
+                "The trace of ConnectionID {:?} needs at least two anchors and no repeated anchor",
+                connection_id
+            )));
+        }
+        Ok(FixedTrace {
+            net_id: connection.net_id,
+            connection_id,
+            trace_path: TracePath::from_anchors(
+                anchors,
+                self.trace_width(connection),
+                self.trace_clearance(connection),
+            )?,
+        })
+    }
+
+    /// the traces of an earlier version of the problem, moved to the connections of this one with the same net name and pads,
+    /// the traces of connections whose pads moved or that no longer exist are left out
+    pub fn translate_traces(
+        &self,
+        previous_problem: &PcbProblem,
+        previous_traces: &BTreeMap<ConnectionID, FixedTrace>,
+    ) -> BTreeMap<ConnectionID, FixedTrace> {
+        let mut traces: BTreeMap<ConnectionID, FixedTrace> = BTreeMap::new();
+        for previous_trace in previous_traces.values() {
+            let Some(previous_net_info) = previous_problem.nets.get(&previous_trace.net_id) else {
+                continue;
+            };
+            let Some(previous_connection) = previous_net_info
+                .connections
+                .get(&previous_trace.connection_id)
+            else {
+                continue;
+            };
+            let Some(net_id) = self.net_by_name(&previous_net_info.name) else {
+                continue;
+            };
+            let Some(connection_id) = self.connection_between(
+                net_id,
+                previous_connection.source.position,
+                previous_connection.sink.position,
+            ) else {
+                continue;
+            };
+            traces.insert(
+                connection_id,
+                FixedTrace {
+                    net_id,
+                    connection_id,
+                    trace_path: previous_trace.trace_path.clone(),
+                },
+            );
+        }
+        traces
+    }
+
+    /// the ECO mode: of the traces of this problem, those of the nets that did not change, to be locked while the rest is re-routed
+    /// a net is ripped up when one of its connections has no trace, e.g. because translate_traces or read_traces
+    /// left out a connection whose pad moved, or when one of its traces conflicts with a pad or a trace of another net
+    pub fn eco_locked_traces(
+        &self,
+        traces: &BTreeMap<ConnectionID, FixedTrace>,
+    ) -> BTreeMap<ConnectionID, FixedTrace> {
+        let mut ripped_up_net_ids: BTreeSet<NetID> = BTreeSet::new();
+        for (net_id, net_info) in self.nets.iter() {
+            let has_all_traces = net_info.connections.keys().all(|connection_id| {
+                traces.get(connection_id).is_some_and(|trace| {
+                    trace.net_id == *net_id && trace.connection_id == *connection_id
+                })
+            });
+            if !has_all_traces {
+                println!("ECO: ripping up net {:?}, its pads changed", net_info.name);
+                ripped_up_net_ids.insert(*net_id);
+            }
+        }
+        let kept_traces = |ripped_up_net_ids: &BTreeSet<NetID>| {
+            let mut kept_traces: BTreeMap<ConnectionID, FixedTrace> = BTreeMap::new();
+            for (net_id, net_info) in self.nets.iter() {
+                if ripped_up_net_ids.contains(net_id) {
+                    continue;
+                }
+                for connection_id in net_info.connections.keys() {
+                    kept_traces.insert(*connection_id, traces[connection_id].clone());
+                }
+            }
+            kept_traces
+        };
+        // removing traces cannot add conflicts, so one pass over the conflicts is enough
+        for diagnostic in self.validate_locked_traces(&kept_traces(&ripped_up_net_ids)) {
+            for net_id in diagnostic.net_ids.iter() {
+                if ripped_up_net_ids.insert(*net_id) {
+                    println!(
+                        "ECO: ripping up net {:?}, {}",
+                        self.nets
+                            .get(net_id)
+                            .map_or("unknown", |net_info| net_info.name.as_str()),
+                        diagnostic.message
+                    );
+                }
+            }
+        }
+        kept_traces(&ripped_up_net_ids)
+    }
+
+    /// re-routes the problem after an engineering change of the previous problem,
+    /// the nets whose pads did not move keep their traces of the previous solution
+    pub fn solve_eco(
+        &self,
+        previous_problem: &PcbProblem,
+        previous_solution: &PcbSolution,
+        config: &SolverConfig,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<PcbSolution, RoutingError> {
+        let traces = self.translate_traces(previous_problem, &previous_solution.determined_traces);
+        let locked_traces = self.eco_locked_traces(&traces);
+        println!(
+            "ECO: {} of {} connections keep their traces",
+            locked_traces.len(),
+            self.nets
+                .values()
+                .map(|net_info| net_info.connections.len())
+                .sum::<usize>()
+        );
+        self.solve_with_locked_traces(&locked_traces, config, pcb_render_model)
+    }
+}
@@ -1,8 +1,10 @@
-use pcb_routing_wgpu3::app::App;
+use pcb_routing_wgpu3::{app::App, block_or_sleep::start_enter_reader};
 use winit::event_loop::{ControlFlow, EventLoop};
 
 fn main() {
     env_logger::init();
+    // the solver waits for Enter between its iterations
+    start_enter_reader();
 
     let event_loop = EventLoop::new().unwrap();
 
@@ -0,0 +1,492 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    astar::{AStarFailure, AStarModel, RoutingMode},
+    hyperparameters::{
+        CONGESTION_CELL_SIZE, HISTORY_CONGESTION_INCREMENT, PRESENT_CONGESTION_FACTOR,
+        PRESENT_CONGESTION_GROWTH,
+    },
+    pcb_problem::{
+        Connection, ConnectionID, FixedTrace, NetID, PcbProblem, PcbSolution, UnroutedReason,
+        reason_without_candidates, unroutable_without_candidates,
+    },
+    pcb_render_model::PcbRenderModel,
+    routing_cost::RoutingCost,
+    routing_error::RoutingError,
+    solver_config::SolverConfig,
+    stop_condition::{StopCondition, StopReason},
+    trace_path::TracePath,
+    vec2::{FixedPoint, FixedVec2, FloatVec2},
+};
+
+/// the congestion of a grid of cells over the board, the board is centered at the origin
+#[derive(Debug, Clone)]
+struct CongestionMap {
+    width: f32,
+    height: f32,
+    num_columns: usize,
+    num_rows: usize,
+    history: Vec<f64>, // per cell, grows in every pass that the cell is shared by several nets
+    occupants: Vec<BTreeMap<NetID, usize>>, // per cell, the number of routed connections of each net
+}
+
+impl CongestionMap {
+    fn new(width: f32, height: f32) -> Self {
+        let num_columns = (width / CONGESTION_CELL_SIZE).ceil().max(1.0) as usize;
+        let num_rows = (height / CONGESTION_CELL_SIZE).ceil().max(1.0) as usize;
+        CongestionMap {
+            width,
+            height,
+            num_columns,
+            num_rows,
+            history: vec![0.0; num_columns * num_rows],
+            occupants: vec![BTreeMap::new(); num_columns * num_rows],
+        }
+    }
+
+    /// the (column, row) of the cell containing the point, clamped to the board
+    fn cell_coordinates(&self, x: f32, y: f32) -> (usize, usize) {
+        let column = ((x + self.width / 2.0) / CONGESTION_CELL_SIZE)
+            .floor()
+            .max(0.0) as usize;
+        let row = ((y + self.height / 2.0) / CONGESTION_CELL_SIZE)
+            .floor()
+            .max(0.0) as usize;
+        (column.min(self.num_columns - 1), row.min(self.num_rows - 1))
+    }
+
+    fn cell_index(&self, point: FloatVec2) -> usize {
+        let (column, row) = self.cell_coordinates(point.x, point.y);
+        row * self.num_columns + column
+    }
+
+    /// the cells within the footprint of the trace: a trace width plus the clearance around its center line,
+    /// where a trace of the same width would collide with it, the clearance is raised to min_clearance
+    fn trace_cells(&self, trace_path: &TracePath, min_clearance: f32) -> BTreeSet<usize> {
+        let mut cells: BTreeSet<usize> = BTreeSet::new();
+        for segment in trace_path.segments.iter() {
+            let margin = segment.width + segment.clearance.max(min_clearance);
+            for point in sample_points(segment.start, segment.end) {
+                let (min_column, min_row) =
+                    self.cell_coordinates(point.x - margin, point.y - margin);
+                let (max_column, max_row) =
+                    self.cell_coordinates(point.x + margin, point.y + margin);
+                for row in min_row..=max_row {
+                    for column in min_column..=max_column {
+                        cells.insert(row * self.num_columns + column);
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    fn add_trace(&mut self, net_id: NetID, trace_path: &TracePath, min_clearance: f32) {
+        for cell in self.trace_cells(trace_path, min_clearance) {
+            *self.occupants[cell].entry(net_id).or_insert(0) += 1;
+        }
+    }
+
+    fn remove_trace(&mut self, net_id: NetID, trace_path: &TracePath, min_clearance: f32) {
+        for cell in self.trace_cells(trace_path, min_clearance) {
+            if let Some(count) = self.occupants[cell].get_mut(&net_id) {
+                *count -= 1;
+                if *count == 0 {
+                    self.occupants[cell].remove(&net_id);
+                }
+            }
+        }
+    }
+
+    /// raises the history cost of every cell that is shared by several nets
+    fn update_history(&mut self) {
+        for (history, occupants) in self.history.iter_mut().zip(self.occupants.iter()) {
+            if occupants.len() > 1 {
+                *history += HISTORY_CONGESTION_INCREMENT * (occupants.len() - 1) as f64;
+            }
+        }
+    }
+
+    /// the cost per unit length of a trace of net_id in the cell, on top of the base cost
+    fn cell_penalty(&self, cell: usize, net_id: NetID, present_factor: f64) -> f64 {
+        let other_nets = self.occupants[cell]
+            .keys()
+            .filter(|occupant| **occupant != net_id)
+            .count();
+        (1.0 + self.history[cell]) * (1.0 + present_factor * other_nets as f64) - 1.0
+    }
+}
+
+/// points along the segment, at most half a cell apart, including both ends
+fn sample_points(start: FixedVec2, end: FixedVec2) -> Vec<FloatVec2> {
+    let (start, end) = (start.to_float(), end.to_float());
+    let length = end.sub(start).magnitude2().sqrt();
+    let num_pieces = (length / (CONGESTION_CELL_SIZE / 2.0)).ceil().max(1.0) as usize;
+    (0..=num_pieces)
+        .map(|index| {
+            let t = index as f32 / num_pieces as f32;
+            FloatVec2 {
+                x: start.x + (end.x - start.x) * t,
+                y: start.y + (end.y - start.y) * t,
+            }
+        })
+        .collect()
+}
+
+/// the cost hook of the negotiated routing: the base cost plus the congestion of the cells that a segment crosses
+/// the penalties are never negative, so the estimate of the base cost stays admissible
+struct CongestionCost {
+    base: Arc<dyn RoutingCost>,
+    congestion_map: Arc<CongestionMap>, // a snapshot taken before the connection is routed
+    net_id: NetID,
+    present_factor: f64,
+}
+
+impl RoutingCost for CongestionCost {
+    fn segment_cost(&self, start: FixedVec2, end: FixedVec2) -> f64 {
+        let points = sample_points(start, end);
+        let piece_length = (end - start).length().to_num::<f64>() / (points.len() - 1) as f64;
+        let penalty: f64 = points
+            .windows(2)
+            .map(|pair| {
+                let middle = FloatVec2 {
+                    x: (pair[0].x + pair[1].x) / 2.0,
+                    y: (pair[0].y + pair[1].y) / 2.0,
+                };
+                let cell = self.congestion_map.cell_index(middle);
+                self.congestion_map
+                    .cell_penalty(cell, self.net_id, self.present_factor)
+            })
+            .sum();
+        self.base.segment_cost(start, end) + penalty * piece_length
+    }
+
+    fn turn_cost(&self, position: FixedVec2, turn_angle: f32) -> Option<f64> {
+        self.base.turn_cost(position, turn_angle)
+    }
+
+    fn estimated_cost(
+        &self,
+        position: FixedVec2,
+        end: FixedVec2,
+        routing_mode: RoutingMode,
+    ) -> f64 {
+        self.base.estimated_cost(position, end, routing_mode)
+    }
+}
+
+/// per routed connection, the routed connections of other nets that collide with it
+fn find_conflicts(
+    problem: &PcbProblem,
+    routes: &BTreeMap<ConnectionID, FixedTrace>,
+) -> BTreeMap<ConnectionID, BTreeSet<ConnectionID>> {
+    let routes_with_boxes: Vec<(&FixedTrace, _)> = routes
+        .values()
+        .filter_map(|fixed_trace| {
+            let bounding_box = fixed_trace.trace_path.clearance_bounding_box()?;
+            Some((fixed_trace, bounding_box))
+        })
+        .collect();
+    let mut conflicts: BTreeMap<ConnectionID, BTreeSet<ConnectionID>> = BTreeMap::new();
+    for (index, (fixed_trace, bounding_box)) in routes_with_boxes.iter().enumerate() {
+        for (other_trace, other_box) in routes_with_boxes[..index].iter() {
+            if other_trace.net_id == fixed_trace.net_id {
+                continue; // traces of the same net may overlap
+            }
+            let class_clearance =
+                problem.clearance_between_nets(fixed_trace.net_id, other_trace.net_id);
+            if !bounding_box
+                .inflate(class_clearance * 2.0)
+                .overlaps(&other_box.inflate(class_clearance * 2.0))
+                || !fixed_trace
+                    .trace_path
+                    .collides_with_clearance(&other_trace.trace_path, class_clearance)
+            {
+                continue;
+            }
+            conflicts
+                .entry(fixed_trace.connection_id)
+                .or_default()
+                .insert(other_trace.connection_id);
+            conflicts
+                .entry(other_trace.connection_id)
+                .or_default()
+                .insert(fixed_trace.connection_id);
+        }
+    }
+    conflicts
+}
+
+/// removes the routes with the most conflicts until no routes collide, returns the removed connections
+fn drop_conflicting_routes(
+    routes: &mut BTreeMap<ConnectionID, FixedTrace>,
+    mut conflicts: BTreeMap<ConnectionID, BTreeSet<ConnectionID>>,
+) -> Vec<ConnectionID> {
+    let mut dropped: Vec<ConnectionID> = Vec::new();
+    // on ties the connection with the larger ID is dropped, so that the result is deterministic
+    while let Some(connection_id) = conflicts
+        .iter()
+        .filter(|(_, others)| !others.is_empty())
+        .max_by_key(|(connection_id, others)| (others.len(), **connection_id))
+        .map(|(connection_id, _)| *connection_id)
+    {
+        let others = conflicts.remove(&connection_id).unwrap_or_default();
+        for other in others {
+            if let Some(other_conflicts) = conflicts.get_mut(&other) {
+                other_conflicts.remove(&connection_id);
+            }
+        }
+        routes.remove(&connection_id);
+        dropped.push(connection_id);
+    }
+    dropped
+}
+
+/// the connections whose A* search failed without an earlier route to keep
+fn unrouted_reasons(
+    astar_failures: &BTreeMap<ConnectionID, AStarFailure>,
+) -> BTreeMap<ConnectionID, UnroutedReason> {
+    astar_failures
+        .keys()
+        .map(|connection_id| {
+            let reason = reason_without_candidates(connection_id, astar_failures);
+            (*connection_id, reason)
+        })
+        .collect()
+}
+
+impl PcbProblem {
+    /// PathFinder-style negotiated congestion routing:
+    /// routes every connection while traces of different nets may overlap,
+    /// then rips up and reroutes the conflicting connections with growing congestion costs until no traces collide
+    pub(crate) fn solve_by_negotiation(
+        &self,
+        locked_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
+        max_iterations: usize,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<PcbSolution, RoutingError> {
+        if max_iterations == 0 {
+            return Err(RoutingError::InvalidInput(
+                "The negotiation needs at least one iteration".to_string(),
+            ));
+        }
+        // the connections are routed in the net order of the config, the locked traces are obstacles
+        let connections: Vec<Arc<Connection>> = self
+            .ordered_net_ids(config.net_ordering)
+            .iter()
+            .flat_map(|net_id| self.nets[net_id].connections.values().cloned())
+            .filter(|connection| !locked_traces.contains_key(&connection.connection_id))
+            .collect();
+        // the footprint of a net covers its largest clearance to any other net
+        let footprint_clearances: BTreeMap<NetID, f32> = self
+            .nets
+            .keys()
+            .map(|net_id| {
+                let max_clearance = self
+                    .nets
+                    .keys()
+                    .filter(|other_net_id| *other_net_id != net_id)
+                    .map(|other_net_id| self.clearance_between_nets(*net_id, *other_net_id))
+                    .fold(0.0, f32::max);
+                (*net_id, max_clearance)
+            })
+            .collect();
+        let mut congestion_map = CongestionMap::new(self.width, self.height);
+        let mut routes: BTreeMap<ConnectionID, FixedTrace> = BTreeMap::new();
+        let mut astar_failures: BTreeMap<ConnectionID, AStarFailure> = BTreeMap::new();
+        let mut present_factor = PRESENT_CONGESTION_FACTOR;
+        let mut reroute_ids: BTreeSet<ConnectionID> = connections
+            .iter()
+            .map(|connection| connection.connection_id)
+            .collect();
+
+        for iteration in 1..=max_iterations {
+            for connection in connections.iter() {
+                let connection_id = connection.connection_id;
+                if !reroute_ids.contains(&connection_id) {
+                    continue;
+                }
+                if let Some(stop_reason) = stop_condition.check() {
+                    return self.stopped_negotiation(config, routes, &astar_failures, stop_reason);
+                }
+                // rip up, the old route stays in routes until a new one is found
+                if let Some(old_trace) = routes.get(&connection_id) {
+                    congestion_map.remove_trace(
+                        connection.net_id,
+                        &old_trace.trace_path,
+                        footprint_clearances[&connection.net_id],
+                    );
+                }
+                let routing_cost = Arc::new(CongestionCost {
+                    base: self.routing_cost.clone(),
+                    congestion_map: Arc::new(congestion_map.clone()),
+                    net_id: connection.net_id,
+                    present_factor,
+                });
+                match self.route_with_congestion(
+                    connection,
+                    routing_cost,
+                    locked_traces,
+                    config,
+                    stop_condition,
+                    pcb_render_model.clone(),
+                )? {
+                    Ok(trace_path) => {
+                        astar_failures.remove(&connection_id);
+                        let fixed_trace = FixedTrace {
+                            net_id: connection.net_id,
+                            connection_id,
+                            trace_path,
+                        };
+                        routes.insert(connection_id, fixed_trace);
+                    }
+                    Err(failure) => {
+                        // the previous route, if any, is kept
+                        println!(
+                            "A* algorithm failed for ConnectionID {:?}: {}",
+                            connection_id, failure
+                        );
+                        if !routes.contains_key(&connection_id) {
+                            astar_failures.insert(connection_id, failure);
+                        }
+                    }
+                }
+                if let Some(fixed_trace) = routes.get(&connection_id) {
+                    congestion_map.add_trace(
+                        connection.net_id,
+                        &fixed_trace.trace_path,
+                        footprint_clearances[&connection.net_id],
+                    );
+                }
+            }
+            if let Some(stop_reason) = stop_condition.check() {
+                return self.stopped_negotiation(config, routes, &astar_failures, stop_reason);
+            }
+            // a connection that cannot be routed through other traces cannot be routed around them either
+            if !astar_failures.is_empty() && !config.allow_partial {
+                return Err(unroutable_without_candidates(
+                    astar_failures.keys().cloned().collect(),
+                    &astar_failures,
+                ));
+            }
+            let conflicts = find_conflicts(self, &routes);
+            println!(
+                "Negotiation iteration {}: {} connections in conflict, present congestion factor {:.3}",
+                iteration,
+                conflicts.len(),
+                present_factor
+            );
+            if conflicts.is_empty() {
+                return Ok(self.partial_solution(
+                    routes,
+                    unrouted_reasons(&astar_failures),
+                    UnroutedReason::Conflicting,
+                ));
+            }
+            congestion_map.update_history();
+            present_factor *= PRESENT_CONGESTION_GROWTH;
+            reroute_ids = conflicts.keys().cloned().collect();
+            reroute_ids.extend(astar_failures.keys().cloned());
+        }
+
+        let conflicts = find_conflicts(self, &routes);
+        if !config.allow_partial {
+            return Err(RoutingError::UnroutableConnections {
+                connection_ids: conflicts.keys().cloned().collect(),
+                reason: format!(
+                    "the negotiation did not resolve the conflicts within {} iterations",
+                    max_iterations
+                ),
+            });
+        }
+        // the routes that remain after dropping the most conflicting ones are the best effort
+        drop_conflicting_routes(&mut routes, conflicts);
+        Ok(self.partial_solution(
+            routes,
+            unrouted_reasons(&astar_failures),
+            UnroutedReason::Conflicting,
+        ))
+    }
+
+    /// the routes that do not collide when the negotiation stops early
+    fn stopped_negotiation(
+        &self,
+        config: &SolverConfig,
+        mut routes: BTreeMap<ConnectionID, FixedTrace>,
+        astar_failures: &BTreeMap<ConnectionID, AStarFailure>,
+        stop_reason: StopReason,
+    ) -> Result<PcbSolution, RoutingError> {
+        println!("Stopping the negotiation: {:?}", stop_reason);
+        let conflicts = find_conflicts(self, &routes);
+        drop_conflicting_routes(&mut routes, conflicts);
+        self.stopped_search(
+            config,
+            routes,
+            unrouted_reasons(astar_failures),
+            stop_reason,
+        )
+    }
+
+    /// runs A* for the connection, with the pads and the locked traces of the other nets as the only obstacles
+    fn route_with_congestion(
+        &self,
+        connection: &Connection,
+        routing_cost: Arc<dyn RoutingCost>,
+        locked_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<Result<TracePath, AStarFailure>, RoutingError> {
+        let (mut obstacle_shapes, mut obstacle_clearance_shapes) =
+            self.pad_obstacle_shapes(&[connection.net_id]);
+        let (trace_shapes, trace_clearance_shapes) =
+            self.trace_obstacle_shapes(&[connection.net_id], locked_traces.values());
+        obstacle_shapes.extend(trace_shapes);
+        obstacle_clearance_shapes.extend(trace_clearance_shapes);
+        let routing_mode = self
+            .nets
+            .get(&connection.net_id)
+            .ok_or_else(|| {
+                RoutingError::InternalInvariant(format!(
+                    "NetID {:?} not found in nets",
+                    connection.net_id
+                ))
+            })?
+            .routing_mode;
+        let mut astar_model = AStarModel {
+            width: self.width,
+            height: self.height,
+            obstacle_shapes,
+            obstacle_clearance_shapes,
+            start: FixedVec2::new(FixedPoint::ZERO, FixedPoint::ZERO), // set below
+            end: FixedVec2::new(FixedPoint::ZERO, FixedPoint::ZERO),
+            trace_width: self.trace_width(connection),
+            trace_clearance: self.trace_clearance(connection),
+            routing_mode,
+            routing_cost,
+            search_budget: config.astar_search_budget,
+            stop_condition: stop_condition.clone(),
+            border_cache: RefCell::new(None),
+            max_length: connection
+                .length_limits
+                .max_length
+                .map(|max_length| max_length as f64),
+            display: config.display_astar,
+        };
+        astar_model.start = astar_model.to_search_point(connection.source.position);
+        astar_model.end = astar_model.to_search_point(connection.sink.position);
+        match astar_model.run(pcb_render_model) {
+            Ok(astar_result) => Ok(Ok(astar_result.trace_path)),
+            Err(
+                RoutingError::SearchBudgetExhausted(failure) | RoutingError::NoPathFound(failure),
+            ) => Ok(Err(failure)),
+            Err(error) => Err(error),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{
+    astar::RoutingMode,
+    hyperparameters::{DEFAULT_TRACE_CLEARANCE, DEFAULT_TRACE_WIDTH},
+};
+
+pub const DEFAULT_NET_CLASS: &str = "Default"; // the class of nets without an explicit class
+
+/// the design rules shared by the nets of a class, e.g. Power or HighSpeed
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetClass {
+    pub name: String,
+    pub trace_width: f32,
+    pub trace_clearance: f32,
+    pub routing_mode: RoutingMode,
+    pub criticality: u32, // classes with a higher criticality are routed first by NetOrdering::CriticalityFirst
+}
+
+impl NetClass {
+    /// a class with the default rules
+    pub fn new(name: &str) -> Self {
+        NetClass {
+            name: name.to_string(),
+            trace_width: DEFAULT_TRACE_WIDTH,
+            trace_clearance: DEFAULT_TRACE_CLEARANCE,
+            routing_mode: RoutingMode::default(),
+            criticality: 0,
+        }
+    }
+}
+
+impl Default for NetClass {
+    fn default() -> Self {
+        NetClass::new(DEFAULT_NET_CLASS)
+    }
+}
+
+/// the key of a class pair in the clearance matrix, independent of the order of the classes
+pub fn class_pair_key(class1: &str, class2: &str) -> (String, String) {
+    if class1 <= class2 {
+        (class1.to_string(), class2.to_string())
+    } else {
+        (class2.to_string(), class1.to_string())
+    }
+}
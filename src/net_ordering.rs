@@ -0,0 +1,111 @@
+use crate::{
+    pcb_problem::{NetID, PcbProblem},
+    prim_shape::BoundingBox,
+};
+
+/// the order in which the negotiation routes the nets, ties are broken by NetID
+/// the sampler samples the nets independently and only merges their candidates in this order,
+/// and the node stack, exact and beam searches fix the candidates by their posteriors, so the order hardly matters there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NetOrdering {
+    #[default]
+    ById,
+    ShortestFirst,        // by the total straight-line length of the connections
+    MostConstrainedFirst, // by the other pads and nets around the pads of the net, most first
+    CriticalityFirst,     // by the criticality of the net class, highest first
+    PriorityFirst,        // by PcbProblem::set_net_priority, highest first
+}
+
+impl NetOrdering {
+    pub const ALL: [NetOrdering; 5] = [
+        NetOrdering::ById,
+        NetOrdering::ShortestFirst,
+        NetOrdering::MostConstrainedFirst,
+        NetOrdering::CriticalityFirst,
+        NetOrdering::PriorityFirst,
+    ];
+}
+
+impl PcbProblem {
+    /// the nets in the order of the heuristic
+    pub fn ordered_net_ids(&self, ordering: NetOrdering) -> Vec<NetID> {
+        // lower keys come first, the sort is stable and the nets start in ID order
+        let mut keyed_net_ids: Vec<(f64, NetID)> = self
+            .nets
+            .keys()
+            .map(|net_id| (self.net_ordering_key(*net_id, ordering), *net_id))
+            .collect();
+        keyed_net_ids.sort_by(|(key1, _), (key2, _)| key1.total_cmp(key2));
+        keyed_net_ids
+            .into_iter()
+            .map(|(_, net_id)| net_id)
+            .collect()
+    }
+
+    fn net_ordering_key(&self, net_id: NetID, ordering: NetOrdering) -> f64 {
+        let net_info = &self.nets[&net_id];
+        match ordering {
+            NetOrdering::ById => 0.0,
+            NetOrdering::ShortestFirst => net_info
+                .connections
+                .values()
+                .map(|connection| {
+                    let dx = connection.sink.position.x - connection.source.position.x;
+                    let dy = connection.sink.position.y - connection.source.position.y;
+                    (dx * dx + dy * dy).sqrt() as f64
+                })
+                .sum(),
+            NetOrdering::MostConstrainedFirst => -(self.net_constraint_count(net_id) as f64),
+            NetOrdering::CriticalityFirst => {
+                let criticality = self
+                    .net_classes
+                    .get(self.net_class_of(net_id))
+                    .map_or(0, |net_class| net_class.criticality);
+                -(criticality as f64)
+            }
+            NetOrdering::PriorityFirst => -(net_info.priority as f64),
+        }
+    }
+
+    /// the pads of other nets inside the bounding box of the pads of the net,
+    /// plus the other nets whose bounding boxes overlap it
+    fn net_constraint_count(&self, net_id: NetID) -> usize {
+        let pad_box = |net_id: NetID| {
+            let net_info = &self.nets[&net_id];
+            let margin = net_info
+                .connections
+                .values()
+                .map(|connection| {
+                    self.trace_width(connection) / 2.0 + self.trace_clearance(connection)
+                })
+                .fold(0.0, f32::max);
+            let positions = net_info
+                .connections
+                .values()
+                .flat_map(|connection| [connection.source.position, connection.sink.position]);
+            BoundingBox::from_points(positions).map(|bounding_box| bounding_box.inflate(margin))
+        };
+        let Some(net_box) = pad_box(net_id) else {
+            return 0; // a net without connections constrains nothing
+        };
+        let mut count = 0;
+        for (other_net_id, other_net_info) in self.nets.iter() {
+            if *other_net_id == net_id {
+                continue;
+            }
+            if pad_box(*other_net_id).is_some_and(|other_box| net_box.overlaps(&other_box)) {
+                count += 1;
+            }
+            for connection in other_net_info.connections.values() {
+                for pad in [&connection.source, &connection.sink] {
+                    if BoundingBox::from_points([pad.position])
+                        .is_some_and(|pad_box| net_box.overlaps(&pad_box))
+                    {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    }
+}
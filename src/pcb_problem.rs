@@ -1,32 +1,42 @@
-use core::panic;
 use std::{
     cell::RefCell,
-    collections::{BTreeSet, BinaryHeap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, BinaryHeap},
+    fmt,
     num::NonZeroUsize,
-    rc::Rc,
     sync::{Arc, Mutex},
 };
 
 use ordered_float::NotNan;
 use rand::{
+    Rng, SeedableRng,
     distr::{Distribution, weighted::WeightedIndex},
     rand_core::block,
+    rngs::StdRng,
 };
+use rayon::prelude::*;
 
 use crate::{
-    astar::AStarModel,
+    astar::{AStarFailure, AStarFailureReason, AStarModel, RoutingMode},
     binary_heap_item::BinaryHeapItem,
     block_or_sleep::{block_or_sleep, block_thread},
+    diff_pair::{DiffPair, DiffPairID},
+    exact_selection::{ExactSelection, select_exactly},
     hyperparameters::{
-        CONSTANT_LEARNING_RATE, ITERATION_TO_NUM_TRACES, ITERATION_TO_PRIOR_PROBABILITY,
-        LINEAR_LEARNING_RATE, MAX_GENERATION_ATTEMPTS, NEXT_ITERATION_TO_REMAINING_PROBABILITY,
-        OPPORTUNITY_COST_WEIGHT, SCORE_WEIGHT,
+        ITERATION_TO_NUM_TRACES, ITERATION_TO_PRIOR_PROBABILITY, MAX_GENERATION_ATTEMPTS,
+        MAX_ITERATION, NEXT_ITERATION_TO_REMAINING_PROBABILITY,
     },
+    length_matching::LengthMatchGroup,
+    net_class::{DEFAULT_NET_CLASS, NetClass, class_pair_key},
     pad::Pad,
     pcb_render_model::{PcbRenderModel, RenderableBatch, ShapeRenderable, UpdatePcbRenderModel},
-    prim_shape::PrimShape,
+    prim_shape::{BoundingBox, PrimShape},
+    problem_validation::Diagnostic,
+    routing_cost::{DefaultRoutingCost, RoutingCost},
+    routing_error::{RoutingError, ensure_invariant},
+    solver_config::{SolverConfig, SolverStrategy},
+    stop_condition::{StopCondition, StopReason},
     trace_path::{TraceAnchors, TracePath},
-    vec2::{FixedPoint, FixedVec2},
+    vec2::FixedVec2,
 };
 
 // use shared::interface_types::{Color, ColorGrid};
@@ -49,6 +59,52 @@ impl Color {
             alpha,
         ]
     }
+
+    /// the display color of the index-th automatically colored net
+    /// the hues are spread by the golden angle, so that neighboring indices look different
+    pub fn from_palette(index: usize) -> Self {
+        let hue = (index as f32 * 137.507_77) % 360.0;
+        let saturation = 0.75;
+        let value = 0.95;
+        // hsv to rgb
+        let chroma = value * saturation;
+        let hue_sector = hue / 60.0;
+        let x = chroma * (1.0 - (hue_sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue_sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+        let to_u8 = |channel: f32| ((channel + m) * 255.0).round() as u8;
+        Color {
+            r: to_u8(r),
+            g: to_u8(g),
+            b: to_u8(b),
+        }
+    }
+}
+
+/// the absolute limits of the length of the trace of a connection, e.g. of a clock trace or a delay line
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LengthLimits {
+    pub min_length: Option<f32>, // soft, shorter traces score worse and get meanders from the length matching
+    pub max_length: Option<f32>, // hard, the A* search prunes longer paths and longer candidates are rejected
+}
+
+impl LengthLimits {
+    pub fn exceeds_max_length(&self, length: f64) -> bool {
+        self.max_length
+            .is_some_and(|max_length| length > max_length as f64)
+    }
+    /// how much longer the trace has to be to reach the min length, 0.0 if it is long enough
+    pub fn missing_length(&self, length: f64) -> f64 {
+        self.min_length
+            .map_or(0.0, |min_length| (min_length as f64 - length).max(0.0))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -57,16 +113,21 @@ pub struct Connection {
     pub connection_id: ConnectionID, // Unique identifier for the connection
     pub source: Pad,
     pub sink: Pad,
-    pub trace_width: f32, // Width of the trace
-    pub trace_clearance: f32, // Clearance around the trace
-                          // pub traces: HashMap<TraceID, TraceInfo>, // List of traces connecting the source and sink pads
+    pub length_limits: LengthLimits,
+    pub trace_width: Option<f32>, // Width of the trace, None for the width of the net's class
+    pub trace_clearance: Option<f32>, // Clearance around the trace, None for the clearance of the net's class
+                          // pub traces: BTreeMap<TraceID, TraceInfo>, // List of traces connecting the source and sink pads
 }
 
 #[derive(Debug, Clone)]
 pub struct NetInfo {
     pub net_id: NetID,
-    pub color: Color,                                       // Color of the net
-    pub connections: HashMap<ConnectionID, Rc<Connection>>, // List of connections in the net, the source pad is the same
+    pub name: String,              // e.g. GND, VCC_3V3, unique in the problem
+    pub net_class: Option<String>, // The name of the net class, e.g. Power
+    pub color: Color,              // Display color of the net, not necessarily unique
+    pub connections: BTreeMap<ConnectionID, Arc<Connection>>, // List of connections in the net, the source pad is the same
+    pub routing_mode: RoutingMode, // The segment angles the traces of this net may use
+    pub priority: i32, // nets with a higher priority are routed first by NetOrdering::PriorityFirst
 }
 
 #[derive(Copy, Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
@@ -78,33 +139,40 @@ pub struct ProbaTraceID(pub usize);
 
 #[derive(Debug)]
 pub struct ProbaTrace {
-    pub net_id: NetID,                        // The net that the trace belongs to
-    pub connection_id: ConnectionID,          // The connection that the trace belongs to
-    pub proba_trace_id: ProbaTraceID,         // Unique identifier for the trace
-    pub trace_path: TracePath,                // The path of the trace
+    pub net_id: NetID,                      // The net that the trace belongs to
+    pub connection_id: ConnectionID,        // The connection that the trace belongs to
+    pub proba_trace_id: ProbaTraceID,       // Unique identifier for the trace
+    pub trace_path: TracePath,              // The path of the trace
     pub iteration: NonZeroUsize, // The iteration that the trace belongs to, starting from 1
-    pub posterior: RefCell<Option<f64>>, // to be accessed in the next iteration
-    pub temp_posterior: RefCell<Option<f64>>, // serve as a buffer for simultaneous updates
+    pub posterior: Mutex<Option<f64>>, // to be accessed in the next iteration
+    pub temp_posterior: Mutex<Option<f64>>, // serve as a buffer for simultaneous updates
+    pub length_limits: LengthLimits, // the limits of the connection, which the score of the trace depends on
 }
 
 impl ProbaTrace {
-    fn get_normalized_prior(&self) -> f64 {
-        *ITERATION_TO_PRIOR_PROBABILITY
+    pub fn get_normalized_prior(&self) -> Result<f64, RoutingError> {
+        ITERATION_TO_PRIOR_PROBABILITY
             .get(&self.iteration)
-            .expect(format!("No prior probability for iteration {:?}", self.iteration).as_str())
+            .copied()
+            .ok_or_else(|| {
+                RoutingError::InternalInvariant(format!(
+                    "No prior probability for iteration {:?}",
+                    self.iteration
+                ))
+            })
     }
 
-    pub fn get_posterior_with_fallback(&self) -> f64 {
-        let posterior = self.posterior.borrow();
+    pub fn get_posterior_with_fallback(&self) -> Result<f64, RoutingError> {
+        let posterior = self.posterior.lock().unwrap();
         if let Some(posterior) = posterior.as_ref() {
-            *posterior
+            Ok(*posterior)
         } else {
             self.get_normalized_prior()
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FixedTrace {
     pub net_id: NetID,               // The net that the trace belongs to
     pub connection_id: ConnectionID, // The connection that the trace belongs to
@@ -114,7 +182,7 @@ pub struct FixedTrace {
 #[derive(Debug, Clone)]
 pub enum Traces {
     Fixed(FixedTrace), // A trace that is fixed and does not change
-    Probabilistic(HashMap<ProbaTraceID, Rc<ProbaTrace>>), // A trace that is probabilistic and can change
+    Probabilistic(BTreeMap<ProbaTraceID, Arc<ProbaTrace>>), // A trace that is probabilistic and can change
 }
 
 // backtrack search:
@@ -128,94 +196,349 @@ pub enum Traces {
 pub struct PcbProblem {
     pub width: f32,
     pub height: f32,
-    pub nets: HashMap<NetID, NetInfo>, // NetID to NetInfo
-    pub net_id_generator: Box<dyn Iterator<Item = NetID> + Send + 'static>, // A generator for NetID, starting from 0
-    pub connection_id_generator: Box<dyn Iterator<Item = ConnectionID> + Send + 'static>, // A generator for ConnectionID, starting from 0
+    pub nets: BTreeMap<NetID, NetInfo>,     // NetID to NetInfo
+    pub net_names: BTreeMap<String, NetID>, // net name to NetID
+    pub net_id_generator: Box<dyn Iterator<Item = NetID> + Send + Sync + 'static>, // A generator for NetID, starting from 0
+    pub connection_id_generator: Box<dyn Iterator<Item = ConnectionID> + Send + Sync + 'static>, // A generator for ConnectionID, starting from 0
+    pub routing_cost: Arc<dyn RoutingCost>, // The cost model of the A* search for all connections
+    pub net_classes: BTreeMap<String, NetClass>, // class name to NetClass, always contains the default class
+    pub class_clearances: BTreeMap<(String, String), f32>, // class pair key to the minimum clearance between their nets
+    pub diff_pairs: BTreeMap<DiffPairID, DiffPair>, // the pairs of connections routed side by side
+    pub diff_pair_id_generator: Box<dyn Iterator<Item = DiffPairID> + Send + Sync + 'static>, // A generator for DiffPairID, starting from 0
+    pub length_match_groups: BTreeMap<String, LengthMatchGroup>, // group name to the connections tuned to the same length
 }
 
 // a connection can have either a determined trace or multiple probabilistic traces
 pub struct ProbaModel {
-    pub trace_id_generator: Box<dyn Iterator<Item = ProbaTraceID> + Send + 'static>, // A generator for TraceID, starting from 0
-    pub connection_to_traces: HashMap<ConnectionID, Traces>, // ConnectionID to list of traces
+    pub next_proba_trace_id: ProbaTraceID, // the ID of the next sampled trace, starting from 0 and never reused by the updates of the model
+    pub connection_to_traces: BTreeMap<ConnectionID, Traces>, // ConnectionID to list of traces
     // pub visited_traces: BTreeSet<TraceAnchors>,
-    pub collision_adjacency: HashMap<ProbaTraceID, HashSet<ProbaTraceID>>, // TraceID to set of colliding TraceIDs
+    pub collision_adjacency: BTreeMap<ProbaTraceID, BTreeSet<ProbaTraceID>>, // TraceID to set of colliding TraceIDs
     pub next_iteration: NonZeroUsize, // The next iteration to be processed, starting from 1
+    pub astar_failures: BTreeMap<ConnectionID, AStarFailure>, // The latest failed A* search of each connection
+    pub rng: StdRng, // samples the obstacle traces, seeded by the solver config
+    pub convergence_stats: Vec<ConvergenceStats>, // one entry per finished sampling iteration
+    // the connections an incremental update kept without resampling, to the next iteration they had reached
+    pub frozen_connections: BTreeMap<ConnectionID, NonZeroUsize>,
+}
+
+impl fmt::Debug for ProbaModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProbaModel")
+            .field("connection_to_traces", &self.connection_to_traces)
+            .field("next_iteration", &self.next_iteration)
+            .field("astar_failures", &self.astar_failures)
+            .field("frozen_connections", &self.frozen_connections)
+            .finish_non_exhaustive()
+    }
+}
+
+/// how the posterior updates of one sampling iteration converged
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvergenceStats {
+    pub iteration: NonZeroUsize,   // the sampling iteration, starting from 1
+    pub num_updates: usize,        // the number of update_posterior calls
+    pub max_posterior_change: f64, // the largest change of a posterior in the last update
+    pub converged: bool,           // whether the change fell under the tolerance before the cap
+}
+
+/// the state of the model before sampling, shared read-only by the per-net sampling tasks
+struct SamplingSnapshot {
+    proba_traces: BTreeMap<ProbaTraceID, Arc<ProbaTrace>>, // proba_trace_id to proba_trace
+    visited_traces: BTreeSet<TraceAnchors>,                // the anchors of all existing traces
+    normalized_posteriors: BTreeMap<ProbaTraceID, f64>, // temporary normalized posterior for each proba_trace
+    stop_condition: StopCondition,                      // ends the sampling of every net early
+}
+
+/// the traces and A* outcomes of sampling one net, merged into the model in net order
+struct NetSamples {
+    trace_paths: Vec<(ConnectionID, TracePath)>,
+    astar_outcomes: Vec<(ConnectionID, Option<AStarFailure>)>, // None for a successful search
 }
 
 impl ProbaModel {
     pub fn create_and_solve(
         problem: &PcbProblem,
-        fixed_traces: &HashMap<ConnectionID, FixedTrace>,
+        fixed_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
         pcb_render_model: Arc<Mutex<PcbRenderModel>>,
-    ) -> Self {
+    ) -> Result<Self, RoutingError> {
+        // the priors and trace counts are only defined up to MAX_ITERATION
+        if config.num_iterations > MAX_ITERATION {
+            return Err(RoutingError::InvalidInput(format!(
+                "The number of iterations must be at most {}, got {}",
+                MAX_ITERATION, config.num_iterations
+            )));
+        }
         let mut connection_ids: Vec<ConnectionID> = Vec::new();
         for net_info in problem.nets.values() {
             for connection in net_info.connections.keys() {
                 connection_ids.push(*connection);
             }
         }
-        let mut connection_to_traces: HashMap<ConnectionID, Traces> = HashMap::new();
+        let mut connection_to_traces: BTreeMap<ConnectionID, Traces> = BTreeMap::new();
         for connection_id in connection_ids {
             let traces = if let Some(fixed_trace) = fixed_traces.get(&connection_id) {
                 Traces::Fixed(fixed_trace.clone())
             } else {
-                Traces::Probabilistic(HashMap::new())
+                Traces::Probabilistic(BTreeMap::new())
             };
             connection_to_traces.insert(connection_id, traces);
         }
         let mut proba_model = ProbaModel {
-            trace_id_generator: Box::new((0..).map(ProbaTraceID)),
+            next_proba_trace_id: ProbaTraceID(0),
             connection_to_traces,
-            collision_adjacency: HashMap::new(),
+            collision_adjacency: BTreeMap::new(),
             next_iteration: NonZeroUsize::new(1).expect("Next iteration must be non-zero"),
+            astar_failures: BTreeMap::new(),
+            rng: config.new_rng(),
+            convergence_stats: Vec::new(),
+            frozen_connections: BTreeMap::new(),
         };
-        // display and block
-        let display_and_block = |proba_model: &ProbaModel| {
-            let render_model = proba_model.to_pcb_render_model(problem);
-            pcb_render_model.update_pcb_render_model(render_model);
-            block_or_sleep();
-        };
-        display_and_block(&proba_model);
-        block_thread();
-
-        // sample and then update posterior
-        // to do: specify iteration number
-        for j in 0..2 {
-            println!("Sampling new traces for iteration {}", j + 1);
-            proba_model.sample_new_traces(problem, pcb_render_model.clone());
-            display_and_block(&proba_model);
-            block_thread();
-
-            for i in 0..10 {
-                println!("Updating posterior for the {}th time", i + 1);
-                proba_model.update_posterior();
-                display_and_block(&proba_model);
+        proba_model.solve(problem, config, stop_condition, pcb_render_model)?;
+        Ok(proba_model)
+    }
+
+    /// the model of the same problem with more fixed traces, built from this one instead of from scratch:
+    /// the candidates that collide with the new fixed traces are dropped, the other candidates keep their posteriors,
+    /// and only the connections that lost candidates or have a candidate near a new fixed trace are sampled again
+    pub fn update_incrementally(
+        &self,
+        problem: &PcbProblem,
+        fixed_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<Self, RoutingError> {
+        if config.num_iterations > MAX_ITERATION {
+            return Err(RoutingError::InvalidInput(format!(
+                "The number of iterations must be at most {}, got {}",
+                MAX_ITERATION, config.num_iterations
+            )));
+        }
+        // the fixed traces that this model does not have, also those of connections whose fixed trace was edited
+        let new_fixed_traces: Vec<&FixedTrace> = fixed_traces
+            .iter()
+            .filter(|(connection_id, fixed_trace)| {
+                !matches!(
+                    self.connection_to_traces.get(connection_id),
+                    Some(Traces::Fixed(old_fixed_trace)) if old_fixed_trace == *fixed_trace
+                )
+            })
+            .map(|(_, fixed_trace)| fixed_trace)
+            .collect();
+        // the fixed traces of this model that were edited or are no longer fixed
+        let old_fixed_traces = self
+            .connection_to_traces
+            .iter()
+            .filter_map(|(connection_id, traces)| match traces {
+                Traces::Fixed(old_fixed_trace)
+                    if fixed_traces.get(connection_id) != Some(old_fixed_trace) =>
+                {
+                    Some(old_fixed_trace)
+                }
+                _ => None,
+            });
+        // the boxes of the changed fixed traces, the obstacles that the candidates nearby were sampled against have changed
+        let changed_boxes: Vec<(NetID, BoundingBox)> = new_fixed_traces
+            .iter()
+            .cloned()
+            .chain(old_fixed_traces)
+            .filter_map(|fixed_trace| {
+                let bounding_box = fixed_trace.trace_path.clearance_bounding_box()?;
+                Some((fixed_trace.net_id, bounding_box))
+            })
+            .collect();
+        let mut connection_to_traces: BTreeMap<ConnectionID, Traces> = BTreeMap::new();
+        let mut frozen_connections: BTreeMap<ConnectionID, NonZeroUsize> = BTreeMap::new();
+        for (connection_id, traces) in self.connection_to_traces.iter() {
+            if let Some(fixed_trace) = fixed_traces.get(connection_id) {
+                connection_to_traces.insert(*connection_id, Traces::Fixed(fixed_trace.clone()));
+                continue;
+            }
+            let Traces::Probabilistic(trace_map) = traces else {
+                // a trace that is no longer fixed is sampled again
+                connection_to_traces.insert(*connection_id, Traces::Probabilistic(BTreeMap::new()));
+                continue;
+            };
+            let mut kept_trace_map: BTreeMap<ProbaTraceID, Arc<ProbaTrace>> = BTreeMap::new();
+            for (proba_trace_id, proba_trace) in trace_map.iter() {
+                let collides = new_fixed_traces.iter().any(|fixed_trace| {
+                    fixed_trace.net_id != proba_trace.net_id
+                        && proba_trace.trace_path.collides_with_clearance(
+                            &fixed_trace.trace_path,
+                            problem.clearance_between_nets(proba_trace.net_id, fixed_trace.net_id),
+                        )
+                });
+                if collides {
+                    continue;
+                }
+                // a copy, so that the posterior updates of the new model do not change this one
+                let kept_trace = ProbaTrace {
+                    net_id: proba_trace.net_id,
+                    connection_id: proba_trace.connection_id,
+                    proba_trace_id: *proba_trace_id,
+                    trace_path: proba_trace.trace_path.clone(),
+                    iteration: proba_trace.iteration,
+                    posterior: Mutex::new(*proba_trace.posterior.lock().unwrap()),
+                    temp_posterior: Mutex::new(None),
+                    length_limits: proba_trace.length_limits,
+                };
+                kept_trace_map.insert(*proba_trace_id, Arc::new(kept_trace));
             }
-            block_thread();
+            let near_changed_region = kept_trace_map.values().any(|proba_trace| {
+                let Some(trace_box) = proba_trace.trace_path.clearance_bounding_box() else {
+                    return false;
+                };
+                changed_boxes.iter().any(|(net_id, changed_box)| {
+                    let class_clearance =
+                        problem.clearance_between_nets(proba_trace.net_id, *net_id);
+                    *net_id != proba_trace.net_id
+                        && trace_box
+                            .inflate(class_clearance * 2.0)
+                            .overlaps(&changed_box.inflate(class_clearance * 2.0))
+                })
+            });
+            // only the connections that lost candidates or are close to a new fixed trace are affected by it
+            if kept_trace_map.len() == trace_map.len() && !near_changed_region {
+                let reached_iteration = self
+                    .frozen_connections
+                    .get(connection_id)
+                    .unwrap_or(&self.next_iteration);
+                frozen_connections.insert(*connection_id, *reached_iteration);
+            }
+            connection_to_traces.insert(*connection_id, Traces::Probabilistic(kept_trace_map));
         }
+        let mut proba_model = ProbaModel {
+            // the new traces get IDs after all those this model has handed out
+            next_proba_trace_id: self.next_proba_trace_id,
+            connection_to_traces,
+            collision_adjacency: BTreeMap::new(),
+            next_iteration: NonZeroUsize::new(1).expect("Next iteration must be non-zero"),
+            astar_failures: self.astar_failures.clone(),
+            // continues the stream of this model, so that every update samples new candidates
+            rng: StdRng::seed_from_u64(self.rng.clone().random()),
+            convergence_stats: Vec::new(),
+            frozen_connections,
+        };
+        let kept_proba_trace_ids: BTreeSet<ProbaTraceID> =
+            proba_model.probabilistic_traces().keys().cloned().collect();
+        proba_model.collision_adjacency = self
+            .collision_adjacency
+            .iter()
+            .filter(|(proba_trace_id, _)| kept_proba_trace_ids.contains(proba_trace_id))
+            .map(|(proba_trace_id, adjacent_trace_ids)| {
+                let adjacent_trace_ids = adjacent_trace_ids
+                    .intersection(&kept_proba_trace_ids)
+                    .cloned()
+                    .collect();
+                (*proba_trace_id, adjacent_trace_ids)
+            })
+            .collect();
         proba_model
+            .astar_failures
+            .retain(|connection_id, _| !fixed_traces.contains_key(connection_id));
+        proba_model.solve(problem, config, stop_condition, pcb_render_model)?;
+        Ok(proba_model)
+    }
+
+    /// whether the sampler adds traces to the connection
+    fn samples_connection(&self, connection_id: &ConnectionID) -> bool {
+        matches!(
+            self.connection_to_traces.get(connection_id),
+            Some(Traces::Probabilistic(_))
+        ) && !self.frozen_connections.contains_key(connection_id)
+    }
+
+    fn display_and_block(
+        &self,
+        problem: &PcbProblem,
+        stop_condition: &StopCondition,
+        pcb_render_model: &Arc<Mutex<PcbRenderModel>>,
+    ) {
+        let render_model = self.to_pcb_render_model(problem);
+        pcb_render_model.update_pcb_render_model(render_model);
+        block_or_sleep(stop_condition);
+    }
+
+    /// samples and then updates the posteriors until they converge, for config.num_iterations iterations
+    fn solve(
+        &mut self,
+        problem: &PcbProblem,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<(), RoutingError> {
+        self.display_and_block(problem, stop_condition, &pcb_render_model);
+        if config.wait_for_enter {
+            block_thread(stop_condition);
+        }
+
+        // sample and then update posterior until it converges
+        for _ in 0..config.num_iterations.get() {
+            if let Some(stop_reason) = stop_condition.check() {
+                // the model keeps the traces of the finished iterations
+                println!("Stopping the sampling: {:?}", stop_reason);
+                break;
+            }
+            let iteration = self.next_iteration;
+            println!("Sampling new traces for iteration {}", iteration);
+            self.sample_new_traces(problem, config, stop_condition, pcb_render_model.clone())?;
+            self.display_and_block(problem, stop_condition, &pcb_render_model);
+            if config.wait_for_enter {
+                block_thread(stop_condition);
+            }
+
+            let mut stats = ConvergenceStats {
+                iteration,
+                num_updates: 0,
+                max_posterior_change: 0.0,
+                converged: false,
+            };
+            while stats.num_updates < config.max_posterior_updates {
+                stats.num_updates += 1;
+                stats.max_posterior_change = config.inference_engine.update_posteriors(self)?;
+                self.display_and_block(problem, stop_condition, &pcb_render_model);
+                if stats.max_posterior_change < config.posterior_tolerance {
+                    stats.converged = true;
+                    break;
+                }
+            }
+            println!(
+                "Iteration {}: {} posterior updates, max posterior change {:.6}, converged: {}",
+                stats.iteration, stats.num_updates, stats.max_posterior_change, stats.converged
+            );
+            self.convergence_stats.push(stats);
+            if config.wait_for_enter {
+                block_thread(stop_condition);
+            }
+        }
+        Ok(())
     }
 
     fn sample_new_traces(
         &mut self,
         problem: &PcbProblem,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
         pcb_render_model: Arc<Mutex<PcbRenderModel>>,
-    ) {
-        let mut new_proba_traces: Vec<Rc<ProbaTrace>> = Vec::new();
+    ) -> Result<(), RoutingError> {
+        if stop_condition.check().is_some() {
+            return Ok(()); // the iteration is not counted
+        }
+        let mut new_proba_traces: Vec<Arc<ProbaTrace>> = Vec::new();
 
-        // connection_id to connection
-        let mut connections: HashMap<ConnectionID, Rc<Connection>> = HashMap::new();
         // connection_id to net_id
-        let mut connection_to_net: HashMap<ConnectionID, NetID> = HashMap::new();
+        let mut connection_to_net: BTreeMap<ConnectionID, NetID> = BTreeMap::new();
         for (net_id, net_info) in problem.nets.iter() {
-            for (connection_id, connection) in net_info.connections.iter() {
-                connections.insert(*connection_id, connection.clone());
+            for connection_id in net_info.connections.keys() {
                 connection_to_net.insert(*connection_id, *net_id);
             }
         }
 
         // proba_trace_id to proba_trace
-        let mut proba_traces: HashMap<ProbaTraceID, Rc<ProbaTrace>> = HashMap::new();
+        let mut proba_traces: BTreeMap<ProbaTraceID, Arc<ProbaTrace>> = BTreeMap::new();
         // visited TraceAnchors
         let mut visited_traces: BTreeSet<TraceAnchors> = BTreeSet::new();
         for traces in self.connection_to_traces.values() {
@@ -228,343 +551,145 @@ impl ProbaModel {
         }
 
         // net_id to proba_trace_ids
-        let mut net_to_proba_traces: HashMap<NetID, Vec<ProbaTraceID>> = problem
+        let mut net_to_proba_traces: BTreeMap<NetID, Vec<ProbaTraceID>> = problem
             .nets
             .keys()
             .map(|net_id| (*net_id, Vec::new()))
             .collect();
         for (connection_id, traces) in self.connection_to_traces.iter() {
             if let Traces::Probabilistic(trace_ids) = traces {
-                let net_id = connection_to_net.get(connection_id).expect(
-                    format!(
+                let net_id = connection_to_net.get(connection_id).ok_or_else(|| {
+                    RoutingError::InternalInvariant(format!(
                         "ConnectionID {:?} not found in connection_id_to_net_id",
                         connection_id
-                    )
-                    .as_str(),
-                );
+                    ))
+                })?;
                 net_to_proba_traces
                     .get_mut(net_id)
-                    .expect(format!("NetID {:?} not found in net_to_proba_traces", net_id).as_str())
+                    .ok_or_else(|| {
+                        RoutingError::InternalInvariant(format!(
+                            "NetID {:?} not found in net_to_proba_traces",
+                            net_id
+                        ))
+                    })?
                     .extend(trace_ids.keys().cloned());
             }
         }
         // temporary normalized posterior for each proba_trace
-        let mut temp_normalized_posteriors: HashMap<ProbaTraceID, f64> = HashMap::new();
-        for (_, traces) in self.connection_to_traces.iter() {
+        let mut temp_normalized_posteriors: BTreeMap<ProbaTraceID, f64> = BTreeMap::new();
+        for (connection_id, traces) in self.connection_to_traces.iter() {
             if let Traces::Probabilistic(trace_ids) = traces {
                 let mut sum_posterior: f64 = 0.0;
                 for (_, proba_trace) in trace_ids.iter() {
-                    let posterior = proba_trace.get_posterior_with_fallback();
+                    let posterior = proba_trace.get_posterior_with_fallback()?;
                     sum_posterior += posterior;
                 }
+                // a frozen connection stays at the iteration it had reached
+                let next_iteration = self
+                    .frozen_connections
+                    .get(connection_id)
+                    .unwrap_or(&self.next_iteration);
                 sum_posterior += NEXT_ITERATION_TO_REMAINING_PROBABILITY
-                    .get(&self.next_iteration)
-                    .expect(
-                        format!(
+                    .get(next_iteration)
+                    .ok_or_else(|| {
+                        RoutingError::InternalInvariant(format!(
                             "No remaining probability for iteration {:?}",
-                            self.next_iteration
-                        )
-                        .as_str(),
-                    );
+                            next_iteration
+                        ))
+                    })?;
                 // normalize the posterior for each trace
                 // divide each posterior by the sum of all posteriors
                 for (proba_trace_id, proba_trace) in trace_ids.iter() {
-                    let posterior = proba_trace.get_posterior_with_fallback();
+                    let posterior = proba_trace.get_posterior_with_fallback()?;
                     let normalized_posterior = posterior / sum_posterior;
                     temp_normalized_posteriors.insert(*proba_trace_id, normalized_posterior);
                 }
             }
         }
 
-        // the outer loop for generating the dijkstra model
-        for net_id in problem.nets.keys() {
-            // collect connections that are not in this net
-            let obstacle_connections: HashSet<ConnectionID> = problem
-                .nets
-                .iter()
-                .filter(|(other_net_id, _)| **other_net_id != *net_id)
-                .flat_map(|(_, net_info)| net_info.connections.keys())
-                .cloned()
-                .collect();
-            // initialize the number of generated traces for each connection
-            let mut num_generated_traces: HashMap<ConnectionID, usize> = self
-                .connection_to_traces
-                .keys()
-                .map(|connection_id| (*connection_id, 0))
-                .collect();
-            // initialize the number of generation attempts
-            let mut num_generation_attempts: usize = 0;
-            // the inner loop for generating traces for each connection in the net
-            let max_num_traces = *ITERATION_TO_NUM_TRACES.get(&self.next_iteration).expect(
-                format!(
-                    "No number of traces for iteration {:?}",
-                    self.next_iteration
+        let snapshot = SamplingSnapshot {
+            proba_traces,
+            visited_traces,
+            normalized_posteriors: temp_normalized_posteriors,
+            stop_condition: stop_condition.clone(),
+        };
+        // each net gets its own generator, so that the result does not depend on the thread schedule
+        let ordered_net_ids = problem.ordered_net_ids(config.net_ordering);
+        let net_seeds: Vec<(NetID, u64)> = ordered_net_ids
+            .iter()
+            .map(|net_id| (*net_id, self.rng.random()))
+            .collect();
+        let all_net_samples: Vec<NetSamples> = net_seeds
+            .into_par_iter()
+            .map(|(net_id, seed)| {
+                let mut rng = StdRng::seed_from_u64(seed);
+                self.sample_net_traces(
+                    problem,
+                    config,
+                    &snapshot,
+                    net_id,
+                    &mut rng,
+                    pcb_render_model.clone(),
                 )
-                .as_str(),
-            );
-            while num_generation_attempts < MAX_GENERATION_ATTEMPTS
-                && num_generated_traces
-                    .values()
-                    .any(|&count| count < max_num_traces)
-            {
-                // println!("Generation attempt: {}", num_generation_attempts + 1);
-                num_generation_attempts += 1;
-                let mut sampled_obstacle_traces: HashMap<ConnectionID, Option<ProbaTraceID>> =
-                    HashMap::new();
-                // randomly generate a trace for each pad pair of other nets (in a rare case the trace will not be generated)
-                for obstacle_connection_id in obstacle_connections.iter() {
-                    // sample a trace from this connection
-                    let traces = self
-                        .connection_to_traces
-                        .get(obstacle_connection_id)
-                        .expect(
-                            format!(
-                                "ConnectionID {:?} not found in connection_to_traces",
-                                obstacle_connection_id
-                            )
-                            .as_str(),
-                        );
-                    let trace_ids = if let Traces::Probabilistic(trace_ids) = traces {
-                        trace_ids.keys().cloned().collect::<Vec<ProbaTraceID>>()
-                    } else {
-                        continue; // Skip fixed traces
-                    };
-                    let mut sum_normalized_posterior: f64 = 0.0;
-                    let mut normalized_posteriors: Vec<f64> = Vec::new();
-                    for trace_id in trace_ids.iter() {
-                        let normalized_posterior =
-                            *temp_normalized_posteriors.get(trace_id).expect(
-                                format!("No normalized posterior for trace ID {:?}", trace_id)
-                                    .as_str(),
-                            );
-                        sum_normalized_posterior += normalized_posterior;
-                        normalized_posteriors.push(normalized_posterior);
-                    }
-                    assert!(
-                        sum_normalized_posterior < 1.0,
-                        "Sum of normalized posteriors must be less than 1.0, got: {}",
-                        sum_normalized_posterior
-                    );
-                    let num_trace_candidates = normalized_posteriors.len();
-                    let remaining_probability = 1.0 - sum_normalized_posterior;
-                    normalized_posteriors.push(remaining_probability);
-                    let dist = WeightedIndex::new(normalized_posteriors).unwrap();
-                    let mut rng = rand::rng();
-                    let index = dist.sample(&mut rng);
-                    let chosen_proba_trace_id: Option<ProbaTraceID> =
-                        if index < num_trace_candidates {
-                            Some(trace_ids[index])
-                        } else {
-                            None
-                        };
-                    sampled_obstacle_traces.insert(*obstacle_connection_id, chosen_proba_trace_id);
-                }
-                let mut obstacle_shapes: Vec<PrimShape> = Vec::new();
-                let mut obstacle_clearance_shapes: Vec<PrimShape> = Vec::new();
-                // add fixed traces to the obstacle shapes
-                for obstacle_connection_id in obstacle_connections.iter() {
-                    let traces = self
-                        .connection_to_traces
-                        .get(obstacle_connection_id)
-                        .expect(
-                            format!(
-                                "ConnectionID {:?} not found in connection_to_traces",
-                                obstacle_connection_id
-                            )
-                            .as_str(),
-                        );
-                    let fixed_trace = if let Traces::Fixed(fixed_trace) = traces {
-                        fixed_trace
-                    } else {
-                        continue; // Skip probabilistic traces
-                    };
-                    let trace_segments = &fixed_trace.trace_path.segments;
-                    for segment in trace_segments.iter() {
-                        let shapes = segment.to_shapes();
-                        obstacle_shapes.extend(shapes);
-                        // add clearance shapes
-                        let clearance_shapes = segment.to_clearance_shapes();
-                        obstacle_clearance_shapes.extend(clearance_shapes);
-                    }
-                }
-                // add all sampled traces to the obstacle shapes
-                for (_, proba_trace_id) in sampled_obstacle_traces.iter() {
-                    let proba_trace_id = if let Some(proba_trace_id) = proba_trace_id {
-                        *proba_trace_id
-                    } else {
-                        continue; // Skip if no trace was sampled
-                    };
-                    let proba_trace = proba_traces.get(&proba_trace_id).expect(
-                        format!(
-                            "ProbaTraceID {:?} not found in proba_traces",
-                            proba_trace_id
-                        )
-                        .as_str(),
-                    );
-                    let trace_segments = &proba_trace.trace_path.segments;
-                    for segment in trace_segments.iter() {
-                        let shapes = segment.to_shapes();
-                        obstacle_shapes.extend(shapes);
-                        // add clearance shapes
-                        let clearance_shapes = segment.to_clearance_shapes();
-                        obstacle_clearance_shapes.extend(clearance_shapes);
-                    }
-                }
-                // add all pads in other nets to the obstacle shapes
-                for obstacle_connection_id in obstacle_connections.iter() {
-                    let connection = connections.get(obstacle_connection_id).expect(
-                        format!(
-                            "ConnectionID {:?} not found in connections",
-                            obstacle_connection_id
-                        )
-                        .as_str(),
-                    );
-                    let source_pad_shapes = connection.source.to_shapes();
-                    let sink_pad_shapes = connection.sink.to_shapes();
-                    obstacle_shapes.extend(source_pad_shapes);
-                    obstacle_shapes.extend(sink_pad_shapes);
-                }
-                let mut astar_model = AStarModel {
-                    width: problem.width,
-                    height: problem.height,
-                    obstacle_shapes,
-                    obstacle_clearance_shapes,
-                    start: FixedVec2 {
-                        x: Default::default(),
-                        y: Default::default(),
-                    },
-                    end: FixedVec2 {
-                        x: Default::default(),
-                        y: Default::default(),
-                    },
-                    trace_width: 0.0,                 // This will be set later
-                    trace_clearance: 0.0,             // This will be set later
-                    border_cache: RefCell::new(None), // Cache for border points, initialized to None
+            })
+            .collect::<Result<Vec<NetSamples>, RoutingError>>()?;
+        // merge in the net order of the config, earlier nets keep the traces that several nets found
+        let mut visited_traces = snapshot.visited_traces;
+        for (net_id, net_samples) in ordered_net_ids.iter().zip(all_net_samples) {
+            for (connection_id, astar_failure) in net_samples.astar_outcomes {
+                // keep the failure, so that unroutable connections can be explained
+                match astar_failure {
+                    Some(failure) => self.astar_failures.insert(connection_id, failure),
+                    None => self.astar_failures.remove(&connection_id),
                 };
-                let connections = &problem
-                    .nets
-                    .get(net_id)
-                    .expect(format!("NetID {:?} not found in nets", net_id).as_str())
-                    .connections;
-                // only consider connections with probabilistic traces
-                let connections: Vec<(ConnectionID, Rc<Connection>)> = connections
-                    .iter()
-                    .filter(|(connection_id, _)| {
-                        let traces = self.connection_to_traces.get(connection_id).expect(
-                            format!(
-                                "ConnectionID {:?} not found in connection_to_traces",
-                                connection_id
-                            )
-                            .as_str(),
-                        );
-                        if let Traces::Probabilistic(_) = traces {
-                            true // Only consider connections with probabilistic traces
-                        } else {
-                            false // Skip fixed traces
-                        }
-                    })
-                    .map(|(connection_id, connection)| (*connection_id, connection.clone()))
-                    .collect();
-
-                for (connection_id, connection) in connections.iter() {
-                    let connection_num_generated_traces =
-                        num_generated_traces.get(connection_id).expect(
-                            format!(
-                                "ConnectionID {:?} not found in num_generated_traces",
-                                connection_id
-                            )
-                            .as_str(),
-                        );
-                    if *connection_num_generated_traces >= max_num_traces {
-                        println!(
-                            "ConnectionID {:?} already has enough traces, skipping",
-                            connection_id
-                        );
-                        continue; // Skip this connection if it already has enough traces
-                    }
-                    // sample a trace for this connection
-                    astar_model.start = {
-                        let mut start = connection.source.position.to_fixed();
-                        if (start.x - start.y).to_bits() % 2 == 1 {
-                            start.x += FixedPoint::DELTA; // Ensure the start point is even
-                        }
-                        start
-                    };
-                    astar_model.end = {
-                        let mut end = connection.sink.position.to_fixed();
-                        if (end.x - end.y).to_bits() % 2 == 1 {
-                            end.x += FixedPoint::DELTA; // Ensure the end point is even
-                        }
-                        end
-                    };
-                    astar_model.trace_width = connection.trace_width;
-                    astar_model.trace_clearance = connection.trace_clearance;
-
-                    // run A* algorithm to find a path
-                    let astar_result = astar_model.run(pcb_render_model.clone());
-                    let astar_result = match astar_result {
-                        Ok(result) => result,
-                        Err(err) => {
-                            println!("A* algorithm failed: {}", err);
-                            continue; // Skip this connection if A* fails
-                        }
-                    };
-                    let trace_path = astar_result.trace_path;
-                    if visited_traces.contains(&trace_path.anchors) {
-                        // println!(
-                        //     "Trace path {:?} already visited, skipping",
-                        //     trace_path.anchors
-                        // );
-                        continue;
-                    }
-                    visited_traces.insert(trace_path.anchors.clone());
-                    let proba_trace_id = self
-                        .trace_id_generator
-                        .next()
-                        .expect("TraceID generator exhausted");
-                    let proba_trace = ProbaTrace {
-                        net_id: *net_id,
-                        connection_id: *connection_id,
-                        proba_trace_id,
-                        trace_path,
-                        iteration: self.next_iteration,
-                        posterior: RefCell::new(None), // Initialize with None, will be updated later
-                        temp_posterior: RefCell::new(None), // Temporary posterior for simultaneous updates
-                    };
-                    new_proba_traces.push(Rc::new(proba_trace));
-                    let num = num_generated_traces.get_mut(connection_id).expect(
-                        format!(
-                            "ConnectionID {:?} not found in num_generated_traces",
-                            connection_id
-                        )
-                        .as_str(),
-                    );
-                    *num += 1;
+            }
+            for (connection_id, trace_path) in net_samples.trace_paths {
+                if !visited_traces.insert(trace_path.anchors.clone()) {
+                    continue; // already found for another net
                 }
+                let proba_trace_id = self.next_proba_trace_id;
+                self.next_proba_trace_id = ProbaTraceID(proba_trace_id.0 + 1);
+                let proba_trace = ProbaTrace {
+                    net_id: *net_id,
+                    connection_id,
+                    proba_trace_id,
+                    trace_path,
+                    iteration: self.next_iteration,
+                    posterior: Mutex::new(None), // Initialize with None, will be updated later
+                    temp_posterior: Mutex::new(None), // Temporary posterior for simultaneous updates
+                    length_limits: problem.connection(connection_id)?.length_limits,
+                };
+                new_proba_traces.push(Arc::new(proba_trace));
             }
         }
+        let new_proba_trace_ids: BTreeSet<ProbaTraceID> = new_proba_traces
+            .iter()
+            .map(|proba_trace| proba_trace.proba_trace_id)
+            .collect();
         // add the new traces to the model
         for proba_trace in new_proba_traces {
             let proba_trace_id = proba_trace.proba_trace_id;
             let connection_id = proba_trace.connection_id;
-            let traces = self.connection_to_traces.get_mut(&connection_id).expect(
-                format!(
-                    "ConnectionID {:?} not found in connection_to_traces",
-                    connection_id
-                )
-                .as_str(),
-            );
-            // traces can only be probabilistic, if it is fixed, we panic
+            let traces = self
+                .connection_to_traces
+                .get_mut(&connection_id)
+                .ok_or_else(|| {
+                    RoutingError::InternalInvariant(format!(
+                        "ConnectionID {:?} not found in connection_to_traces",
+                        connection_id
+                    ))
+                })?;
+            // traces can only be probabilistic
             let traces = if let Traces::Probabilistic(trace_map) = traces {
                 trace_map
             } else {
-                panic!(
+                return Err(RoutingError::InternalInvariant(format!(
                     "ConnectionID {:?} has fixed traces, cannot add probabilistic trace",
                     connection_id
-                );
+                )));
             };
             let old = traces.insert(proba_trace_id, proba_trace.clone());
-            assert!(
+            ensure_invariant!(
                 old.is_none(),
                 "ProbaTraceID {:?} already exists for ConnectionID {:?}",
                 proba_trace_id,
@@ -572,91 +697,11 @@ impl ProbaModel {
             );
         }
 
-        // update proba_traces to include the new traces
-        let mut proba_traces: HashMap<ProbaTraceID, Rc<ProbaTrace>> = HashMap::new();
-        for traces in self.connection_to_traces.values() {
-            if let Traces::Probabilistic(trace_map) = traces {
-                for (proba_trace_id, proba_trace) in trace_map.iter() {
-                    proba_traces.insert(*proba_trace_id, proba_trace.clone());
-                }
-            }
-        }
-        // update the collision adjacency
-        let mut collision_adjacency: HashMap<ProbaTraceID, HashSet<ProbaTraceID>> = proba_traces
-            .iter()
-            .map(|(proba_trace_id, _)| (*proba_trace_id, HashSet::new()))
-            .collect();
-        // update net_to_proba_traces to include the new traces
-        let mut net_to_proba_traces: HashMap<NetID, Vec<ProbaTraceID>> = problem
-            .nets
-            .keys()
-            .map(|net_id| (*net_id, Vec::new()))
-            .collect();
-        for (connection_id, traces) in self.connection_to_traces.iter() {
-            if let Traces::Probabilistic(trace_ids) = traces {
-                let net_id = connection_to_net.get(connection_id).expect(
-                    format!(
-                        "ConnectionID {:?} not found in connection_id_to_net_id",
-                        connection_id
-                    )
-                    .as_str(),
-                );
-                net_to_proba_traces
-                    .get_mut(net_id)
-                    .expect(format!("NetID {:?} not found in net_to_proba_traces", net_id).as_str())
-                    .extend(trace_ids.keys().cloned());
-            }
-        }
-        // calculate the collisions between traces
-        let proba_traces_vec: Vec<Vec<ProbaTraceID>> = net_to_proba_traces.into_values().collect();
-        for i in 0..proba_traces_vec.len() {
-            for j in (i + 1)..proba_traces_vec.len() {
-                let net_i = &proba_traces_vec[i];
-                let net_j = &proba_traces_vec[j];
-                for trace_i in net_i.iter() {
-                    for trace_j in net_j.iter() {
-                        // check if the traces collide
-                        let proba_trace_i = proba_traces.get(trace_i).expect(
-                            format!("ProbaTraceID {:?} not found in proba_traces", trace_i)
-                                .as_str(),
-                        );
-                        let proba_trace_j = proba_traces.get(trace_j).expect(
-                            format!("ProbaTraceID {:?} not found in proba_traces", trace_j)
-                                .as_str(),
-                        );
-                        if proba_trace_i
-                            .trace_path
-                            .collides_with(&proba_trace_j.trace_path)
-                        {
-                            // add the collision to the adjacency
-                            collision_adjacency
-                                .get_mut(trace_i)
-                                .expect(
-                                    format!(
-                                        "ProbaTraceID {:?} not found in collision_adjacency",
-                                        trace_i
-                                    )
-                                    .as_str(),
-                                )
-                                .insert(*trace_j);
-                            collision_adjacency
-                                .get_mut(trace_j)
-                                .expect(
-                                    format!(
-                                        "ProbaTraceID {:?} not found in collision_adjacency",
-                                        trace_j
-                                    )
-                                    .as_str(),
-                                )
-                                .insert(*trace_i);
-                        }
-                    }
-                }
-            }
-        }
-        self.collision_adjacency = collision_adjacency;
+        // only the new traces are tested, the collisions between the existing traces do not change
+        self.update_collision_adjacency(problem, &new_proba_trace_ids);
         // update next_iteration
         self.next_iteration = NonZeroUsize::new(self.next_iteration.get() + 1).unwrap();
+        Ok(())
     }
     pub fn to_pcb_render_model(&self, problem: &PcbProblem) -> PcbRenderModel {
         let mut trace_shape_renderables: Vec<RenderableBatch> = Vec::new();
@@ -676,7 +721,8 @@ impl ProbaModel {
                     self.connection_to_traces.get(&connection.connection_id)
                 {
                     for proba_trace in trace_map.values() {
-                        let posterior = proba_trace.get_posterior_with_fallback();
+                        // a trace without a prior is drawn transparent, the solve reports the error
+                        let posterior = proba_trace.get_posterior_with_fallback().unwrap_or(0.0);
                         let posterior = posterior.clamp(0.0, 1.0); // Ensure posterior is between 0 and 1
                         let color = net_info.color.to_float4(posterior as f32);
                         let renderable_batches = proba_trace.trace_path.to_renderables(color);
@@ -710,9 +756,362 @@ impl ProbaModel {
         }
     }
 
-    pub fn update_posterior(&mut self) {
-        let proba_traces: HashMap<ProbaTraceID, Rc<ProbaTrace>> = self
+    /// adds the new traces to the collision adjacency, testing them against the other traces of other nets
+    /// pairs with disjoint bounding boxes are skipped, and the exact tests of the new traces run in parallel
+    fn update_collision_adjacency(
+        &mut self,
+        problem: &PcbProblem,
+        new_proba_trace_ids: &BTreeSet<ProbaTraceID>,
+    ) {
+        // every probabilistic trace with the bounding box of its clearance shapes
+        let mut traces_with_boxes: Vec<(Arc<ProbaTrace>, BoundingBox)> = Vec::new();
+        for traces in self.connection_to_traces.values() {
+            if let Traces::Probabilistic(trace_map) = traces {
+                for proba_trace in trace_map.values() {
+                    // a trace without segments collides with nothing
+                    if let Some(bounding_box) = proba_trace.trace_path.clearance_bounding_box() {
+                        traces_with_boxes.push((proba_trace.clone(), bounding_box));
+                    }
+                }
+            }
+        }
+        let new_traces_with_boxes: Vec<&(Arc<ProbaTrace>, BoundingBox)> = traces_with_boxes
+            .iter()
+            .filter(|(proba_trace, _)| new_proba_trace_ids.contains(&proba_trace.proba_trace_id))
+            .collect();
+        let collisions: Vec<(ProbaTraceID, ProbaTraceID)> = new_traces_with_boxes
+            .par_iter()
+            .flat_map_iter(|(new_trace, new_box)| {
+                traces_with_boxes
+                    .iter()
+                    .filter(move |(other_trace, other_box)| {
+                        if other_trace.net_id == new_trace.net_id {
+                            return false; // traces of the same net may overlap
+                        }
+                        // a pair of new traces is tested once
+                        if new_proba_trace_ids.contains(&other_trace.proba_trace_id)
+                            && other_trace.proba_trace_id > new_trace.proba_trace_id
+                        {
+                            return false;
+                        }
+                        let class_clearance =
+                            problem.clearance_between_nets(new_trace.net_id, other_trace.net_id);
+                        // raising a clearance by at most class_clearance grows the margin by at most twice that
+                        new_box
+                            .inflate(class_clearance * 2.0)
+                            .overlaps(&other_box.inflate(class_clearance * 2.0))
+                            && new_trace
+                                .trace_path
+                                .collides_with_clearance(&other_trace.trace_path, class_clearance)
+                    })
+                    .map(move |(other_trace, _)| {
+                        (new_trace.proba_trace_id, other_trace.proba_trace_id)
+                    })
+            })
+            .collect();
+        for proba_trace_id in new_proba_trace_ids.iter() {
+            self.collision_adjacency.entry(*proba_trace_id).or_default();
+        }
+        for (proba_trace_id, other_proba_trace_id) in collisions {
+            self.collision_adjacency
+                .entry(proba_trace_id)
+                .or_default()
+                .insert(other_proba_trace_id);
+            self.collision_adjacency
+                .entry(other_proba_trace_id)
+                .or_default()
+                .insert(proba_trace_id);
+        }
+    }
+    /// samples new traces for the connections of one net against sampled traces of the other nets
+    /// only reads the model, so that the nets can be sampled in parallel
+    fn sample_net_traces(
+        &self,
+        problem: &PcbProblem,
+        config: &SolverConfig,
+        snapshot: &SamplingSnapshot,
+        net_id: NetID,
+        rng: &mut StdRng,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<NetSamples, RoutingError> {
+        let mut net_samples = NetSamples {
+            trace_paths: Vec::new(),
+            astar_outcomes: Vec::new(),
+        };
+        // e.g. a net whose connections are all fixed or frozen
+        if !problem.nets[&net_id]
+            .connections
+            .keys()
+            .any(|connection_id| self.samples_connection(connection_id))
+        {
+            return Ok(net_samples);
+        }
+        // traces found for this net in this call
+        let mut net_visited_traces: BTreeSet<TraceAnchors> = BTreeSet::new();
+        // collect connections that are not in this net
+        let obstacle_connections: BTreeSet<ConnectionID> = problem
+            .nets
+            .iter()
+            .filter(|(other_net_id, _)| **other_net_id != net_id)
+            .flat_map(|(_, net_info)| net_info.connections.keys())
+            .cloned()
+            .collect();
+        // initialize the number of generated traces for each connection
+        let mut num_generated_traces: BTreeMap<ConnectionID, usize> = self
             .connection_to_traces
+            .keys()
+            .map(|connection_id| (*connection_id, 0))
+            .collect();
+        // initialize the number of generation attempts
+        let mut num_generation_attempts: usize = 0;
+        // the inner loop for generating traces for each connection in the net
+        let max_num_traces = *ITERATION_TO_NUM_TRACES
+            .get(&self.next_iteration)
+            .ok_or_else(|| {
+                RoutingError::InternalInvariant(format!(
+                    "No number of traces for iteration {:?}",
+                    self.next_iteration
+                ))
+            })?;
+        while num_generation_attempts < MAX_GENERATION_ATTEMPTS
+            && snapshot.stop_condition.check().is_none()
+            && num_generated_traces
+                .values()
+                .any(|&count| count < max_num_traces)
+        {
+            // println!("Generation attempt: {}", num_generation_attempts + 1);
+            num_generation_attempts += 1;
+            let mut sampled_obstacle_traces: BTreeMap<ConnectionID, Option<ProbaTraceID>> =
+                BTreeMap::new();
+            // randomly generate a trace for each pad pair of other nets (in a rare case the trace will not be generated)
+            for obstacle_connection_id in obstacle_connections.iter() {
+                // sample a trace from this connection
+                let traces = self
+                    .connection_to_traces
+                    .get(obstacle_connection_id)
+                    .ok_or_else(|| {
+                        RoutingError::InternalInvariant(format!(
+                            "ConnectionID {:?} not found in connection_to_traces",
+                            obstacle_connection_id
+                        ))
+                    })?;
+                let trace_ids = if let Traces::Probabilistic(trace_ids) = traces {
+                    trace_ids.keys().cloned().collect::<Vec<ProbaTraceID>>()
+                } else {
+                    continue; // Skip fixed traces
+                };
+                let mut sum_normalized_posterior: f64 = 0.0;
+                let mut normalized_posteriors: Vec<f64> = Vec::new();
+                for trace_id in trace_ids.iter() {
+                    let normalized_posterior = *snapshot
+                        .normalized_posteriors
+                        .get(trace_id)
+                        .ok_or_else(|| {
+                            RoutingError::InternalInvariant(format!(
+                                "No normalized posterior for trace ID {:?}",
+                                trace_id
+                            ))
+                        })?;
+                    sum_normalized_posterior += normalized_posterior;
+                    normalized_posteriors.push(normalized_posterior);
+                }
+                ensure_invariant!(
+                    sum_normalized_posterior < 1.0,
+                    "Sum of normalized posteriors must be less than 1.0, got: {}",
+                    sum_normalized_posterior
+                );
+                let num_trace_candidates = normalized_posteriors.len();
+                let remaining_probability = 1.0 - sum_normalized_posterior;
+                normalized_posteriors.push(remaining_probability);
+                let dist = WeightedIndex::new(normalized_posteriors).map_err(|error| {
+                    RoutingError::InternalInvariant(format!(
+                        "Invalid sampling weights for ConnectionID {:?}: {}",
+                        obstacle_connection_id, error
+                    ))
+                })?;
+                let index = dist.sample(rng);
+                let chosen_proba_trace_id: Option<ProbaTraceID> = if index < num_trace_candidates {
+                    Some(trace_ids[index])
+                } else {
+                    None
+                };
+                sampled_obstacle_traces.insert(*obstacle_connection_id, chosen_proba_trace_id);
+            }
+            let mut obstacle_shapes: Vec<PrimShape> = Vec::new();
+            let mut obstacle_clearance_shapes: Vec<PrimShape> = Vec::new();
+            // add fixed traces to the obstacle shapes
+            let fixed_traces =
+                self.connection_to_traces
+                    .values()
+                    .filter_map(|traces| match traces {
+                        Traces::Fixed(fixed_trace) => Some(fixed_trace),
+                        Traces::Probabilistic(_) => None,
+                    });
+            let (trace_shapes, trace_clearance_shapes) =
+                problem.trace_obstacle_shapes(&[net_id], fixed_traces);
+            obstacle_shapes.extend(trace_shapes);
+            obstacle_clearance_shapes.extend(trace_clearance_shapes);
+            // add all sampled traces to the obstacle shapes
+            for (_, proba_trace_id) in sampled_obstacle_traces.iter() {
+                let proba_trace_id = if let Some(proba_trace_id) = proba_trace_id {
+                    *proba_trace_id
+                } else {
+                    continue; // Skip if no trace was sampled
+                };
+                let proba_trace = snapshot.proba_traces.get(&proba_trace_id).ok_or_else(|| {
+                    RoutingError::InternalInvariant(format!(
+                        "ProbaTraceID {:?} not found in proba_traces",
+                        proba_trace_id
+                    ))
+                })?;
+                let class_clearance = problem.clearance_between_nets(net_id, proba_trace.net_id);
+                let trace_segments = &proba_trace.trace_path.segments;
+                for segment in trace_segments.iter() {
+                    let shapes = segment.to_shapes();
+                    obstacle_shapes.extend(shapes);
+                    // add clearance shapes, widened to the clearance between the net classes
+                    let clearance_shapes = segment
+                        .with_min_clearance(class_clearance)
+                        .to_clearance_shapes();
+                    obstacle_clearance_shapes.extend(clearance_shapes);
+                }
+            }
+            // add all pads in other nets to the obstacle shapes
+            let (pad_shapes, pad_clearance_shapes) = problem.pad_obstacle_shapes(&[net_id]);
+            obstacle_shapes.extend(pad_shapes);
+            obstacle_clearance_shapes.extend(pad_clearance_shapes);
+            let mut astar_model = AStarModel {
+                width: problem.width,
+                height: problem.height,
+                obstacle_shapes,
+                obstacle_clearance_shapes,
+                start: FixedVec2 {
+                    x: Default::default(),
+                    y: Default::default(),
+                },
+                end: FixedVec2 {
+                    x: Default::default(),
+                    y: Default::default(),
+                },
+                trace_width: 0.0,     // This will be set later
+                trace_clearance: 0.0, // This will be set later
+                routing_mode: problem
+                    .nets
+                    .get(&net_id)
+                    .ok_or_else(|| {
+                        RoutingError::InternalInvariant(format!(
+                            "NetID {:?} not found in nets",
+                            net_id
+                        ))
+                    })?
+                    .routing_mode,
+                routing_cost: problem.routing_cost.clone(),
+                search_budget: config.astar_search_budget,
+                stop_condition: snapshot.stop_condition.clone(),
+                border_cache: RefCell::new(None), // Cache for border points, initialized to None
+                max_length: None,                 // This will be set later
+                display: config.display_astar,
+            };
+            let connections = &problem
+                .nets
+                .get(&net_id)
+                .ok_or_else(|| {
+                    RoutingError::InternalInvariant(format!("NetID {:?} not found in nets", net_id))
+                })?
+                .connections;
+            // only consider connections with probabilistic traces
+            let connections: Vec<(ConnectionID, Arc<Connection>)> = connections
+                .iter()
+                .filter(|(connection_id, _)| {
+                    // Only consider connections with probabilistic traces, skip fixed and frozen traces
+                    self.samples_connection(connection_id)
+                })
+                .map(|(connection_id, connection)| (*connection_id, connection.clone()))
+                .collect();
+
+            for (connection_id, connection) in connections.iter() {
+                let connection_num_generated_traces =
+                    num_generated_traces.get(connection_id).ok_or_else(|| {
+                        RoutingError::InternalInvariant(format!(
+                            "ConnectionID {:?} not found in num_generated_traces",
+                            connection_id
+                        ))
+                    })?;
+                if *connection_num_generated_traces >= max_num_traces {
+                    println!(
+                        "ConnectionID {:?} already has enough traces, skipping",
+                        connection_id
+                    );
+                    continue; // Skip this connection if it already has enough traces
+                }
+                // sample a trace for this connection
+                astar_model.start = astar_model.to_search_point(connection.source.position);
+                astar_model.end = astar_model.to_search_point(connection.sink.position);
+                astar_model.trace_width = problem.trace_width(connection);
+                astar_model.trace_clearance = problem.trace_clearance(connection);
+                astar_model.max_length = connection
+                    .length_limits
+                    .max_length
+                    .map(|max_length| max_length as f64);
+
+                // run A* algorithm to find a path
+                let astar_result = astar_model.run(pcb_render_model.clone());
+                let astar_result = match astar_result {
+                    Ok(result) => result,
+                    Err(
+                        RoutingError::SearchBudgetExhausted(failure)
+                        | RoutingError::NoPathFound(failure),
+                    ) => {
+                        println!(
+                            "A* algorithm failed for ConnectionID {:?}: {}",
+                            connection_id, failure
+                        );
+                        // keep the failure, so that unroutable connections can be explained
+                        net_samples
+                            .astar_outcomes
+                            .push((*connection_id, Some(failure)));
+                        continue; // Skip this connection if A* fails
+                    }
+                    Err(error) => return Err(error),
+                };
+                net_samples.astar_outcomes.push((*connection_id, None));
+                let trace_path = astar_result.trace_path;
+                // a trace longer than the hard limit is never a candidate, whatever the search returned
+                if connection
+                    .length_limits
+                    .exceeds_max_length(trace_path.length)
+                {
+                    println!(
+                        "Trace of ConnectionID {:?} exceeds the max length, skipping",
+                        connection_id
+                    );
+                    continue;
+                }
+                if snapshot.visited_traces.contains(&trace_path.anchors)
+                    || net_visited_traces.contains(&trace_path.anchors)
+                {
+                    // println!(
+                    //     "Trace path {:?} already visited, skipping",
+                    //     trace_path.anchors
+                    // );
+                    continue;
+                }
+                net_visited_traces.insert(trace_path.anchors.clone());
+                net_samples.trace_paths.push((*connection_id, trace_path));
+                let num = num_generated_traces.get_mut(connection_id).ok_or_else(|| {
+                    RoutingError::InternalInvariant(format!(
+                        "ConnectionID {:?} not found in num_generated_traces",
+                        connection_id
+                    ))
+                })?;
+                *num += 1;
+            }
+        }
+        Ok(net_samples)
+    }
+    /// every candidate trace of the connections without a fixed trace
+    pub fn probabilistic_traces(&self) -> BTreeMap<ProbaTraceID, Arc<ProbaTrace>> {
+        self.connection_to_traces
             .values()
             .filter_map(|traces| {
                 if let Traces::Probabilistic(trace_map) = traces {
@@ -722,83 +1121,25 @@ impl ProbaModel {
                 }
             })
             .flatten()
-            .collect();
-        // Update the posterior probabilities for all traces in the model
-        for (proba_trace_id, proba_trace) in proba_traces.iter() {
-            let adjacent_traces = self.collision_adjacency.get(proba_trace_id).expect(
-                format!("No adjacent traces for ProbaTraceID {:?}", proba_trace_id).as_str(),
-            );
-            let mut proba_product = 1.0;
-            for adjacent_trace_id in adjacent_traces.iter() {
-                let adjacent_trace = proba_traces.get(adjacent_trace_id).expect(
-                    format!(
-                        "No ProbaTraceID {:?} found in proba_traces",
-                        adjacent_trace_id
-                    )
-                    .as_str(),
-                );
-                let posterior = adjacent_trace.get_posterior_with_fallback();
-                // to do: update this
-                let one_minus_posterior = (1.0 - posterior).max(0.0); // Ensure non-negative
-                proba_product *= one_minus_posterior;
-            }
-            let target_posterior = proba_product;
-            assert!(
-                target_posterior >= 0.0 && target_posterior <= 1.0,
-                "Target posterior must be between 0 and 1"
-            );
-            // get num traces in the same iteration
-            let current_posterior = proba_trace.get_posterior_with_fallback();
-            let opportunity_cost = target_posterior / current_posterior;
-            let score = proba_trace.trace_path.get_score();
-            let score_weight = *SCORE_WEIGHT.lock().unwrap();
-            let opportunity_cost_weight = *OPPORTUNITY_COST_WEIGHT.lock().unwrap();
-            let target_posterior_unnormalized = 1.0
-                * f64::powf(score, score_weight)
-                * f64::powf(opportunity_cost, opportunity_cost_weight);
-            let target_posterior_normalized =
-                proba_trace.get_normalized_prior() * target_posterior_unnormalized;
-            let mut temp_posterior = proba_trace.temp_posterior.borrow_mut();
-            let target_greater_than_current = target_posterior_normalized > current_posterior;
-            let constant_offset = if target_greater_than_current {
-                CONSTANT_LEARNING_RATE
-            } else {
-                -CONSTANT_LEARNING_RATE
-            };
-            let new_posterior = current_posterior
-                + (target_posterior_normalized - current_posterior) * LINEAR_LEARNING_RATE
-                + constant_offset;
-            let new_posterior = if target_greater_than_current {
-                new_posterior.max(target_posterior_normalized)
-            } else {
-                new_posterior.min(target_posterior_normalized)
-            };
-            *temp_posterior = Some(new_posterior);
-        }
-        // move temp_posterior to posterior
-        for (_, proba_trace) in proba_traces.iter() {
-            let mut posterior = proba_trace.posterior.borrow_mut();
-            let mut temp_posterior = proba_trace.temp_posterior.borrow_mut();
-            let temp_posterior_val = temp_posterior.unwrap();
-            *posterior = Some(temp_posterior_val);
-            // reset temp_posterior
-            *temp_posterior = None;
-        }
+            .collect()
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Node {
-    pub remaining_trace_candidates: BinaryHeap<BinaryHeapItem<NotNan<f64>, Rc<ProbaTrace>>>, // The remaining trace candidates to be processed, sorted by their scores)>
-    pub fixed_traces: HashMap<ConnectionID, FixedTrace>,
+    pub remaining_trace_candidates: BinaryHeap<BinaryHeapItem<NotNan<f64>, Arc<ProbaTrace>>>, // The remaining trace candidates to be processed, sorted by their scores)>
+    pub fixed_traces: BTreeMap<ConnectionID, FixedTrace>,
     pub prob_up_to_date: bool, // Whether the probabilistic model is up to date
+    pub astar_failures: BTreeMap<ConnectionID, AStarFailure>, // The failed A* searches of the probabilistic model
+    pub fixed_score: f64, // the sum of the posteriors of the traces fixed by the search, scores the beam search nodes
+    pub proba_model: Arc<ProbaModel>, // the model of the candidates, the base of an incremental update
 }
 
 impl Node {
-    fn from_proba_model(proba_model: &ProbaModel) -> Self {
-        let mut fixed_traces: HashMap<ConnectionID, FixedTrace> = HashMap::new();
+    fn from_proba_model(proba_model: Arc<ProbaModel>) -> Result<Self, RoutingError> {
+        let mut fixed_traces: BTreeMap<ConnectionID, FixedTrace> = BTreeMap::new();
         let mut remaining_trace_candidates: BinaryHeap<
-            BinaryHeapItem<NotNan<f64>, Rc<ProbaTrace>>,
+            BinaryHeapItem<NotNan<f64>, Arc<ProbaTrace>>,
         > = BinaryHeap::new();
         for (connection_id, traces) in proba_model.connection_to_traces.iter() {
             match traces {
@@ -807,9 +1148,13 @@ impl Node {
                 }
                 Traces::Probabilistic(trace_map) => {
                     for (_proba_trace_id, proba_trace) in trace_map.iter() {
-                        let posterior = proba_trace.get_posterior_with_fallback();
-                        let not_nan_proba =
-                            NotNan::new(posterior).expect("Probability must be non-NaN");
+                        let posterior = proba_trace.get_posterior_with_fallback()?;
+                        let not_nan_proba = NotNan::new(posterior).map_err(|_| {
+                            RoutingError::InternalInvariant(format!(
+                                "The posterior of {:?} is NaN",
+                                proba_trace.proba_trace_id
+                            ))
+                        })?;
                         remaining_trace_candidates.push(BinaryHeapItem {
                             key: not_nan_proba,
                             value: proba_trace.clone(),
@@ -818,19 +1163,23 @@ impl Node {
                 }
             }
         }
-        Node {
+        Ok(Node {
             remaining_trace_candidates,
             fixed_traces,
             prob_up_to_date: true, // Initially, the probabilistic model is up to date
-        }
+            astar_failures: proba_model.astar_failures.clone(),
+            fixed_score: 0.0,
+            proba_model,
+        })
     }
-    fn fix_trace(&mut self, connection_id: ConnectionID, fixed_trace: FixedTrace) {
+    fn fix_trace(&mut self, connection_id: ConnectionID, fixed_trace: FixedTrace, posterior: f64) {
         // Add the fixed trace to the fixed traces
         self.fixed_traces.insert(connection_id, fixed_trace);
+        self.fixed_score += posterior;
         // Remove all trace candidates for this connection from the remaining candidates
         let mut remaining_trace_candidates_copy = self.remaining_trace_candidates.clone();
         let mut new_remaining_trace_candidates: BinaryHeap<
-            BinaryHeapItem<NotNan<f64>, Rc<ProbaTrace>>,
+            BinaryHeapItem<NotNan<f64>, Arc<ProbaTrace>>,
         > = BinaryHeap::new();
         for candidate in remaining_trace_candidates_copy.drain() {
             if candidate.value.connection_id != connection_id {
@@ -842,19 +1191,26 @@ impl Node {
         self.prob_up_to_date = false;
     }
     /// If an attemp fails, return none; it will pop the priority queue in both scenarios
-    /// assume there are still candidates in the priority queue
-    pub fn try_fix_top_ranked_trace(&mut self) -> Option<Self> {
+    /// an empty priority queue is also a failed attempt
+    pub fn try_fix_top_ranked_trace(&mut self, problem: &PcbProblem) -> Option<Self> {
         // for self, peek from the priority queue
         // if succeed, remove all traces from the same connection, and generate a new node with the same priority queue and a fixed trace
         // if fail, return error
-        let top_ranked_candidate = self
-            .remaining_trace_candidates
-            .pop()
-            .expect("No remaining trace candidates to fix");
+        let Some(top_ranked_candidate) = self.remaining_trace_candidates.pop() else {
+            println!("No remaining trace candidates to fix");
+            return None;
+        };
         let top_ranked_trace_path = &top_ranked_candidate.value.trace_path;
-        // check if the trace collides with any fixed trace
+        // check if the trace collides with any fixed trace of another net
         for fixed_trace in self.fixed_traces.values() {
-            if top_ranked_trace_path.collides_with(&fixed_trace.trace_path) {
+            if fixed_trace.net_id == top_ranked_candidate.value.net_id {
+                continue; // traces of the same net may touch
+            }
+            let class_clearance = problem
+                .clearance_between_nets(top_ranked_candidate.value.net_id, fixed_trace.net_id);
+            if top_ranked_trace_path
+                .collides_with_clearance(&fixed_trace.trace_path, class_clearance)
+            {
                 // If it collides, we cannot fix this trace
                 println!("Top ranked trace collides with a fixed trace, cannot fix it");
                 return None; // Return None to indicate failure
@@ -870,42 +1226,112 @@ impl Node {
         };
         // delete all trace candidates for this connection in the new node
         let mut new_node = self.clone();
-        new_node.fix_trace(connection_id, fixed_trace);
+        new_node.fix_trace(
+            connection_id,
+            fixed_trace,
+            top_ranked_candidate.key.into_inner(),
+        );
         Some(new_node) // Return the new node with the fixed trace
     }
     pub fn from_fixed_traces(
         problem: &PcbProblem,
-        fixed_traces: &HashMap<ConnectionID, FixedTrace>,
+        fixed_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
         pcb_render_model: Arc<Mutex<PcbRenderModel>>,
-    ) -> Self {
-        let proba_model = ProbaModel::create_and_solve(problem, fixed_traces, pcb_render_model);
-        Node::from_proba_model(&proba_model)
+    ) -> Result<Self, RoutingError> {
+        let proba_model = ProbaModel::create_and_solve(
+            problem,
+            fixed_traces,
+            config,
+            stop_condition,
+            pcb_render_model,
+        )?;
+        Node::from_proba_model(Arc::new(proba_model))
     }
     /// if self is already up to date, return none
     pub fn try_update_proba_model(
         &self,
         problem: &PcbProblem,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
         pcb_render_model: Arc<Mutex<PcbRenderModel>>,
-    ) -> Option<Self> {
+    ) -> Result<Option<Self>, RoutingError> {
         if self.prob_up_to_date {
-            return None; // If the probabilistic model is already up to date, do nothing
+            return Ok(None); // If the probabilistic model is already up to date, do nothing
         }
         let fixed_traces = &self.fixed_traces;
-        let new_node = Node::from_fixed_traces(problem, fixed_traces, pcb_render_model);
-        Some(new_node) // Return the new node with the updated probabilistic model
+        let mut new_node = if config.incremental_updates {
+            let proba_model = self.proba_model.update_incrementally(
+                problem,
+                fixed_traces,
+                config,
+                stop_condition,
+                pcb_render_model,
+            )?;
+            Node::from_proba_model(Arc::new(proba_model))?
+        } else {
+            Node::from_fixed_traces(
+                problem,
+                fixed_traces,
+                config,
+                stop_condition,
+                pcb_render_model,
+            )?
+        };
+        new_node.fixed_score = self.fixed_score;
+        Ok(Some(new_node)) // Return the new node with the updated probabilistic model
+    }
+    /// the connections that have neither a fixed trace nor a remaining trace candidate
+    pub fn connections_without_candidates(&self, problem: &PcbProblem) -> Vec<ConnectionID> {
+        let connections_with_candidates: BTreeSet<ConnectionID> = self
+            .remaining_trace_candidates
+            .iter()
+            .map(|candidate| candidate.value.connection_id)
+            .collect();
+        let mut connection_ids: Vec<ConnectionID> = problem
+            .nets
+            .values()
+            .flat_map(|net_info| net_info.connections.keys())
+            .filter(|connection_id| {
+                !self.fixed_traces.contains_key(connection_id)
+                    && !connections_with_candidates.contains(connection_id)
+            })
+            .cloned()
+            .collect();
+        connection_ids.sort();
+        connection_ids
     }
     pub fn is_solution(&self, problem: &PcbProblem) -> bool {
+        self.is_solution_except(problem, &BTreeSet::new())
+    }
+    /// whether all connections except the skipped ones have fixed traces
+    pub fn is_solution_except(
+        &self,
+        problem: &PcbProblem,
+        skipped_connection_ids: &BTreeSet<ConnectionID>,
+    ) -> bool {
         // Check if all connections in the problem have fixed traces in this node
         for net_info in problem.nets.values() {
             for connection_id in net_info.connections.keys() {
-                if !self.fixed_traces.contains_key(connection_id) {
+                if !self.fixed_traces.contains_key(connection_id)
+                    && !skipped_connection_ids.contains(connection_id)
+                {
                     return false; // If any connection does not have a fixed trace, it's not a solution
                 }
             }
         }
         true // All connections have fixed traces, so this is a solution
     }
-    pub fn try_fix_any_trace(&mut self) -> Option<Self> {
+    /// the fixed traces after fixing the best candidate that fits until none fits, the best effort of a stopped search
+    pub(crate) fn greedy_fixed_traces(&self, problem: &PcbProblem) -> BTreeMap<ConnectionID, FixedTrace> {
+        let mut node = self.clone();
+        while let Some(new_node) = node.try_fix_any_trace(problem) {
+            node = new_node;
+        }
+        node.fixed_traces
+    }
+    pub fn try_fix_any_trace(&mut self, problem: &PcbProblem) -> Option<Self> {
         // Try to fix any trace from the remaining candidates
         while self.remaining_trace_candidates.len() > 0 {
             let top_ranked_candidate = self
@@ -916,7 +1342,14 @@ impl Node {
             // Check if the trace collides with any fixed trace
             let mut collision_found = false;
             for fixed_trace in self.fixed_traces.values() {
-                if top_ranked_trace_path.collides_with(&fixed_trace.trace_path) {
+                if fixed_trace.net_id == top_ranked_candidate.value.net_id {
+                    continue; // traces of the same net may touch
+                }
+                let class_clearance = problem
+                    .clearance_between_nets(top_ranked_candidate.value.net_id, fixed_trace.net_id);
+                if top_ranked_trace_path
+                    .collides_with_clearance(&fixed_trace.trace_path, class_clearance)
+                {
                     // If it collides, we cannot fix this trace
                     println!("Top ranked trace collides with a fixed trace, cannot fix it");
                     collision_found = true;
@@ -933,7 +1366,11 @@ impl Node {
                     trace_path: top_ranked_trace_path.clone(),
                 };
                 let mut new_node = self.clone();
-                new_node.fix_trace(connection_id, fixed_trace);
+                new_node.fix_trace(
+                    connection_id,
+                    fixed_trace,
+                    top_ranked_candidate.key.into_inner(),
+                );
                 return Some(new_node); // Return the new node with the fixed trace
             }
         }
@@ -941,41 +1378,342 @@ impl Node {
     }
 }
 
+/// why a connection has no trace in a partial solution
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnroutedReason {
+    SearchFailed(AStarFailureReason), // no candidate trace, the last A* search failed for this reason
+    NoCandidate,                      // no candidate trace was sampled
+    Conflicting,                      // the candidate traces collide with the routed traces
+    Stopped(StopReason),              // the solve stopped before the connection was routed
+    SelectionBudgetExhausted,         // the exact selection ran out of search nodes before routing it
+}
+
+impl fmt::Display for UnroutedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnroutedReason::SearchFailed(reason) => {
+                write!(
+                    f,
+                    "no candidate trace, the A* search failed with {:?}",
+                    reason
+                )
+            }
+            UnroutedReason::NoCandidate => write!(f, "no candidate trace was sampled"),
+            UnroutedReason::Conflicting => {
+                write!(f, "the candidate traces collide with the routed traces")
+            }
+            UnroutedReason::Stopped(reason) => {
+                write!(f, "the solver stopped with {:?} before routing it", reason)
+            }
+            UnroutedReason::SelectionBudgetExhausted => {
+                write!(f, "the exact selection ran out of search nodes before routing it")
+            }
+        }
+    }
+}
+
+/// equal seeds give equal solutions, unless a search budget has a time limit
+#[derive(Debug, Clone, PartialEq)]
 pub struct PcbSolution {
-    pub determined_traces: HashMap<ConnectionID, FixedTrace>, // NetID to ConnectionID to FixedTrace
+    pub determined_traces: BTreeMap<ConnectionID, FixedTrace>, // NetID to ConnectionID to FixedTrace
+    pub unrouted_connections: BTreeMap<ConnectionID, UnroutedReason>, // empty unless SolverConfig::allow_partial or in RoutingError::Stopped
+}
+
+impl PcbSolution {
+    /// whether every connection of the problem has a trace
+    pub fn is_complete(&self) -> bool {
+        self.unrouted_connections.is_empty()
+    }
+}
+
+/// the reason of a connection without candidate traces
+pub(crate) fn reason_without_candidates(
+    connection_id: &ConnectionID,
+    astar_failures: &BTreeMap<ConnectionID, AStarFailure>,
+) -> UnroutedReason {
+    match astar_failures.get(connection_id) {
+        Some(failure) => UnroutedReason::SearchFailed(failure.reason),
+        None => UnroutedReason::NoCandidate,
+    }
+}
+
+/// the error for connections that have no candidate trace, with the reason of their failed A* searches
+pub(crate) fn unroutable_without_candidates(
+    connection_ids: Vec<ConnectionID>,
+    astar_failures: &BTreeMap<ConnectionID, AStarFailure>,
+) -> RoutingError {
+    let reasons: Vec<String> = connection_ids
+        .iter()
+        .map(|connection_id| match astar_failures.get(connection_id) {
+            Some(failure) => format!("{:?}: {}", connection_id, failure),
+            None => format!("{:?}: no candidate trace was sampled", connection_id),
+        })
+        .collect();
+    RoutingError::UnroutableConnections {
+        connection_ids,
+        reason: reasons.join("; "),
+    }
+}
+
+/// adds the locked traces and the traces of the differential pairs to the solution of the strategy
+fn add_pre_routed_traces(solution: &mut PcbSolution, diff_pair_solution: &PcbSolution) {
+    // the locked traces are part of every solution, also of one that stopped before routing
+    for (connection_id, locked_trace) in diff_pair_solution.determined_traces.iter() {
+        solution.unrouted_connections.remove(connection_id);
+        solution
+            .determined_traces
+            .insert(*connection_id, locked_trace.clone());
+    }
+    // the connections of a pair that could not be routed together are not routed apart either
+    for (connection_id, reason) in diff_pair_solution.unrouted_connections.iter() {
+        solution.determined_traces.remove(connection_id);
+        solution
+            .unrouted_connections
+            .insert(*connection_id, reason.clone());
+    }
 }
 
 impl PcbProblem {
     pub fn new(width: f32, height: f32) -> Self {
         let net_id_generator = Box::new((0..).map(NetID));
         let connection_id_generator = Box::new((0..).map(ConnectionID));
+        let diff_pair_id_generator = Box::new((0..).map(DiffPairID));
         PcbProblem {
             width,
             height,
-            nets: HashMap::new(),
+            nets: BTreeMap::new(),
+            net_names: BTreeMap::new(),
             net_id_generator,
             connection_id_generator,
+            routing_cost: Arc::new(DefaultRoutingCost),
+            net_classes: BTreeMap::from([(DEFAULT_NET_CLASS.to_string(), NetClass::default())]),
+            class_clearances: BTreeMap::new(),
+            diff_pairs: BTreeMap::new(),
+            diff_pair_id_generator,
+            length_match_groups: BTreeMap::new(),
         }
     }
-    pub fn add_net(&mut self, color: Color) -> NetID {
-        let duplicate_color = self.nets.values().any(|net_info| net_info.color == color);
-        assert!(
-            !duplicate_color,
-            "Net with color {:?} already exists",
-            color
-        );
+    /// replaces the default length and bend cost, e.g. with per-region preferences
+    pub fn set_routing_cost(&mut self, routing_cost: Arc<dyn RoutingCost>) {
+        self.routing_cost = routing_cost;
+    }
+    /// adds a net with the next color of the palette
+    pub fn add_net(&mut self, name: &str) -> Result<NetID, RoutingError> {
+        let color = Color::from_palette(self.nets.len());
+        self.add_net_with_color(name, color)
+    }
+    pub fn add_net_with_color(&mut self, name: &str, color: Color) -> Result<NetID, RoutingError> {
+        if self.net_names.contains_key(name) {
+            return Err(RoutingError::InvalidInput(format!(
+                "Net with name {:?} already exists",
+                name
+            )));
+        }
         let net_id = self
             .net_id_generator
             .next()
             .expect("NetID generator exhausted");
         let net_info = NetInfo {
             net_id,
+            name: name.to_string(),
+            net_class: None,
             color,
-            connections: HashMap::new(),
+            connections: BTreeMap::new(),
+            routing_mode: RoutingMode::default(),
+            priority: 0,
         };
         self.nets.insert(net_id, net_info);
-        net_id
+        self.net_names.insert(name.to_string(), net_id);
+        Ok(net_id)
+    }
+    pub fn net_by_name(&self, name: &str) -> Option<NetID> {
+        self.net_names.get(name).cloned()
+    }
+    pub fn add_net_class(&mut self, net_class: NetClass) -> Result<(), RoutingError> {
+        if self.net_classes.contains_key(&net_class.name) {
+            return Err(RoutingError::InvalidInput(format!(
+                "Net class {:?} already exists",
+                net_class.name
+            )));
+        }
+        self.net_classes.insert(net_class.name.clone(), net_class);
+        Ok(())
+    }
+    fn net_class_by_name(&self, name: &str) -> Result<&NetClass, RoutingError> {
+        self.net_classes
+            .get(name)
+            .ok_or_else(|| RoutingError::InvalidInput(format!("Net class {:?} not found", name)))
+    }
+    /// the minimum clearance between the nets of two classes, in either order
+    pub fn set_class_clearance(
+        &mut self,
+        class1: &str,
+        class2: &str,
+        clearance: f32,
+    ) -> Result<(), RoutingError> {
+        self.net_class_by_name(class1)?;
+        self.net_class_by_name(class2)?;
+        self.class_clearances
+            .insert(class_pair_key(class1, class2), clearance);
+        Ok(())
+    }
+    /// assigns the net to the class and applies the routing mode of the class,
+    /// the connections without their own trace width and clearance are routed with those of the class
+    pub fn set_net_class(&mut self, net_id: NetID, net_class: &str) -> Result<(), RoutingError> {
+        let routing_mode = self.net_class_by_name(net_class)?.routing_mode;
+        let net_info = self.net_info_mut(net_id)?;
+        net_info.net_class = Some(net_class.to_string());
+        net_info.routing_mode = routing_mode;
+        Ok(())
+    }
+    /// the class name of the net, nets without a class belong to the default class
+    pub fn net_class_of(&self, net_id: NetID) -> &str {
+        self.nets
+            .get(&net_id)
+            .and_then(|net_info| net_info.net_class.as_deref())
+            .unwrap_or(DEFAULT_NET_CLASS)
+    }
+    /// the rules of the class of the net, the default rules for a class that is not defined (which validate reports)
+    fn net_class_rules(&self, net_id: NetID) -> NetClass {
+        self.net_classes
+            .get(self.net_class_of(net_id))
+            .cloned()
+            .unwrap_or_default()
     }
+    /// the width of the connection's own traces if it has one, the width of its net's class otherwise
+    /// resolved whenever a trace is routed, so that a later change of the class applies to existing connections
+    pub fn trace_width(&self, connection: &Connection) -> f32 {
+        connection
+            .trace_width
+            .unwrap_or_else(|| self.net_class_rules(connection.net_id).trace_width)
+    }
+    /// the clearance of the connection's own traces if it has one, the clearance of its net's class otherwise
+    pub fn trace_clearance(&self, connection: &Connection) -> f32 {
+        connection
+            .trace_clearance
+            .unwrap_or_else(|| self.net_class_rules(connection.net_id).trace_clearance)
+    }
+    /// the clearance required between two nets by the class matrix, 0.0 if the matrix has no rule for them
+    /// the effective clearance between two objects is the maximum of this and their own clearances
+    pub fn clearance_between_nets(&self, net1: NetID, net2: NetID) -> f32 {
+        let key = class_pair_key(self.net_class_of(net1), self.net_class_of(net2));
+        self.class_clearances.get(&key).cloned().unwrap_or(0.0)
+    }
+    /// the largest clearance required between any of the nets and the other net by the class matrix
+    pub(crate) fn clearance_to_nets(&self, net_ids: &[NetID], other_net_id: NetID) -> f32 {
+        net_ids
+            .iter()
+            .map(|net_id| self.clearance_between_nets(*net_id, other_net_id))
+            .fold(0.0, f32::max)
+    }
+    /// the shapes and clearance shapes of the pads of the other nets, which the traces of net_ids must avoid
+    /// a differential pair passes both of its nets, a single trace only its own
+    pub(crate) fn pad_obstacle_shapes(
+        &self,
+        net_ids: &[NetID],
+    ) -> (Vec<PrimShape>, Vec<PrimShape>) {
+        let mut obstacle_shapes: Vec<PrimShape> = Vec::new();
+        let mut obstacle_clearance_shapes: Vec<PrimShape> = Vec::new();
+        for (other_net_id, net_info) in self.nets.iter() {
+            if net_ids.contains(other_net_id) {
+                continue;
+            }
+            for connection in net_info.connections.values() {
+                obstacle_shapes.extend(connection.source.to_shapes());
+                obstacle_shapes.extend(connection.sink.to_shapes());
+                // keep the clearance between the net classes around the pads as well
+                let class_clearance = self.clearance_to_nets(net_ids, *other_net_id);
+                if class_clearance > 0.0 {
+                    for pad in [&connection.source, &connection.sink] {
+                        let class_clearance_pad = Pad {
+                            clearance: class_clearance,
+                            ..pad.clone()
+                        };
+                        obstacle_clearance_shapes.extend(class_clearance_pad.to_clearance_shapes());
+                    }
+                }
+            }
+        }
+        (obstacle_shapes, obstacle_clearance_shapes)
+    }
+
+    /// the shapes of the traces of other nets as obstacles of the nets, widened to the clearance between the net classes
+    pub(crate) fn trace_obstacle_shapes<'a>(
+        &self,
+        net_ids: &[NetID],
+        fixed_traces: impl IntoIterator<Item = &'a FixedTrace>,
+    ) -> (Vec<PrimShape>, Vec<PrimShape>) {
+        let mut obstacle_shapes: Vec<PrimShape> = Vec::new();
+        let mut obstacle_clearance_shapes: Vec<PrimShape> = Vec::new();
+        for fixed_trace in fixed_traces {
+            if net_ids.contains(&fixed_trace.net_id) {
+                continue;
+            }
+            let class_clearance = self.clearance_to_nets(net_ids, fixed_trace.net_id);
+            for segment in fixed_trace.trace_path.segments.iter() {
+                obstacle_shapes.extend(segment.to_shapes());
+                obstacle_clearance_shapes.extend(
+                    segment
+                        .with_min_clearance(class_clearance)
+                        .to_clearance_shapes(),
+                );
+            }
+        }
+        (obstacle_shapes, obstacle_clearance_shapes)
+    }
+
+    /// whether the trace of the net comes closer to a pad of another net than the A* search allows
+    pub fn trace_collides_with_pads(&self, net_id: NetID, trace_path: &TracePath) -> bool {
+        let (pad_shapes, pad_clearance_shapes) = self.pad_obstacle_shapes(&[net_id]);
+        let collides = |shapes: Vec<PrimShape>, obstacle_shapes: &Vec<PrimShape>| {
+            shapes.iter().any(|shape| {
+                obstacle_shapes
+                    .iter()
+                    .any(|obstacle_shape| shape.collides_with(obstacle_shape))
+            })
+        };
+        trace_path.segments.iter().any(|segment| {
+            collides(segment.to_shapes(), &pad_clearance_shapes)
+                || collides(segment.to_clearance_shapes(), &pad_shapes)
+        })
+    }
+    fn net_info_mut(&mut self, net_id: NetID) -> Result<&mut NetInfo, RoutingError> {
+        self.nets
+            .get_mut(&net_id)
+            .ok_or_else(|| RoutingError::InvalidInput(format!("NetID {:?} not found", net_id)))
+    }
+    /// overrides the routing mode of the net's class for this net, e.g. to route a single net with Manhattan segments
+    pub fn set_net_routing_mode(
+        &mut self,
+        net_id: NetID,
+        routing_mode: RoutingMode,
+    ) -> Result<(), RoutingError> {
+        let net_info = self.net_info_mut(net_id)?;
+        net_info.routing_mode = routing_mode;
+        Ok(())
+    }
+    pub fn set_net_priority(&mut self, net_id: NetID, priority: i32) -> Result<(), RoutingError> {
+        let net_info = self.net_info_mut(net_id)?;
+        net_info.priority = priority;
+        Ok(())
+    }
+    /// the min and max length of the trace of the connection, see LengthLimits
+    pub fn set_length_limits(
+        &mut self,
+        connection_id: ConnectionID,
+        length_limits: LengthLimits,
+    ) -> Result<(), RoutingError> {
+        let connection = self
+            .nets
+            .values_mut()
+            .find_map(|net_info| net_info.connections.get_mut(&connection_id))
+            .ok_or_else(|| {
+                RoutingError::InvalidInput(format!("ConnectionID {:?} not found", connection_id))
+            })?;
+        Arc::make_mut(connection).length_limits = length_limits;
+        Ok(())
+    }
+    /// adds a connection with its own trace width and clearance, which take precedence over the rules of the class of the net
     /// assert the sources in the same net are the same
     pub fn add_connection(
         &mut self,
@@ -984,8 +1722,29 @@ impl PcbProblem {
         sink: Pad,
         trace_width: f32,
         trace_clearance: f32,
-    ) -> ConnectionID {
-        let net_info = self.nets.get_mut(&net_id).expect("NetID not found");
+    ) -> Result<ConnectionID, RoutingError> {
+        self.insert_connection(net_id, source, sink, Some(trace_width), Some(trace_clearance))
+    }
+    /// adds a connection that is routed with the trace width and clearance of the class of the net,
+    /// also if the net is assigned to another class later
+    pub fn add_connection_from_class(
+        &mut self,
+        net_id: NetID,
+        source: Pad,
+        sink: Pad,
+    ) -> Result<ConnectionID, RoutingError> {
+        self.insert_connection(net_id, source, sink, None, None)
+    }
+    fn insert_connection(
+        &mut self,
+        net_id: NetID,
+        source: Pad,
+        sink: Pad,
+        trace_width: Option<f32>,
+        trace_clearance: Option<f32>,
+    ) -> Result<ConnectionID, RoutingError> {
+        // check the net before taking an id from the generator
+        self.net_info_mut(net_id)?;
         let connection_id = self
             .connection_id_generator
             .next()
@@ -997,27 +1756,289 @@ impl PcbProblem {
             sink,
             trace_width,
             trace_clearance,
+            length_limits: LengthLimits::default(),
         };
-        net_info
+        self.net_info_mut(net_id)?
             .connections
-            .insert(connection_id, Rc::new(connection));
-        connection_id
+            .insert(connection_id, Arc::new(connection));
+        Ok(connection_id)
     }
-
     pub fn solve(
         &self,
         pcb_render_model: Arc<Mutex<PcbRenderModel>>,
-    ) -> Result<PcbSolution, String> {
+    ) -> Result<PcbSolution, RoutingError> {
+        self.solve_with_config(&SolverConfig::default(), pcb_render_model)
+    }
+
+    pub fn solve_with_config(
+        &self,
+        config: &SolverConfig,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<PcbSolution, RoutingError> {
+        self.solve_with_locked_traces(&BTreeMap::new(), config, pcb_render_model)
+    }
+
+    /// routes the connections without a locked trace around the locked ones, which are part of the solution as given
+    /// the locked traces can come from an earlier PcbSolution, from read_traces, or from eco_locked_traces
+    pub fn solve_with_locked_traces(
+        &self,
+        locked_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<PcbSolution, RoutingError> {
+        // fail before routing if the input cannot be solved
+        let mut diagnostics = self.validate();
+        diagnostics.extend(self.validate_locked_traces(locked_traces));
+        let (errors, warnings): (Vec<Diagnostic>, Vec<Diagnostic>) = diagnostics
+            .into_iter()
+            .partition(|diagnostic| diagnostic.is_error());
+        for warning in warnings.iter() {
+            println!("{}", warning);
+        }
+        if !errors.is_empty() {
+            return Err(RoutingError::InvalidProblem(errors));
+        }
+        // the time limit starts after the validation
+        let stop_condition = config.new_stop_condition();
+        // the differential pairs are routed first and locked for the strategy, which routes the rest around them
+        let diff_pair_solution = self.route_diff_pairs(
+            locked_traces,
+            config,
+            &stop_condition,
+            pcb_render_model.clone(),
+        )?;
+        let pre_routed_traces = &diff_pair_solution.determined_traces;
+        let solution = match config.strategy {
+            SolverStrategy::NodeStack => self.solve_with_node_stack(
+                pre_routed_traces,
+                config,
+                &stop_condition,
+                pcb_render_model,
+            ),
+            SolverStrategy::Exact { max_nodes } => self.solve_exactly(
+                pre_routed_traces,
+                config,
+                &stop_condition,
+                max_nodes,
+                pcb_render_model,
+            ),
+            SolverStrategy::Negotiation { max_iterations } => self.solve_by_negotiation(
+                pre_routed_traces,
+                config,
+                &stop_condition,
+                max_iterations,
+                pcb_render_model,
+            ),
+            SolverStrategy::BeamSearch { beam_width } => self.solve_with_beam_search(
+                pre_routed_traces,
+                config,
+                &stop_condition,
+                beam_width,
+                pcb_render_model,
+            ),
+        };
+        let mut solution = match solution {
+            Ok(solution) => solution,
+            // the partial solution of a stop also keeps the locked traces and the pairs
+            Err(RoutingError::Stopped {
+                reason,
+                mut partial_solution,
+            }) => {
+                add_pre_routed_traces(&mut partial_solution, &diff_pair_solution);
+                return Err(RoutingError::Stopped {
+                    reason,
+                    partial_solution,
+                });
+            }
+            Err(RoutingError::SelectionBudgetExhausted {
+                explored_nodes,
+                mut partial_solution,
+            }) => {
+                add_pre_routed_traces(&mut partial_solution, &diff_pair_solution);
+                return Err(RoutingError::SelectionBudgetExhausted {
+                    explored_nodes,
+                    partial_solution,
+                });
+            }
+            Err(error) => return Err(error),
+        };
+        add_pre_routed_traces(&mut solution, &diff_pair_solution);
+        // the meanders of the length matching go around the finished routing, the locked traces are kept as given
+        for report in self.match_lengths(&mut solution, locked_traces)? {
+            println!("{}", report);
+        }
+        Ok(solution)
+    }
+
+    /// the solution of the routed traces, every other connection is unrouted for default_reason unless it already has a reason
+    pub(crate) fn partial_solution(
+        &self,
+        determined_traces: BTreeMap<ConnectionID, FixedTrace>,
+        mut unrouted_connections: BTreeMap<ConnectionID, UnroutedReason>,
+        default_reason: UnroutedReason,
+    ) -> PcbSolution {
+        for connection_id in self
+            .nets
+            .values()
+            .flat_map(|net_info| net_info.connections.keys())
+        {
+            if !determined_traces.contains_key(connection_id) {
+                unrouted_connections
+                    .entry(*connection_id)
+                    .or_insert(default_reason.clone());
+            }
+        }
+        PcbSolution {
+            determined_traces,
+            unrouted_connections,
+        }
+    }
+
+    /// samples the candidates once and chooses the cheapest collision-free combination of them
+    fn solve_exactly(
+        &self,
+        locked_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
+        max_nodes: usize,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<PcbSolution, RoutingError> {
+        let proba_model = ProbaModel::create_and_solve(
+            self,
+            locked_traces,
+            config,
+            stop_condition,
+            pcb_render_model,
+        )?;
+        // the candidates of a stopped sampling are incomplete, the candidates that fit are the best effort
+        if let Some(stop_reason) = stop_condition.check() {
+            let fixed_traces =
+                Node::from_proba_model(Arc::new(proba_model))?.greedy_fixed_traces(self);
+            return self.stopped_search(config, fixed_traces, BTreeMap::new(), stop_reason);
+        }
+        let connections_without_candidates: Vec<ConnectionID> = proba_model
+            .connection_to_traces
+            .iter()
+            .filter(|(_, traces)| matches!(traces, Traces::Probabilistic(trace_map) if trace_map.is_empty()))
+            .map(|(connection_id, _)| *connection_id)
+            .collect();
+        if !connections_without_candidates.is_empty() && !config.allow_partial {
+            return Err(unroutable_without_candidates(
+                connections_without_candidates,
+                &proba_model.astar_failures,
+            ));
+        }
+        match select_exactly(
+            &proba_model,
+            max_nodes,
+            config.allow_partial,
+            stop_condition,
+        )? {
+            ExactSelection::Assignment {
+                traces,
+                unrouted,
+                total_cost,
+                optimal,
+                explored_nodes,
+            } => {
+                println!(
+                    "Exact selection: total cost {:.3}, optimal: {}, {} nodes explored",
+                    total_cost, optimal, explored_nodes
+                );
+                let determined_traces = traces
+                    .into_iter()
+                    .map(|(connection_id, proba_trace)| {
+                        let fixed_trace = FixedTrace {
+                            net_id: proba_trace.net_id,
+                            connection_id,
+                            trace_path: proba_trace.trace_path.clone(),
+                        };
+                        (connection_id, fixed_trace)
+                    })
+                    .collect();
+                let unrouted_connections = unrouted
+                    .into_iter()
+                    .map(|connection_id| {
+                        let reason = if connections_without_candidates.contains(&connection_id) {
+                            reason_without_candidates(&connection_id, &proba_model.astar_failures)
+                        } else {
+                            UnroutedReason::Conflicting
+                        };
+                        (connection_id, reason)
+                    })
+                    .collect();
+                Ok(PcbSolution {
+                    determined_traces,
+                    unrouted_connections,
+                })
+            }
+            ExactSelection::Infeasible(proof) => Err(RoutingError::UnroutableConnections {
+                connection_ids: proof.connection_ids,
+                reason: format!(
+                    "the candidate traces of these connections cannot be chosen without a collision (exhaustive search of {} nodes)",
+                    proof.explored_nodes
+                ),
+            }),
+            ExactSelection::BudgetExhausted { explored_nodes } => {
+                // without an assignment of the search, the candidates that fit are the best effort, as after a stop
+                let unrouted_connections = connections_without_candidates
+                    .iter()
+                    .map(|connection_id| {
+                        let reason =
+                            reason_without_candidates(connection_id, &proba_model.astar_failures);
+                        (*connection_id, reason)
+                    })
+                    .collect();
+                let fixed_traces =
+                    Node::from_proba_model(Arc::new(proba_model))?.greedy_fixed_traces(self);
+                if let Some(stop_reason) = stop_condition.check() {
+                    return self.stopped_search(
+                        config,
+                        fixed_traces,
+                        unrouted_connections,
+                        stop_reason,
+                    );
+                }
+                println!(
+                    "Exact selection: no assignment within {} nodes, fixing the candidates that fit",
+                    explored_nodes
+                );
+                let partial_solution = self.partial_solution(
+                    fixed_traces,
+                    unrouted_connections,
+                    UnroutedReason::SelectionBudgetExhausted,
+                );
+                if config.allow_partial {
+                    return Ok(partial_solution);
+                }
+                Err(RoutingError::SelectionBudgetExhausted {
+                    explored_nodes,
+                    partial_solution: Box::new(partial_solution),
+                })
+            }
+        }
+    }
+
+    /// the depth-first search over nodes that fix one more trace each
+    fn solve_with_node_stack(
+        &self,
+        locked_traces: &BTreeMap<ConnectionID, FixedTrace>,
+        config: &SolverConfig,
+        stop_condition: &StopCondition,
+        pcb_render_model: Arc<Mutex<PcbRenderModel>>,
+    ) -> Result<PcbSolution, RoutingError> {
         let mut node_stack: Vec<Node> = Vec::new();
 
-        fn last_updated_node_index(node_stack: &Vec<Node>) -> usize {
+        fn last_updated_node_index(node_stack: &Vec<Node>) -> Result<usize, RoutingError> {
             for (index, node) in node_stack.iter().enumerate().rev() {
                 if node.prob_up_to_date {
-                    return index; // Return the index of the last updated node
+                    return Ok(index); // Return the index of the last updated node
                 }
             }
             // because the first node is always up to date, it is impossible to reach here
-            panic!("No updated node found in the stack");
+            Err(RoutingError::InternalInvariant(
+                "No updated node found in the stack".to_string(),
+            ))
         }
 
         fn print_current_stack(node_stack: &Vec<Node>) {
@@ -1033,23 +2054,77 @@ impl PcbProblem {
             }
         }
 
-        let first_node = Node::from_fixed_traces(self, &HashMap::new(), pcb_render_model.clone());
-        // assume the first node has trace candidates
+        let first_node = Node::from_fixed_traces(
+            self,
+            locked_traces,
+            config,
+            stop_condition,
+            pcb_render_model.clone(),
+        )?;
+        // the candidates of a stopped sampling are incomplete, so the connections without candidates are unknown,
+        // the candidates that fit are the best effort
+        if let Some(stop_reason) = stop_condition.check() {
+            let fixed_traces = first_node.greedy_fixed_traces(self);
+            return self.stopped_search(config, fixed_traces, BTreeMap::new(), stop_reason);
+        }
+        // without any fixed trace, a connection without candidates cannot be routed by the search below
+        let unroutable_connection_ids = first_node.connections_without_candidates(self);
+        if !unroutable_connection_ids.is_empty() && !config.allow_partial {
+            return Err(unroutable_without_candidates(
+                unroutable_connection_ids,
+                &first_node.astar_failures,
+            ));
+        }
+        // in the partial mode the search routes the other connections
+        let unrouted_connections: BTreeMap<ConnectionID, UnroutedReason> =
+            unroutable_connection_ids
+                .iter()
+                .map(|connection_id| {
+                    let reason =
+                        reason_without_candidates(connection_id, &first_node.astar_failures);
+                    (*connection_id, reason)
+                })
+                .collect();
+        let unroutable_connection_ids: BTreeSet<ConnectionID> =
+            unroutable_connection_ids.into_iter().collect();
+        // the deepest node explains which connections could not be routed if the search fails
+        let mut max_fixed_traces: BTreeMap<ConnectionID, FixedTrace> = BTreeMap::new();
         node_stack.push(first_node);
 
+        let mut num_solver_iterations: usize = 0;
         while node_stack.len() > 0 {
             print_current_stack(&node_stack);
             let top_node = node_stack.last_mut().unwrap();
-            if top_node.is_solution(self) {
+            if top_node.fixed_traces.len() >= max_fixed_traces.len() {
+                max_fixed_traces = top_node.fixed_traces.clone();
+            }
+            if top_node.is_solution_except(self, &unroutable_connection_ids) {
                 println!("Found a solution!");
                 // If the top node is a solution, we can return it
                 let fixed_traces = top_node.fixed_traces.clone();
                 let solution = PcbSolution {
                     determined_traces: fixed_traces,
+                    unrouted_connections,
                 };
                 return Ok(solution);
             }
-            let new_node = top_node.try_fix_top_ranked_trace();
+            num_solver_iterations += 1;
+            let stop_reason = stop_condition.check().or_else(|| {
+                config
+                    .max_solver_iterations
+                    .filter(|max_iterations| num_solver_iterations > *max_iterations)
+                    .map(|_| StopReason::IterationLimitReached)
+            });
+            if let Some(stop_reason) = stop_reason {
+                println!("Stopping the search: {:?}", stop_reason);
+                return self.stopped_search(
+                    config,
+                    max_fixed_traces,
+                    unrouted_connections,
+                    stop_reason,
+                );
+            }
+            let new_node = top_node.try_fix_top_ranked_trace(self);
             match new_node {
                 Some(new_node) => {
                     // If we successfully fixed a trace, push the new node onto the stack
@@ -1065,17 +2140,24 @@ impl PcbProblem {
                         "Failed to fix the top ranked trace, trying to update the probabilistic model in the middle of the stack"
                     );
                     let current_node_index = node_stack.len() - 1;
-                    let last_updated_index = last_updated_node_index(&node_stack);
+                    let last_updated_index = last_updated_node_index(&node_stack)?;
                     let target_index = (current_node_index + last_updated_index + 1) / 2; // bias to right for consistency
-                    let new_node = node_stack[target_index]
-                        .try_update_proba_model(self, pcb_render_model.clone());
+                    let new_node = node_stack[target_index].try_update_proba_model(
+                        self,
+                        config,
+                        stop_condition,
+                        pcb_render_model.clone(),
+                    )?;
                     match new_node {
                         Some(new_node) => {
                             // If we successfully updated the probabilistic model, replace the node at the target index with the new node
-                            assert!(
+                            ensure_invariant!(
                                 target_index < node_stack.len(),
                                 "Target index must be within the stack bounds"
                             );
+                            // the updated copy sits above the target node, so the target node is not updated again
+                            // otherwise a node without candidates would be updated forever
+                            node_stack[target_index].prob_up_to_date = true;
                             if target_index == node_stack.len() - 1 {
                                 node_stack.push(new_node);
                             } else {
@@ -1089,7 +2171,7 @@ impl PcbProblem {
                         }
                         None => {
                             // If we failed to update the probabilistic model, we pop the current node from the stack
-                            assert!(
+                            ensure_invariant!(
                                 target_index == node_stack.len() - 1,
                                 "target index must be the last node in the stack"
                             );
@@ -1102,6 +2184,57 @@ impl PcbProblem {
                 }
             }
         }
-        Err("No solution found".to_string())
+        self.failed_search(config, max_fixed_traces, unrouted_connections)
+    }
+
+    /// the result of a search that stopped early, the routed traces in the partial mode
+    /// and an error carrying the same partial solution otherwise
+    pub(crate) fn stopped_search(
+        &self,
+        config: &SolverConfig,
+        determined_traces: BTreeMap<ConnectionID, FixedTrace>,
+        unrouted_connections: BTreeMap<ConnectionID, UnroutedReason>,
+        stop_reason: StopReason,
+    ) -> Result<PcbSolution, RoutingError> {
+        let partial_solution = self.partial_solution(
+            determined_traces,
+            unrouted_connections,
+            UnroutedReason::Stopped(stop_reason),
+        );
+        if !config.allow_partial {
+            return Err(RoutingError::Stopped {
+                reason: stop_reason,
+                partial_solution: Box::new(partial_solution),
+            });
+        }
+        Ok(partial_solution)
+    }
+
+    /// the result of a node search that ran out of nodes, the deepest node is the best effort
+    pub(crate) fn failed_search(
+        &self,
+        config: &SolverConfig,
+        max_fixed_traces: BTreeMap<ConnectionID, FixedTrace>,
+        unrouted_connections: BTreeMap<ConnectionID, UnroutedReason>,
+    ) -> Result<PcbSolution, RoutingError> {
+        if config.allow_partial {
+            return Ok(self.partial_solution(
+                max_fixed_traces,
+                unrouted_connections,
+                UnroutedReason::Conflicting,
+            ));
+        }
+        let mut unrouted_connection_ids: Vec<ConnectionID> = self
+            .nets
+            .values()
+            .flat_map(|net_info| net_info.connections.keys())
+            .filter(|connection_id| !max_fixed_traces.contains_key(connection_id))
+            .cloned()
+            .collect();
+        unrouted_connection_ids.sort();
+        Err(RoutingError::UnroutableConnections {
+            connection_ids: unrouted_connection_ids,
+            reason: "no collision-free combination of candidate traces was found".to_string(),
+        })
     }
 }
@@ -198,3 +198,36 @@ impl PrimShape {
         }
     }
 }
+
+/// an axis-aligned box around shapes, used to skip exact collision tests of shapes that are far apart
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingBox {
+    pub min: FloatVec2,
+    pub max: FloatVec2,
+}
+
+impl BoundingBox {
+    /// the smallest box containing the points, None if there are no points
+    pub fn from_points(points: impl IntoIterator<Item = FloatVec2>) -> Option<BoundingBox> {
+        points.into_iter().fold(None, |bounding_box, point| {
+            let bounding_box = bounding_box.unwrap_or(BoundingBox { min: point, max: point });
+            Some(BoundingBox {
+                min: FloatVec2 { x: bounding_box.min.x.min(point.x), y: bounding_box.min.y.min(point.y) },
+                max: FloatVec2 { x: bounding_box.max.x.max(point.x), y: bounding_box.max.y.max(point.y) },
+            })
+        })
+    }
+    pub fn inflate(&self, margin: f32) -> BoundingBox {
+        BoundingBox {
+            min: FloatVec2 { x: self.min.x - margin, y: self.min.y - margin },
+            max: FloatVec2 { x: self.max.x + margin, y: self.max.y + margin },
+        }
+    }
+    /// touching boxes overlap
+    pub fn overlaps(&self, other: &BoundingBox) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+}
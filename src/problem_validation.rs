@@ -0,0 +1,574 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    diff_pair::DiffPair,
+    length_matching::LengthMatchGroup,
+    net_class::NetClass,
+    pad::{Pad, PadShape},
+    pcb_problem::{Connection, ConnectionID, FixedTrace, LengthLimits, NetID, PcbProblem},
+    prim_shape::PrimShape,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticSeverity {
+    Error,   // the problem cannot be solved as given
+    Warning, // the problem can be solved, but probably not as intended
+}
+
+/// a finding of PcbProblem::validate, with the nets and connections it is about
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: DiagnosticSeverity,
+    pub net_ids: Vec<NetID>,
+    pub connection_ids: Vec<ConnectionID>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(net_ids: Vec<NetID>, connection_ids: Vec<ConnectionID>, message: String) -> Self {
+        Diagnostic {
+            severity: DiagnosticSeverity::Error,
+            net_ids,
+            connection_ids,
+            message,
+        }
+    }
+
+    fn warning(net_ids: Vec<NetID>, connection_ids: Vec<ConnectionID>, message: String) -> Self {
+        Diagnostic {
+            severity: DiagnosticSeverity::Warning,
+            net_ids,
+            connection_ids,
+            message,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == DiagnosticSeverity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.severity, self.message)?;
+        if !self.net_ids.is_empty() {
+            write!(f, ", nets {:?}", self.net_ids)?;
+        }
+        if !self.connection_ids.is_empty() {
+            write!(f, ", connections {:?}", self.connection_ids)?;
+        }
+        Ok(())
+    }
+}
+
+/// whether the shape lies within the board, which is centered at the origin
+fn is_shape_inside_board(shape: &PrimShape, width: f32, height: f32) -> bool {
+    let half_width = width / 2.0;
+    let half_height = height / 2.0;
+    match shape {
+        PrimShape::Circle(circle) => {
+            let radius = circle.diameter / 2.0;
+            circle.position.x - radius >= -half_width
+                && circle.position.x + radius <= half_width
+                && circle.position.y - radius >= -half_height
+                && circle.position.y + radius <= half_height
+        }
+        PrimShape::Rectangle(rectangle) => rectangle
+            .to_polygon()
+            .0
+            .iter()
+            .all(|corner| corner.x.abs() <= half_width && corner.y.abs() <= half_height),
+    }
+}
+
+/// whether the pads are closer than the clearance of either of them or min_clearance
+fn pads_overlap_within_clearance(pad1: &Pad, pad2: &Pad, min_clearance: f32) -> bool {
+    let raise_clearance = |pad: &Pad| Pad {
+        clearance: pad.clearance.max(min_clearance),
+        ..pad.clone()
+    };
+    let (pad1, pad2) = (&raise_clearance(pad1), &raise_clearance(pad2));
+    let collides = |shapes: Vec<PrimShape>, clearance_shapes: Vec<PrimShape>| {
+        shapes.iter().any(|shape| {
+            clearance_shapes
+                .iter()
+                .any(|clearance_shape| shape.collides_with(clearance_shape))
+        })
+    };
+    collides(pad1.to_shapes(), pad2.to_clearance_shapes())
+        || collides(pad2.to_shapes(), pad1.to_clearance_shapes())
+}
+
+// NaN is neither positive nor non-negative
+fn is_positive(value: f32) -> bool {
+    value > 0.0
+}
+
+fn is_non_negative(value: f32) -> bool {
+    value >= 0.0
+}
+
+fn is_same_position(pad1: &Pad, pad2: &Pad) -> bool {
+    pad1.position.x == pad2.position.x && pad1.position.y == pad2.position.y
+}
+
+fn validate_net_class(net_class: &NetClass) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    if !is_positive(net_class.trace_width) {
+        messages.push(format!(
+            "the trace width of net class {:?} must be positive, got {}",
+            net_class.name, net_class.trace_width
+        ));
+    }
+    if !is_non_negative(net_class.trace_clearance) {
+        messages.push(format!(
+            "the trace clearance of net class {:?} must not be negative, got {}",
+            net_class.name, net_class.trace_clearance
+        ));
+    }
+    messages
+}
+
+fn validate_length_limits(connection: &Connection) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    let LengthLimits {
+        min_length,
+        max_length,
+    } = connection.length_limits;
+    for (limit_name, limit) in [("min length", min_length), ("max length", max_length)] {
+        if let Some(limit) = limit
+            && !is_positive(limit)
+        {
+            messages.push(format!(
+                "the {} must be positive, got {}",
+                limit_name, limit
+            ));
+        }
+    }
+    if let (Some(min_length), Some(max_length)) = (min_length, max_length)
+        && min_length > max_length
+    {
+        messages.push(format!(
+            "the min length {} is greater than the max length {}",
+            min_length, max_length
+        ));
+    }
+    // no trace is shorter than the straight line between the pads
+    let distance = connection
+        .sink
+        .position
+        .sub(connection.source.position)
+        .magnitude2()
+        .sqrt();
+    if let Some(max_length) = max_length
+        && max_length < distance
+    {
+        messages.push(format!(
+            "the max length {} is shorter than the distance {} between the pads",
+            max_length, distance
+        ));
+    }
+    messages
+}
+
+fn validate_pad(pad: &Pad) -> Option<String> {
+    let positive_size = match pad.shape {
+        PadShape::Circle { diameter } => is_positive(diameter),
+        PadShape::Square { side_length } => is_positive(side_length),
+        PadShape::Rectangle { width, height } => is_positive(width) && is_positive(height),
+    };
+    if !positive_size {
+        return Some(format!("the size of {:?} must be positive", pad.shape));
+    }
+    if !is_non_negative(pad.clearance) {
+        return Some(format!(
+            "the pad clearance must not be negative, got {}",
+            pad.clearance
+        ));
+    }
+    None
+}
+
+impl PcbProblem {
+    /// checks the problem for inputs that the solver cannot handle, errors make solve fail before routing
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        if !(is_positive(self.width) && is_positive(self.height)) {
+            diagnostics.push(Diagnostic::error(
+                vec![],
+                vec![],
+                format!(
+                    "the board size must be positive, got {} x {}",
+                    self.width, self.height
+                ),
+            ));
+        }
+
+        for net_class in self.net_classes.values() {
+            for message in validate_net_class(net_class) {
+                diagnostics.push(Diagnostic::error(vec![], vec![], message));
+            }
+        }
+        for ((class1, class2), clearance) in self.class_clearances.iter() {
+            for class_name in [class1, class2] {
+                if !self.net_classes.contains_key(class_name) {
+                    diagnostics.push(Diagnostic::error(
+                        vec![],
+                        vec![],
+                        format!(
+                            "the clearance matrix refers to the unknown net class {:?}",
+                            class_name
+                        ),
+                    ));
+                }
+            }
+            if !is_non_negative(*clearance) {
+                diagnostics.push(Diagnostic::error(
+                    vec![],
+                    vec![],
+                    format!(
+                        "the clearance between net classes {:?} and {:?} must not be negative, got {}",
+                        class1, class2, clearance
+                    ),
+                ));
+            }
+        }
+
+        // every pad with the net and the connection it belongs to, a shared source pad is listed once
+        let mut pads: Vec<(NetID, ConnectionID, &Pad)> = Vec::new();
+        for net_id in self.nets.keys() {
+            let net_info = &self.nets[net_id];
+            if self.net_names.get(&net_info.name) != Some(net_id) {
+                diagnostics.push(Diagnostic::error(
+                    vec![*net_id],
+                    vec![],
+                    format!(
+                        "the net name {:?} is not unique or not registered",
+                        net_info.name
+                    ),
+                ));
+            }
+            if let Some(class_name) = &net_info.net_class
+                && !self.net_classes.contains_key(class_name)
+            {
+                diagnostics.push(Diagnostic::error(
+                    vec![*net_id],
+                    vec![],
+                    format!("the net class {:?} is not defined", class_name),
+                ));
+            }
+            if net_info.connections.is_empty() {
+                diagnostics.push(Diagnostic::warning(
+                    vec![*net_id],
+                    vec![],
+                    "the net has no connections".to_string(),
+                ));
+            }
+
+            let connection_ids: Vec<ConnectionID> = net_info.connections.keys().cloned().collect();
+            for (connection_index, connection_id) in connection_ids.iter().enumerate() {
+                let connection = &net_info.connections[connection_id];
+                // the rules taken from the class are checked with the class
+                if let Some(trace_width) = connection.trace_width
+                    && !is_positive(trace_width)
+                {
+                    diagnostics.push(Diagnostic::error(
+                        vec![*net_id],
+                        vec![*connection_id],
+                        format!("the trace width must be positive, got {}", trace_width),
+                    ));
+                }
+                if let Some(trace_clearance) = connection.trace_clearance
+                    && !is_non_negative(trace_clearance)
+                {
+                    diagnostics.push(Diagnostic::error(
+                        vec![*net_id],
+                        vec![*connection_id],
+                        format!(
+                            "the trace clearance must not be negative, got {}",
+                            trace_clearance
+                        ),
+                    ));
+                } else if self.trace_clearance(connection) == 0.0 {
+                    diagnostics.push(Diagnostic::warning(
+                        vec![*net_id],
+                        vec![*connection_id],
+                        "the trace clearance is zero".to_string(),
+                    ));
+                }
+                if is_same_position(&connection.source, &connection.sink) {
+                    diagnostics.push(Diagnostic::error(
+                        vec![*net_id],
+                        vec![*connection_id],
+                        "the source and the sink are at the same position".to_string(),
+                    ));
+                }
+                diagnostics.extend(
+                    validate_length_limits(connection)
+                        .into_iter()
+                        .map(|message| {
+                            Diagnostic::error(vec![*net_id], vec![*connection_id], message)
+                        }),
+                );
+                for other_connection_id in connection_ids[..connection_index].iter() {
+                    let other_connection = &net_info.connections[other_connection_id];
+                    let same_direction =
+                        is_same_position(&connection.source, &other_connection.source)
+                            && is_same_position(&connection.sink, &other_connection.sink);
+                    let reversed = is_same_position(&connection.source, &other_connection.sink)
+                        && is_same_position(&connection.sink, &other_connection.source);
+                    if same_direction || reversed {
+                        diagnostics.push(Diagnostic::warning(
+                            vec![*net_id],
+                            vec![*other_connection_id, *connection_id],
+                            "the connections join the same pads".to_string(),
+                        ));
+                    }
+                }
+
+                for pad in [&connection.source, &connection.sink] {
+                    if pads.iter().any(|(other_net_id, _, other_pad)| {
+                        other_net_id == net_id && is_same_position(pad, other_pad)
+                    }) {
+                        continue; // already checked
+                    }
+                    if let Some(message) = validate_pad(pad) {
+                        diagnostics.push(Diagnostic::error(
+                            vec![*net_id],
+                            vec![*connection_id],
+                            message,
+                        ));
+                    }
+                    let inside_board = pad
+                        .to_shapes()
+                        .iter()
+                        .all(|shape| is_shape_inside_board(shape, self.width, self.height));
+                    if !inside_board {
+                        diagnostics.push(Diagnostic::error(
+                            vec![*net_id],
+                            vec![*connection_id],
+                            format!(
+                                "the pad at ({}, {}) is not inside the board",
+                                pad.position.x, pad.position.y
+                            ),
+                        ));
+                    }
+                    pads.push((*net_id, *connection_id, pad));
+                }
+            }
+        }
+
+        // pads of different nets would short before any trace is routed
+        for (index, (net_id, connection_id, pad)) in pads.iter().enumerate() {
+            for (other_net_id, other_connection_id, other_pad) in pads[..index].iter() {
+                if other_net_id == net_id {
+                    continue;
+                }
+                let class_clearance = self.clearance_between_nets(*net_id, *other_net_id);
+                if !pads_overlap_within_clearance(pad, other_pad, class_clearance) {
+                    continue;
+                }
+                diagnostics.push(Diagnostic::error(
+                    vec![*other_net_id, *net_id],
+                    vec![*other_connection_id, *connection_id],
+                    format!(
+                        "the pads at ({}, {}) and ({}, {}) overlap within their clearance",
+                        other_pad.position.x, other_pad.position.y, pad.position.x, pad.position.y
+                    ),
+                ));
+            }
+        }
+
+        for diff_pair in self.diff_pairs.values() {
+            diagnostics.extend(self.validate_diff_pair(diff_pair));
+        }
+        for group in self.length_match_groups.values() {
+            diagnostics.extend(self.validate_length_match_group(group));
+        }
+        diagnostics
+    }
+
+    fn validate_length_match_group(&self, group: &LengthMatchGroup) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let mut net_ids: Vec<NetID> = Vec::new();
+        for connection_id in group.connection_ids.iter() {
+            match self.connection(*connection_id) {
+                Ok(connection) => net_ids.push(connection.net_id),
+                Err(_) => diagnostics.push(Diagnostic::error(
+                    vec![],
+                    vec![*connection_id],
+                    format!(
+                        "the length match group {:?} refers to an unknown connection",
+                        group.name
+                    ),
+                )),
+            }
+        }
+        if !is_non_negative(group.tolerance) {
+            diagnostics.push(Diagnostic::error(
+                net_ids.clone(),
+                group.connection_ids.clone(),
+                format!(
+                    "the tolerance of length match group {:?} must not be negative, got {}",
+                    group.name, group.tolerance
+                ),
+            ));
+        }
+        if group.connection_ids.len() < 2 {
+            diagnostics.push(Diagnostic::warning(
+                net_ids,
+                group.connection_ids.clone(),
+                format!(
+                    "the length match group {:?} has fewer than two connections",
+                    group.name
+                ),
+            ));
+        }
+        diagnostics
+    }
+
+    fn validate_diff_pair(&self, diff_pair: &DiffPair) -> Vec<Diagnostic> {
+        let connection_ids = vec![
+            diff_pair.positive_connection_id,
+            diff_pair.negative_connection_id,
+        ];
+        let (Ok(positive_connection), Ok(negative_connection)) = (
+            self.connection(diff_pair.positive_connection_id),
+            self.connection(diff_pair.negative_connection_id),
+        ) else {
+            return vec![Diagnostic::error(
+                vec![],
+                connection_ids,
+                format!(
+                    "the differential pair {:?} refers to an unknown connection",
+                    diff_pair.diff_pair_id
+                ),
+            )];
+        };
+        let net_ids = vec![positive_connection.net_id, negative_connection.net_id];
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        if !is_positive(diff_pair.trace_width) {
+            diagnostics.push(Diagnostic::error(
+                net_ids.clone(),
+                connection_ids.clone(),
+                format!(
+                    "the trace width of differential pair {:?} must be positive, got {}",
+                    diff_pair.diff_pair_id, diff_pair.trace_width
+                ),
+            ));
+        }
+        // the traces of the pair are as close as the gap, which must keep their clearance
+        let min_gap = self
+            .trace_clearance(positive_connection)
+            .max(self.trace_clearance(negative_connection))
+            .max(self.clearance_between_nets(net_ids[0], net_ids[1]));
+        if !is_positive(diff_pair.gap) || diff_pair.gap < min_gap {
+            diagnostics.push(Diagnostic::error(
+                net_ids.clone(),
+                connection_ids.clone(),
+                format!(
+                    "the gap of differential pair {:?} must be positive and at least the clearance {} of its traces, got {}",
+                    diff_pair.diff_pair_id, min_gap, diff_pair.gap
+                ),
+            ));
+        }
+        let other_diff_pair = self.diff_pairs.values().find(|other_diff_pair| {
+            other_diff_pair.diff_pair_id != diff_pair.diff_pair_id
+                && connection_ids.iter().any(|connection_id| {
+                    [
+                        other_diff_pair.positive_connection_id,
+                        other_diff_pair.negative_connection_id,
+                    ]
+                    .contains(connection_id)
+                })
+        });
+        if let Some(other_diff_pair) = other_diff_pair {
+            diagnostics.push(Diagnostic::error(
+                net_ids.clone(),
+                connection_ids.clone(),
+                format!(
+                    "the differential pairs {:?} and {:?} share a connection",
+                    diff_pair.diff_pair_id, other_diff_pair.diff_pair_id
+                ),
+            ));
+        }
+        if self.nets[&net_ids[0]].routing_mode != self.nets[&net_ids[1]].routing_mode {
+            diagnostics.push(Diagnostic::warning(
+                net_ids,
+                connection_ids,
+                format!(
+                    "the nets of differential pair {:?} have different routing modes, the pair uses the mode of the positive net",
+                    diff_pair.diff_pair_id
+                ),
+            ));
+        }
+        diagnostics
+    }
+
+    /// checks the traces that PcbProblem::solve_with_locked_traces keeps as given
+    pub fn validate_locked_traces(
+        &self,
+        locked_traces: &BTreeMap<ConnectionID, FixedTrace>,
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        // the traces that belong to their connections, checked against each other below
+        let mut valid_traces: Vec<&FixedTrace> = Vec::new();
+        for (connection_id, locked_trace) in locked_traces.iter() {
+            let net_ids = vec![locked_trace.net_id];
+            let connection_ids = vec![*connection_id];
+            let belongs_to_net = self
+                .nets
+                .get(&locked_trace.net_id)
+                .is_some_and(|net_info| net_info.connections.contains_key(connection_id));
+            if locked_trace.connection_id != *connection_id || !belongs_to_net {
+                diagnostics.push(Diagnostic::error(
+                    net_ids,
+                    connection_ids,
+                    format!(
+                        "the locked trace of connection {:?} in net {:?} is not a connection of that net",
+                        locked_trace.connection_id, locked_trace.net_id
+                    ),
+                ));
+                continue;
+            }
+            if locked_trace.trace_path.segments.is_empty() {
+                diagnostics.push(Diagnostic::error(
+                    net_ids,
+                    connection_ids,
+                    "the locked trace has no segments".to_string(),
+                ));
+                continue;
+            }
+            if self.trace_collides_with_pads(locked_trace.net_id, &locked_trace.trace_path) {
+                diagnostics.push(Diagnostic::error(
+                    net_ids,
+                    connection_ids,
+                    "the locked trace overlaps a pad of another net within their clearance"
+                        .to_string(),
+                ));
+            }
+            valid_traces.push(locked_trace);
+        }
+
+        for (index, locked_trace) in valid_traces.iter().enumerate() {
+            for other_trace in valid_traces[..index].iter() {
+                if other_trace.net_id == locked_trace.net_id {
+                    continue; // traces of the same net may touch
+                }
+                let class_clearance =
+                    self.clearance_between_nets(locked_trace.net_id, other_trace.net_id);
+                if locked_trace
+                    .trace_path
+                    .collides_with_clearance(&other_trace.trace_path, class_clearance)
+                {
+                    diagnostics.push(Diagnostic::error(
+                        vec![other_trace.net_id, locked_trace.net_id],
+                        vec![other_trace.connection_id, locked_trace.connection_id],
+                        "the locked traces overlap within their clearance".to_string(),
+                    ));
+                }
+            }
+        }
+        diagnostics
+    }
+}
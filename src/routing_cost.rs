@@ -0,0 +1,69 @@
+use crate::{
+    astar::RoutingMode, hyperparameters::ESTIMATE_COEFFICIENT, trace_path::turn_penalty,
+    vec2::FixedVec2,
+};
+
+/// The cost model of the A* search.
+/// The search stays optimal only if `estimated_cost` is admissible:
+/// it must never exceed the cheapest sum of `segment_cost` and `turn_cost` from the position to the end.
+/// Costs that only add to the trace length (congestion, penalty regions, ...) keep the default heuristic admissible.
+pub trait RoutingCost: Send + Sync {
+    /// the cost of a straight segment from start to end, not including the bend at start
+    fn segment_cost(&self, start: FixedVec2, end: FixedVec2) -> f64;
+    /// the cost of bending by turn_angle degrees at position, None if the bend is not allowed
+    fn turn_cost(&self, position: FixedVec2, turn_angle: f32) -> Option<f64>;
+    /// a lower bound of the remaining cost from position to end, exactly 0.0 at end, which the search checks for the found path
+    fn estimated_cost(&self, position: FixedVec2, end: FixedVec2, routing_mode: RoutingMode)
+    -> f64;
+}
+
+pub fn euclidean_distance(start: &FixedVec2, end: &FixedVec2) -> f64 {
+    let start = start.to_float();
+    let end = end.to_float();
+    let dx = (end.x - start.x) as f64;
+    let dy = (end.y - start.y) as f64;
+    f64::sqrt(dx * dx + dy * dy)
+}
+
+pub fn octile_distance(start: &FixedVec2, end: &FixedVec2) -> f64 {
+    let start = start.to_float();
+    let end = end.to_float();
+    let dx = (end.x - start.x).abs() as f64;
+    let dy = (end.y - start.y).abs() as f64;
+    f64::max(dx, dy) + (f64::sqrt(2.0) - 1.0) * f64::min(dx, dy)
+}
+
+pub fn manhattan_distance(start: &FixedVec2, end: &FixedVec2) -> f64 {
+    let start = start.to_float();
+    let end = end.to_float();
+    (end.x - start.x).abs() as f64 + (end.y - start.y).abs() as f64
+}
+
+/// the trace length plus the bend penalties,
+/// estimated by the shortest distance that the routing mode allows (octile for the octilinear mode)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultRoutingCost;
+
+impl RoutingCost for DefaultRoutingCost {
+    fn segment_cost(&self, start: FixedVec2, end: FixedVec2) -> f64 {
+        (end - start).length().to_num()
+    }
+
+    fn turn_cost(&self, _position: FixedVec2, turn_angle: f32) -> Option<f64> {
+        turn_penalty(turn_angle)
+    }
+
+    fn estimated_cost(
+        &self,
+        position: FixedVec2,
+        end: FixedVec2,
+        routing_mode: RoutingMode,
+    ) -> f64 {
+        let distance = match routing_mode {
+            RoutingMode::Octilinear => octile_distance(&position, &end),
+            RoutingMode::Manhattan => manhattan_distance(&position, &end),
+            RoutingMode::AnyAngle => euclidean_distance(&position, &end),
+        };
+        distance * ESTIMATE_COEFFICIENT
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use crate::{
+    astar::{AStarFailure, AStarFailureReason},
+    pcb_problem::{ConnectionID, PcbSolution},
+    problem_validation::Diagnostic,
+    stop_condition::StopReason,
+};
+
+/// the errors of the public routing API, so that odd inputs do not abort the process
+#[derive(Debug, Clone)]
+pub enum RoutingError {
+    InvalidInput(String),            // the problem description is inconsistent
+    InvalidProblem(Vec<Diagnostic>), // the errors found by PcbProblem::validate
+    UnroutableConnections {
+        connection_ids: Vec<ConnectionID>,
+        reason: String,
+    },
+    SearchBudgetExhausted(AStarFailure), // the A* search ran out of expansions or time
+    NoPathFound(AStarFailure),           // the A* search expanded every reachable position
+    Stopped {
+        reason: StopReason,
+        partial_solution: Box<PcbSolution>, // the traces routed before the stop, as allow_partial would return them
+    }, // stopped before a solution, without allow_partial
+    SelectionBudgetExhausted {
+        explored_nodes: usize,
+        partial_solution: Box<PcbSolution>, // the candidates that fit, as allow_partial would return them
+    }, // the exact selection found no combination within its node budget, without allow_partial
+    InternalInvariant(String),           // a bug in the router, not in the input
+    Io(String),                          // reading or writing a file failed
+}
+
+impl fmt::Display for RoutingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoutingError::InvalidInput(message) => write!(f, "Invalid input: {}", message),
+            RoutingError::InvalidProblem(diagnostics) => {
+                let messages: Vec<String> = diagnostics
+                    .iter()
+                    .map(|diagnostic| diagnostic.to_string())
+                    .collect();
+                write!(f, "Invalid problem: {}", messages.join("; "))
+            }
+            RoutingError::UnroutableConnections {
+                connection_ids,
+                reason,
+            } => write!(f, "Unroutable connections {:?}: {}", connection_ids, reason),
+            RoutingError::SearchBudgetExhausted(failure) => {
+                write!(f, "A* search budget exhausted: {}", failure)
+            }
+            RoutingError::NoPathFound(failure) => write!(f, "No path found: {}", failure),
+            RoutingError::Stopped {
+                reason,
+                partial_solution,
+            } => {
+                write!(
+                    f,
+                    "The solver stopped with {:?} before a solution was found, {} connections were routed",
+                    reason,
+                    partial_solution.determined_traces.len()
+                )
+            }
+            RoutingError::SelectionBudgetExhausted {
+                explored_nodes,
+                partial_solution,
+            } => {
+                write!(
+                    f,
+                    "The exact selection found no collision-free combination within {} search nodes, {} connections were routed",
+                    explored_nodes,
+                    partial_solution.determined_traces.len()
+                )
+            }
+            RoutingError::InternalInvariant(message) => {
+                write!(f, "Internal invariant violated: {}", message)
+            }
+            RoutingError::Io(message) => write!(f, "I/O error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RoutingError {}
+
+impl From<AStarFailure> for RoutingError {
+    fn from(failure: AStarFailure) -> Self {
+        match failure.reason {
+            AStarFailureReason::ExpansionLimitReached
+            | AStarFailureReason::TimeLimitReached
+            | AStarFailureReason::Cancelled => RoutingError::SearchBudgetExhausted(failure),
+            AStarFailureReason::FrontierExhausted => RoutingError::NoPathFound(failure),
+        }
+    }
+}
+
+/// like assert!, but returns RoutingError::InternalInvariant from the enclosing function instead of panicking
+macro_rules! ensure_invariant {
+    ($condition:expr) => {
+        $crate::routing_error::ensure_invariant!($condition, "{}", stringify!($condition))
+    };
+    ($condition:expr, $($message:tt)+) => {
+        let condition: bool = $condition;
+        if !condition {
+            return Err($crate::routing_error::RoutingError::InternalInvariant(format!($($message)+)));
+        }
+    };
+}
+pub(crate) use ensure_invariant;
@@ -0,0 +1,108 @@
+use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use crate::{
+    astar::SearchBudget,
+    hyperparameters::{
+        BEAM_WIDTH, EXACT_MAX_NODES, MAX_ITERATION, MAX_POSTERIOR_UPDATES,
+        NEGOTIATION_MAX_ITERATIONS, POSTERIOR_TOLERANCE,
+    },
+    inference_engine::{FixedPointInference, InferenceEngine},
+    net_ordering::NetOrdering,
+    stop_condition::{CancelToken, StopCondition},
+};
+
+/// how PcbProblem::solve_with_config chooses the traces from the candidates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SolverStrategy {
+    /// fix the top-ranked candidates one by one and resample the model when they collide
+    #[default]
+    NodeStack,
+    /// sample the candidates once and pick the cheapest collision-free combination by branch and bound
+    Exact { max_nodes: usize },
+    /// route every connection allowing overlaps, then rip up and reroute the conflicting ones with congestion costs
+    Negotiation { max_iterations: usize },
+    /// keep the best nodes of every depth, fixing one more trace each and resampling the model of the survivors
+    BeamSearch { beam_width: usize },
+}
+
+impl SolverStrategy {
+    pub fn exact() -> Self {
+        SolverStrategy::Exact {
+            max_nodes: EXACT_MAX_NODES,
+        }
+    }
+
+    pub fn negotiation() -> Self {
+        SolverStrategy::Negotiation {
+            max_iterations: NEGOTIATION_MAX_ITERATIONS,
+        }
+    }
+
+    pub fn beam_search() -> Self {
+        SolverStrategy::BeamSearch {
+            beam_width: BEAM_WIDTH,
+        }
+    }
+}
+
+/// the tunable settings of PcbProblem::solve_with_config
+#[derive(Debug, Clone)]
+pub struct SolverConfig {
+    pub astar_search_budget: SearchBudget, // budget of every A* search run by the sampler
+    pub seed: Option<u64>, // seed of the sampler, None for a different route on every run
+    pub num_iterations: NonZeroUsize, // sampling iterations of a probabilistic model, at most MAX_ITERATION
+    pub posterior_tolerance: f64, // the posterior updates of an iteration stop when no posterior changes more
+    pub max_posterior_updates: usize, // the posterior updates of an iteration stop here even without convergence
+    pub inference_engine: Arc<dyn InferenceEngine>, // computes the posteriors of the candidate traces
+    pub strategy: SolverStrategy,
+    pub net_ordering: NetOrdering, // the order of the nets in the negotiation, see NetOrdering for the other strategies
+    pub allow_partial: bool, // return the best set of routed connections instead of an error if not all fit
+    pub incremental_updates: bool, // update the model of a search node from its parent's model instead of sampling it anew
+    // a solve that is cancelled or runs out of time or iterations returns the traces routed so far,
+    // as the solution in the partial mode and inside RoutingError::Stopped otherwise
+    pub cancel_token: CancelToken,
+    pub time_limit: Option<Duration>, // wall time of the whole solve, None for no limit
+    pub max_solver_iterations: Option<usize>, // iterations of the node stack and beam searches, None for no limit
+    // to follow the solve in the window, both are off by default and turned on by the app
+    pub display_astar: bool, // render every A* expansion and sleep DISPLAY_PERIOD_MILLIS after it
+    pub wait_for_enter: bool, // wait for Enter between the iterations of the sampler, see block_or_sleep::start_enter_reader
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        SolverConfig {
+            astar_search_budget: SearchBudget::default(),
+            seed: None,
+            num_iterations: MAX_ITERATION,
+            posterior_tolerance: POSTERIOR_TOLERANCE,
+            max_posterior_updates: MAX_POSTERIOR_UPDATES,
+            inference_engine: Arc::new(FixedPointInference),
+            strategy: SolverStrategy::default(),
+            net_ordering: NetOrdering::default(),
+            allow_partial: false,
+            incremental_updates: true,
+            cancel_token: CancelToken::new(),
+            time_limit: None,
+            max_solver_iterations: None,
+            display_astar: false,
+            wait_for_enter: false,
+        }
+    }
+}
+
+impl SolverConfig {
+    /// the random number generator of a probabilistic model, seeded from self.seed if given
+    pub fn new_rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        }
+    }
+
+    /// the stop condition of a solve that starts now
+    pub fn new_stop_condition(&self) -> StopCondition {
+        StopCondition::new(self.cancel_token.clone(), self.time_limit)
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// a flag shared between the solver and the thread that started it, e.g. to stop when the window closes
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// the solver stops at its next check, see SolverConfig::allow_partial for what it returns
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// why a solve stopped before it finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StopReason {
+    Cancelled,
+    TimeLimitReached,      // SolverConfig::time_limit
+    IterationLimitReached, // SolverConfig::max_solver_iterations
+}
+
+/// the cancel token and the deadline of one solve, checked by the A* search, the sampler and the solve loop
+#[derive(Debug, Clone, Default)]
+pub struct StopCondition {
+    cancel_token: CancelToken,
+    deadline: Option<Instant>, // None for no time limit
+}
+
+impl StopCondition {
+    /// a condition whose time limit starts now
+    pub fn new(cancel_token: CancelToken, time_limit: Option<Duration>) -> Self {
+        StopCondition {
+            cancel_token,
+            deadline: time_limit.map(|time_limit| Instant::now() + time_limit),
+        }
+    }
+
+    /// the reason to stop, if any; cancellation takes precedence over the deadline
+    pub fn check(&self) -> Option<StopReason> {
+        if self.cancel_token.is_cancelled() {
+            return Some(StopReason::Cancelled);
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Some(StopReason::TimeLimitReached);
+        }
+        None
+    }
+}
@@ -1,6 +1,9 @@
+use std::sync::Arc;
+
 use cgmath::Deg;
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
 
-use crate::{pad::{Pad, PadShape}, pcb_problem::{self, Color, PcbProblem}, vec2::FloatVec2};
+use crate::{astar::RoutingMode, net_class::{NetClass, DEFAULT_NET_CLASS}, pad::{Pad, PadShape}, pcb_problem::{self, Color, LengthLimits, PcbProblem}, vec2::FloatVec2};
 
 
 
@@ -8,7 +11,7 @@ use crate::{pad::{Pad, PadShape}, pcb_problem::{self, Color, PcbProblem}, vec2::
 
 pub fn pcb_problem1()->PcbProblem{
     let mut pcb_problem = PcbProblem::new(15.0, 15.0);
-    let red_net_id = pcb_problem.add_net(Color{r: 255, g: 0, b: 0});
+    let red_net_id = pcb_problem.add_net_with_color("red", Color{r: 255, g: 0, b: 0}).unwrap();
     let red_source_pad = Pad{
         position: FloatVec2{x: -6.0, y: 0.0},
         shape: PadShape::Circle { diameter: 0.6},
@@ -35,29 +38,29 @@ pub fn pcb_problem1()->PcbProblem{
         red_sink_pad1,
         0.5,
         0.05,
-    );
+    ).unwrap();
     pcb_problem.add_connection(
         red_net_id, 
         red_source_pad.clone(),
         red_sink_pad2,
         0.5,
         0.05,
-    );
+    ).unwrap();
     pcb_problem.add_connection(
         red_net_id, 
         red_source_pad.clone(),
         red_sink_pad3,
         0.5,
         0.05,
-    );
+    ).unwrap();
     pcb_problem.add_connection(
         red_net_id, 
         red_source_pad.clone(),
         red_sink_pad4,
         0.5,
         0.05,
-    );
-    let purple_net_id = pcb_problem.add_net(Color{r: 128, g: 0, b: 128});
+    ).unwrap();
+    let purple_net_id = pcb_problem.add_net_with_color("purple", Color{r: 128, g: 0, b: 128}).unwrap();
     let purple_source_pad = Pad{
         position: FloatVec2{x: -6.0, y: -1.0},
         shape: PadShape::Circle { diameter: 0.8 },
@@ -74,15 +77,15 @@ pub fn pcb_problem1()->PcbProblem{
         purple_sink_pad1,
         0.5,
         0.05,
-    );
+    ).unwrap();
     pcb_problem.add_connection(
         purple_net_id, 
         purple_source_pad.clone(),
         purple_sink_pad2,
         0.5,
         0.05,
-    );
-    let blue_net_id = pcb_problem.add_net(Color{r: 0, g: 0, b: 255});
+    ).unwrap();
+    let blue_net_id = pcb_problem.add_net_with_color("blue", Color{r: 0, g: 0, b: 255}).unwrap();
     let blue_source_pad = Pad{
         position: FloatVec2{x: -2.0, y: -1.0},
         shape: PadShape::Circle { diameter: 0.8 },
@@ -99,15 +102,15 @@ pub fn pcb_problem1()->PcbProblem{
         blue_sink_pad1,
         0.3,
         0.05,
-    );
+    ).unwrap();
     pcb_problem.add_connection(
         blue_net_id, 
         blue_source_pad.clone(),
         blue_sink_pad2,
         0.3,
         0.05,
-    );
-    let gray_net_id = pcb_problem.add_net(Color{r: 128, g: 128, b: 128});
+    ).unwrap();
+    let gray_net_id = pcb_problem.add_net_with_color("gray", Color{r: 128, g: 128, b: 128}).unwrap();
     let gray_source_pad = Pad{
         position: FloatVec2{x: -6.0, y: -2.0},
         shape: PadShape::Circle { diameter: 0.6 },
@@ -122,9 +125,9 @@ pub fn pcb_problem1()->PcbProblem{
         gray_sink_pad,
         0.2,
         0.05,
-    );
+    ).unwrap();
 
-    let brown_net_id = pcb_problem.add_net(Color{r: 165, g: 42, b: 42});
+    let brown_net_id = pcb_problem.add_net_with_color("brown", Color{r: 165, g: 42, b: 42}).unwrap();
     let brown_source_pad = Pad{
         position: FloatVec2{x: -6.0, y: -3.0},
         shape: PadShape::Circle { diameter: 0.8 },
@@ -139,16 +142,16 @@ pub fn pcb_problem1()->PcbProblem{
         brown_sink_pad,
         0.2,
         0.05,
-    );
+    ).unwrap();
     pcb_problem
 }
 
 pub fn pcb_problem2()->PcbProblem{
     let mut pcb_problem = PcbProblem::new(20.0, 20.0);
-    let red_net_id = pcb_problem.add_net(Color{r: 255, g: 0, b: 0});
-    let green_net_id = pcb_problem.add_net(Color{r: 0, g: 255, b: 0});
-    let blue_net_id = pcb_problem.add_net(Color{r: 0, g: 0, b: 255});
-    let yellow_net_id = pcb_problem.add_net(Color{r: 255, g: 255, b: 0});
+    let red_net_id = pcb_problem.add_net_with_color("red", Color{r: 255, g: 0, b: 0}).unwrap();
+    let green_net_id = pcb_problem.add_net_with_color("green", Color{r: 0, g: 255, b: 0}).unwrap();
+    let blue_net_id = pcb_problem.add_net_with_color("blue", Color{r: 0, g: 0, b: 255}).unwrap();
+    let yellow_net_id = pcb_problem.add_net_with_color("yellow", Color{r: 255, g: 255, b: 0}).unwrap();
     let pad = Pad{
         position: FloatVec2{x: 0.0, y: 0.0},
         shape: PadShape::Circle { diameter: 0.6},
@@ -183,27 +186,251 @@ pub fn pcb_problem2()->PcbProblem{
         red_sink_pad,
         0.5,
         0.2,
-    );
+    ).unwrap();
     pcb_problem.add_connection(
         green_net_id, 
         green_source_pad.clone(),
         green_sink_pad,
         0.7,
         0.05,
-    );
+    ).unwrap();
     pcb_problem.add_connection(
         blue_net_id, 
         blue_source_pad.clone(),
         blue_sink_pad,
         0.6,
         0.3,
-    );
+    ).unwrap();
     pcb_problem.add_connection(
         yellow_net_id, 
         yellow_source_pad.clone(),
         yellow_sink_pad,
         0.4,
         0.1,
-    );
+    ).unwrap();
+    pcb_problem
+}
+
+/// two crossing-free nets whose pads are not octilinearly aligned, in an RF class routed with any-angle segments
+pub fn pcb_problem_any_angle()->PcbProblem{
+    let mut pcb_problem = PcbProblem::new(15.0, 15.0);
+    let mut rf_class = NetClass::new("RF");
+    rf_class.trace_width = 0.3;
+    rf_class.trace_clearance = 0.1;
+    rf_class.routing_mode = RoutingMode::AnyAngle;
+    pcb_problem.add_net_class(rf_class).unwrap();
+    let orange_net_id = pcb_problem.add_net_with_color("orange", Color{r: 255, g: 165, b: 0}).unwrap();
+    let cyan_net_id = pcb_problem.add_net_with_color("cyan", Color{r: 0, g: 255, b: 255}).unwrap();
+    pcb_problem.set_net_class(orange_net_id, "RF").unwrap();
+    pcb_problem.set_net_class(cyan_net_id, "RF").unwrap();
+    let pad = Pad{
+        position: FloatVec2{x: 0.0, y: 0.0},
+        shape: PadShape::Circle { diameter: 0.6},
+        rotation: Deg(0.0),
+        clearance: 0.1,
+    };
+    let mut orange_source_pad = pad.clone();
+    orange_source_pad.position = FloatVec2 { x: -5.0, y: -4.0 };
+    let mut orange_sink_pad = pad.clone();
+    orange_sink_pad.position = FloatVec2 { x: 4.0, y: 1.5 };
+    let mut cyan_source_pad = pad.clone();
+    cyan_source_pad.position = FloatVec2 { x: -5.0, y: 2.0 };
+    let mut cyan_sink_pad = pad.clone();
+    cyan_sink_pad.position = FloatVec2 { x: 3.0, y: 5.5 };
+    let obstacle_net_id = pcb_problem.add_net_with_color("obstacle", Color{r: 128, g: 128, b: 128}).unwrap();
+    let mut obstacle_source_pad = pad.clone();
+    obstacle_source_pad.position = FloatVec2 { x: -1.0, y: -1.0 };
+    obstacle_source_pad.shape = PadShape::Rectangle { width: 1.5, height: 0.8 };
+    obstacle_source_pad.rotation = Deg(30.0);
+    let mut obstacle_sink_pad = obstacle_source_pad.clone();
+    obstacle_sink_pad.position = FloatVec2 { x: 0.0, y: 3.0 };
+    pcb_problem.add_connection_from_class(
+        orange_net_id,
+        orange_source_pad,
+        orange_sink_pad,
+    ).unwrap();
+    pcb_problem.add_connection_from_class(
+        cyan_net_id,
+        cyan_source_pad,
+        cyan_sink_pad,
+    ).unwrap();
+    pcb_problem.add_connection(
+        obstacle_net_id,
+        obstacle_source_pad,
+        obstacle_sink_pad,
+        0.3,
+        0.1,
+    ).unwrap();
+    pcb_problem
+}
+
+/// the layout of pcb_problem2 with every net restricted to axis-aligned segments
+pub fn pcb_problem_manhattan()->PcbProblem{
+    let mut pcb_problem = pcb_problem2();
+    let net_ids: Vec<_> = pcb_problem.nets.keys().cloned().collect();
+    for net_id in net_ids {
+        pcb_problem.set_net_routing_mode(net_id, RoutingMode::Manhattan).unwrap();
+    }
+    pcb_problem
+}
+
+/// pcb_problem2 with the red and green nets in a Power class that keeps a wider clearance to the other nets
+pub fn pcb_problem_net_classes()->PcbProblem{
+    let mut pcb_problem = pcb_problem2();
+    let mut power_class = NetClass::new("Power");
+    power_class.trace_width = 0.8;
+    power_class.trace_clearance = 0.2;
+    pcb_problem.add_net_class(power_class).unwrap();
+    pcb_problem.set_class_clearance("Power", DEFAULT_NET_CLASS, 0.5).unwrap();
+    for name in ["red", "green"] {
+        let net_id = pcb_problem.net_by_name(name).unwrap();
+        pcb_problem.set_net_class(net_id, "Power").unwrap();
+        // drop the widths of pcb_problem2, so that the connections are routed with the rules of the class
+        for connection in pcb_problem.nets.get_mut(&net_id).unwrap().connections.values_mut() {
+            let connection = Arc::make_mut(connection);
+            connection.trace_width = None;
+            connection.trace_clearance = None;
+        }
+    }
+    pcb_problem
+}
+
+/// the round pad of the fixtures below, at x, y
+fn pad(x: f32, y: f32)->Pad{
+    Pad{
+        position: FloatVec2{x, y},
+        shape: PadShape::Circle { diameter: 0.6},
+        rotation: Deg(0.0),
+        clearance: 0.1,
+    }
+}
+
+/// a USB pair from the left to the right, bent around the upper pad of a clock net in its way, and a gpio net
+pub fn pcb_problem_diff_pair()->PcbProblem{
+    let mut pcb_problem = PcbProblem::new(20.0, 20.0);
+    let positive_net_id = pcb_problem.add_net_with_color("usb_dp", Color{r: 255, g: 0, b: 0}).unwrap();
+    let negative_net_id = pcb_problem.add_net_with_color("usb_dn", Color{r: 0, g: 0, b: 255}).unwrap();
+    let clock_net_id = pcb_problem.add_net_with_color("clk", Color{r: 0, g: 255, b: 0}).unwrap();
+    let gpio_net_id = pcb_problem.add_net_with_color("gpio", Color{r: 255, g: 255, b: 0}).unwrap();
+    let positive_connection_id = pcb_problem.add_connection(
+        positive_net_id,
+        pad(-6.0, 4.5),
+        pad(6.0, 4.5),
+        0.25,
+        0.15,
+    ).unwrap();
+    let negative_connection_id = pcb_problem.add_connection(
+        negative_net_id,
+        pad(-6.0, 3.5),
+        pad(6.0, 3.5),
+        0.25,
+        0.15,
+    ).unwrap();
+    pcb_problem.add_connection(
+        clock_net_id,
+        pad(0.0, 3.5),
+        pad(0.0, -6.0),
+        0.3,
+        0.1,
+    ).unwrap();
+    pcb_problem.add_connection(
+        gpio_net_id,
+        pad(-6.0, -4.0),
+        pad(-2.0, -7.0),
+        0.3,
+        0.1,
+    ).unwrap();
+    pcb_problem.add_diff_pair(positive_connection_id, negative_connection_id, 0.25, 0.2).unwrap();
     pcb_problem
-}
\ No newline at end of file
+}
+
+/// three data nets of a byte lane with different distances between their pads, to be tuned to the same length
+pub fn pcb_problem_length_matching()->PcbProblem{
+    let mut pcb_problem = PcbProblem::new(20.0, 20.0);
+    let mut connection_ids = Vec::new();
+    for (index, (source, sink)) in [((-6.0, 4.0), (6.0, 4.0)), ((-6.0, 1.0), (6.0, -2.0)), ((-6.0, -5.0), (4.0, -5.0))].into_iter().enumerate() {
+        let net_id = pcb_problem.add_net(&format!("dq{}", index)).unwrap();
+        connection_ids.push(pcb_problem.add_connection(
+            net_id,
+            pad(source.0, source.1),
+            pad(sink.0, sink.1),
+            0.25,
+            0.15,
+        ).unwrap());
+    }
+    pcb_problem.add_length_match_group("byte_lane", connection_ids, 0.1).unwrap();
+    pcb_problem
+}
+
+/// a clock net with a max length that leaves little room for detours, and a delay line with a min length
+pub fn pcb_problem_length_limits()->PcbProblem{
+    let mut pcb_problem = PcbProblem::new(20.0, 20.0);
+    let clk_net_id = pcb_problem.add_net("clk").unwrap();
+    let clk_connection_id = pcb_problem.add_connection(clk_net_id, pad(-6.0, 4.0), pad(6.0, 4.0), 0.25, 0.15).unwrap();
+    pcb_problem.set_length_limits(clk_connection_id, LengthLimits { min_length: None, max_length: Some(15.0) }).unwrap();
+    let delay_net_id = pcb_problem.add_net("delay").unwrap();
+    let delay_connection_id = pcb_problem.add_connection(delay_net_id, pad(-6.0, -3.0), pad(6.0, -3.0), 0.25, 0.15).unwrap();
+    pcb_problem.set_length_limits(delay_connection_id, LengthLimits { min_length: Some(16.0), max_length: None }).unwrap();
+    // crosses the straight line of clk, which can only go around its upper pad within its max length
+    let gpio_net_id = pcb_problem.add_net("gpio").unwrap();
+    pcb_problem.add_connection(gpio_net_id, pad(0.0, 5.5), pad(0.0, 1.0), 0.25, 0.15).unwrap();
+    pcb_problem
+}
+
+/// two parallel nets far apart, which route at once, e.g. for the tests of the incremental model updates and the stop conditions
+pub fn pcb_problem_parallel_nets()->PcbProblem{
+    let mut pcb_problem = PcbProblem::new(15.0, 15.0);
+    let bottom_net_id = pcb_problem.add_net("bottom").unwrap();
+    pcb_problem.add_connection(bottom_net_id, pad(-5.0, -2.0), pad(5.0, -2.0), 0.5, 0.1).unwrap();
+    let top_net_id = pcb_problem.add_net("top").unwrap();
+    pcb_problem.add_connection(top_net_id, pad(-5.0, 2.0), pad(5.0, 2.0), 0.5, 0.1).unwrap();
+    pcb_problem
+}
+
+/// two nets whose pads reach from one edge of the board to the other, so that their traces always cross
+pub fn pcb_problem_crossing_nets()->PcbProblem{
+    let mut pcb_problem = PcbProblem::new(10.0, 10.0);
+    let horizontal_net_id = pcb_problem.add_net("horizontal").unwrap();
+    pcb_problem.add_connection(horizontal_net_id, pad(-4.5, 0.0), pad(4.5, 0.0), 0.25, 0.15).unwrap();
+    let vertical_net_id = pcb_problem.add_net("vertical").unwrap();
+    pcb_problem.add_connection(vertical_net_id, pad(0.0, -4.5), pad(0.0, 4.5), 0.25, 0.15).unwrap();
+    pcb_problem
+}
+
+/// a board with num_nets two-pad nets on random grid positions, with random priorities and some nets in a Critical class
+/// equal seeds give equal problems
+pub fn random_pcb_problem(seed: u64, num_nets: usize)->PcbProblem{
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut pcb_problem = PcbProblem::new(16.0, 16.0);
+    let mut critical_class = NetClass::new("Critical");
+    critical_class.criticality = 1;
+    pcb_problem.add_net_class(critical_class).unwrap();
+    // pad positions 2.0 apart, so that the pads of different nets never overlap
+    let mut positions: Vec<FloatVec2> = Vec::new();
+    for x in -3..=3 {
+        for y in -3..=3 {
+            positions.push(FloatVec2{x: x as f32 * 2.0, y: y as f32 * 2.0});
+        }
+    }
+    positions.shuffle(&mut rng);
+    assert!(2 * num_nets <= positions.len(), "at most {} nets fit on the board", positions.len() / 2);
+    for net_index in 0..num_nets {
+        let net_id = pcb_problem.add_net_with_color(&format!("net{}", net_index), Color::from_palette(net_index)).unwrap();
+        pcb_problem.set_net_priority(net_id, rng.random_range(0..3)).unwrap();
+        if rng.random_bool(0.3) {
+            pcb_problem.set_net_class(net_id, "Critical").unwrap();
+        }
+        let pad = |position: FloatVec2| Pad{
+            position,
+            shape: PadShape::Circle { diameter: 0.6 },
+            rotation: Deg(0.0),
+            clearance: 0.05,
+        };
+        pcb_problem.add_connection_from_class(
+            net_id,
+            pad(positions[2 * net_index]),
+            pad(positions[2 * net_index + 1]),
+        ).unwrap();
+    }
+    pcb_problem
+}
@@ -0,0 +1,189 @@
+use std::{collections::BTreeMap, path::Path, str::FromStr};
+
+use crate::{
+    pcb_problem::{ConnectionID, FixedTrace, PcbProblem},
+    routing_error::RoutingError,
+    trace_path::TracePath,
+    vec2::{FixedPoint, FixedVec2, FloatVec2},
+};
+
+const TRACE_FILE_HEADER: &str = "# net\tsource\tsink\twidth\tclearance\tlength\tanchors";
+
+fn parse_value<T: FromStr>(text: &str, line_number: usize, what: &str) -> Result<T, RoutingError> {
+    text.trim().parse().map_err(|_| {
+        RoutingError::InvalidInput(format!("line {}: invalid {} {:?}", line_number, what, text))
+    })
+}
+
+fn parse_pair<T: FromStr>(
+    text: &str,
+    line_number: usize,
+    what: &str,
+) -> Result<(T, T), RoutingError> {
+    let Some((x, y)) = text.trim().split_once(' ') else {
+        return Err(RoutingError::InvalidInput(format!(
+            "line {}: invalid {} {:?}, expected \"x y\"",
+            line_number, what, text
+        )));
+    };
+    Ok((
+        parse_value(x, line_number, what)?,
+        parse_value(y, line_number, what)?,
+    ))
+}
+
+/// one line per trace, with the net name and the pad positions of its connection instead of the IDs,
+/// which change when the problem is built again; the fields are separated by tabs, the points are "x y" separated by ';'
+pub fn traces_to_string(
+    problem: &PcbProblem,
+    traces: &BTreeMap<ConnectionID, FixedTrace>,
+) -> Result<String, RoutingError> {
+    let mut lines: Vec<String> = vec![TRACE_FILE_HEADER.to_string()];
+    for (connection_id, fixed_trace) in traces.iter() {
+        let net_info = problem.nets.get(&fixed_trace.net_id).ok_or_else(|| {
+            RoutingError::InvalidInput(format!("NetID {:?} not found", fixed_trace.net_id))
+        })?;
+        let connection = net_info.connections.get(connection_id).ok_or_else(|| {
+            RoutingError::InvalidInput(format!(
+                "ConnectionID {:?} not found in net {:?}",
+                connection_id, net_info.name
+            ))
+        })?;
+        if net_info.name.contains(['\t', '\n', '\r']) {
+            return Err(RoutingError::InvalidInput(format!(
+                "The net name {:?} cannot be written to a trace file",
+                net_info.name
+            )));
+        }
+        let Some(first_segment) = fixed_trace.trace_path.segments.first() else {
+            continue; // a trace without segments connects nothing
+        };
+        let anchors: Vec<String> = fixed_trace
+            .trace_path
+            .anchors
+            .0
+            .iter()
+            .map(|anchor| format!("{} {}", anchor.x, anchor.y))
+            .collect();
+        lines.push(format!(
+            "{}\t{} {}\t{} {}\t{}\t{}\t{}\t{}",
+            net_info.name,
+            connection.source.position.x,
+            connection.source.position.y,
+            connection.sink.position.x,
+            connection.sink.position.y,
+            first_segment.width,
+            first_segment.clearance,
+            fixed_trace.trace_path.length,
+            anchors.join(";")
+        ));
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+/// the traces of the text that belong to the problem, matched by net name and pad positions;
+/// the lines of nets or connections that the problem does not have, e.g. because a pad moved, are skipped
+pub fn parse_traces(
+    problem: &PcbProblem,
+    text: &str,
+) -> Result<BTreeMap<ConnectionID, FixedTrace>, RoutingError> {
+    let mut traces: BTreeMap<ConnectionID, FixedTrace> = BTreeMap::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [net_name, source, sink, width, clearance, length, anchors] = fields[..] else {
+            return Err(RoutingError::InvalidInput(format!(
+                "line {}: expected 7 fields separated by tabs, got {}",
+                line_number,
+                fields.len()
+            )));
+        };
+        let (source_x, source_y): (f32, f32) = parse_pair(source, line_number, "source")?;
+        let (sink_x, sink_y): (f32, f32) = parse_pair(sink, line_number, "sink")?;
+        let width: f32 = parse_value(width, line_number, "width")?;
+        let clearance: f32 = parse_value(clearance, line_number, "clearance")?;
+        let length: f64 = parse_value(length, line_number, "length")?;
+        let anchors: Vec<FixedVec2> = anchors
+            .split(';')
+            .map(|anchor| {
+                let (x, y): (FixedPoint, FixedPoint) = parse_pair(anchor, line_number, "anchor")?;
+                Ok(FixedVec2::new(x, y))
+            })
+            .collect::<Result<_, RoutingError>>()?;
+        if anchors.len() < 2 || anchors.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(RoutingError::InvalidInput(format!(
+                "line {}: a trace needs at least two anchors and no repeated anchor",
+                line_number
+            )));
+        }
+        let Some(net_id) = problem.net_by_name(net_name) else {
+            println!(
+                "line {}: skipping the trace of the unknown net {:?}",
+                line_number, net_name
+            );
+            continue;
+        };
+        let source = FloatVec2 {
+            x: source_x,
+            y: source_y,
+        };
+        let sink = FloatVec2 {
+            x: sink_x,
+            y: sink_y,
+        };
+        let Some(connection_id) = problem.connection_between(net_id, source, sink) else {
+            println!(
+                "line {}: skipping the trace of net {:?}, no connection joins its pads",
+                line_number, net_name
+            );
+            continue;
+        };
+        let trace_path = TracePath {
+            length,
+            ..TracePath::from_anchors(anchors, width, clearance)?
+        };
+        traces.insert(
+            connection_id,
+            FixedTrace {
+                net_id,
+                connection_id,
+                trace_path,
+            },
+        );
+    }
+    Ok(traces)
+}
+
+/// writes the traces in the format of traces_to_string, e.g. the determined traces of a PcbSolution
+pub fn write_traces(
+    problem: &PcbProblem,
+    traces: &BTreeMap<ConnectionID, FixedTrace>,
+    path: impl AsRef<Path>,
+) -> Result<(), RoutingError> {
+    let text = traces_to_string(problem, traces)?;
+    std::fs::write(path.as_ref(), text).map_err(|error| {
+        RoutingError::Io(format!(
+            "cannot write {}: {}",
+            path.as_ref().display(),
+            error
+        ))
+    })
+}
+
+/// reads the traces of a file written by write_traces, to be locked by PcbProblem::solve_with_locked_traces
+pub fn read_traces(
+    problem: &PcbProblem,
+    path: impl AsRef<Path>,
+) -> Result<BTreeMap<ConnectionID, FixedTrace>, RoutingError> {
+    let text = std::fs::read_to_string(path.as_ref()).map_err(|error| {
+        RoutingError::Io(format!(
+            "cannot read {}: {}",
+            path.as_ref().display(),
+            error
+        ))
+    })?;
+    parse_traces(problem, &text)
+}
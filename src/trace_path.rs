@@ -1,7 +1,12 @@
 use crate::{
-    hyperparameters::HALF_PROBABILITY_RAW_SCORE,
+    hyperparameters::{
+        HALF_PROBABILITY_RAW_SCORE, RIGHT_ANGLE_TURN_PENALTY, SHORT_TRACE_PENALTY,
+        TURN_ANGLE_TOLERANCE, TURN_PENALTY,
+    },
+    pcb_problem::LengthLimits,
     pcb_render_model::{RenderableBatch, ShapeRenderable},
-    prim_shape::{CircleShape, PrimShape, RectangleShape},
+    prim_shape::{BoundingBox, CircleShape, PrimShape, RectangleShape},
+    routing_error::{RoutingError, ensure_invariant},
     vec2::{FixedPoint, FixedVec2, FloatVec2},
 };
 
@@ -201,7 +206,42 @@ impl Direction {
     }
 }
 
-#[derive(Debug, Clone)]
+/// counterclockwise angle from start to end in degrees, measured from the positive x axis
+pub fn degree_angle(start: FixedVec2, end: FixedVec2) -> f32 {
+    if let Ok(direction) = Direction::from_points(start, end) {
+        return direction.to_degree_angle();
+    }
+    let start = start.to_float();
+    let end = end.to_float();
+    (end.y - start.y).atan2(end.x - start.x).to_degrees()
+}
+
+/// the angle in degrees between two headings, between 0 (straight) and 180 (reversal)
+pub fn turn_angle(from_degree_angle: f32, to_degree_angle: f32) -> f32 {
+    let difference = (to_degree_angle - from_degree_angle).rem_euclid(360.0);
+    if difference > 180.0 {
+        360.0 - difference
+    } else {
+        difference
+    }
+}
+
+/// the cost of a bend, growing from TURN_PENALTY at 45° to RIGHT_ANGLE_TURN_PENALTY at 90°
+/// returns None for acute bends (sharper than 90°), which are not allowed
+pub fn turn_penalty(turn_angle: f32) -> Option<f64> {
+    if turn_angle < TURN_ANGLE_TOLERANCE {
+        Some(0.0)
+    } else if turn_angle <= 45.0 + TURN_ANGLE_TOLERANCE {
+        Some(TURN_PENALTY * (turn_angle as f64 / 45.0).min(1.0))
+    } else if turn_angle <= 90.0 + TURN_ANGLE_TOLERANCE {
+        let ratio = ((turn_angle as f64 - 45.0) / 45.0).min(1.0);
+        Some(TURN_PENALTY + (RIGHT_ANGLE_TURN_PENALTY - TURN_PENALTY) * ratio)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TraceSegment {
     pub start: FixedVec2, // Start point of the trace segment
     pub end: FixedVec2,   // End point of the trace segment
@@ -210,8 +250,19 @@ pub struct TraceSegment {
 }
 
 impl TraceSegment {
-    pub fn get_direction(&self) -> Direction {
-        Direction::from_points(self.start, self.end).unwrap()
+    /// returns None if the segment is not octilinear (any-angle routing)
+    pub fn get_direction(&self) -> Option<Direction> {
+        Direction::from_points(self.start, self.end).ok()
+    }
+    /// counterclockwise angle of the segment in degrees, measured from the positive x axis
+    pub fn get_degree_angle(&self) -> f32 {
+        degree_angle(self.start, self.end)
+    }
+    pub fn length(&self) -> f64 {
+        (self.end - self.start).length().to_num()
+    }
+    pub fn is_axis_aligned(&self) -> bool {
+        self.start.x == self.end.x || self.start.y == self.end.y
     }
     pub fn to_shapes(&self) -> Vec<PrimShape> {
         // a trace segment is composed of two circles and a rectangle
@@ -233,7 +284,7 @@ impl TraceSegment {
             },
             width: segment_length,
             height: self.width,
-            rotation: cgmath::Deg(self.get_direction().to_degree_angle()),
+            rotation: cgmath::Deg(self.get_degree_angle()),
         });
         vec![start_circle, end_circle, segment_rect]
     }
@@ -259,7 +310,7 @@ impl TraceSegment {
             },
             width: segment_length + self.clearance * 2.0,
             height: new_width,
-            rotation: cgmath::Deg(self.get_direction().to_degree_angle()),
+            rotation: cgmath::Deg(self.get_degree_angle()),
         });
         vec![clearance_start_circle, clearance_end_circle, clearance_rect]
     }
@@ -284,6 +335,18 @@ impl TraceSegment {
         }
         false
     }
+    /// a copy of the segment whose clearance is at least min_clearance
+    pub fn with_min_clearance(&self, min_clearance: f32) -> TraceSegment {
+        TraceSegment {
+            clearance: self.clearance.max(min_clearance),
+            ..self.clone()
+        }
+    }
+    /// like collides_with, but both clearances are raised to at least min_clearance
+    pub fn collides_with_clearance(&self, other: &TraceSegment, min_clearance: f32) -> bool {
+        self.with_min_clearance(min_clearance)
+            .collides_with(&other.with_min_clearance(min_clearance))
+    }
     pub fn to_renderables(&self, color: [f32; 4]) -> Vec<ShapeRenderable> {
         let shapes = self.to_shapes();
         shapes
@@ -303,7 +366,7 @@ impl TraceSegment {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TraceAnchors(pub Vec<FixedVec2>); // List of turning points in the trace path, including start and end
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TracePath {
     pub anchors: TraceAnchors, // List of turning points in the trace path, including start and end
     pub segments: Vec<TraceSegment>, // List of segments in the trace path
@@ -312,10 +375,43 @@ pub struct TracePath {
 // shrink?
 
 impl TracePath {
+    /// builds the segments from the anchors, the length is the sum of the segment lengths
+    pub fn from_anchors(
+        anchors: Vec<FixedVec2>,
+        width: f32,
+        clearance: f32,
+    ) -> Result<TracePath, RoutingError> {
+        let mut segments: Vec<TraceSegment> = Vec::new();
+        for pair in anchors.windows(2) {
+            ensure_invariant!(
+                pair[0] != pair[1],
+                "Start and end positions should not be the same, got {:?} twice",
+                pair[0]
+            );
+            segments.push(TraceSegment {
+                start: pair[0],
+                end: pair[1],
+                width,
+                clearance,
+            });
+        }
+        let length = segments.iter().map(|segment| segment.length()).sum();
+        Ok(TracePath {
+            anchors: TraceAnchors(anchors),
+            segments,
+            length,
+        })
+    }
+
     pub fn collides_with(&self, other: &TracePath) -> bool {
+        self.collides_with_clearance(other, 0.0)
+    }
+
+    /// like collides_with, but both clearances are raised to at least min_clearance
+    pub fn collides_with_clearance(&self, other: &TracePath, min_clearance: f32) -> bool {
         for segment_self in &self.segments {
             for segment_other in &other.segments {
-                if segment_self.collides_with(segment_other) {
+                if segment_self.collides_with_clearance(segment_other, min_clearance) {
                     return true;
                 }
             }
@@ -323,17 +419,53 @@ impl TracePath {
         false
     }
 
-    pub fn get_score(&self) -> f64 {
-        // to do
-        let score_raw = self.length; // placeholder for actual score calculation
+    /// a box containing the clearance shapes of all segments, None for a path without segments
+    /// the margin covers the corners of rotated clearance rectangles, which stick out beyond the end circles
+    pub fn clearance_bounding_box(&self) -> Option<BoundingBox> {
+        let margin = self
+            .segments
+            .iter()
+            .map(|segment| segment.width / 2.0 + segment.clearance * 2.0)
+            .reduce(f32::max)?;
+        let anchor_box =
+            BoundingBox::from_points(self.anchors.0.iter().map(|anchor| anchor.to_float()))?;
+        Some(anchor_box.inflate(margin))
+    }
+
+    /// the sum of the bend penalties along the trace, as the A* search charges them
+    /// None if the trace has an acute bend, which the search does not allow
+    pub fn turn_cost(&self) -> Option<f64> {
+        self.segments
+            .windows(2)
+            .map(|pair| {
+                turn_penalty(turn_angle(
+                    pair[0].get_degree_angle(),
+                    pair[1].get_degree_angle(),
+                ))
+            })
+            .sum()
+    }
+
+    /// 0.0 for a trace longer than the max length or with an acute bend, a trace shorter than the min length scores as a longer one
+    pub fn get_score(&self, length_limits: &LengthLimits) -> Result<f64, RoutingError> {
+        if length_limits.exceeds_max_length(self.length) {
+            return Ok(0.0);
+        }
+        let Some(turn_cost) = self.turn_cost() else {
+            return Ok(0.0);
+        };
+        // the cost of the default A* search, the length plus the bend penalties
+        let score_raw = self.length
+            + turn_cost
+            + SHORT_TRACE_PENALTY * length_limits.missing_length(self.length);
         let k = f64::ln(2.0) / HALF_PROBABILITY_RAW_SCORE;
         let score = f64::exp(-k * score_raw);
-        assert!(
-            score >= 0.0 && score <= 1.0,
+        ensure_invariant!(
+            (0.0..=1.0).contains(&score),
             "Score must be between 0 and 1, got: {}",
             score
         );
-        score
+        Ok(score)
     }
 
     pub fn to_renderables(&self, color: [f32; 4]) -> [RenderableBatch; 2] {
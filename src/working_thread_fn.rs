@@ -3,21 +3,36 @@ use std::sync::{Arc, Mutex};
 use cgmath::Deg;
 
 use crate::{
+    hyperparameters::DISPLAY_ASTAR,
     pad::{Pad, PadShape},
     pcb_problem::{self, Color, PcbProblem},
     pcb_render_model::{self, PcbRenderModel},
+    solver_config::SolverConfig,
+    stop_condition::CancelToken,
     test_pcb_problem::{pcb_problem1, pcb_problem2},
     vec2::FloatVec2,
 };
 
-pub fn working_thread_fn(pcb_render_model: Arc<Mutex<PcbRenderModel>>) {
+pub fn working_thread_fn(pcb_render_model: Arc<Mutex<PcbRenderModel>>, cancel_token: CancelToken) {
     println!("Working thread started");
     let pcb_problem = pcb_problem1();
-    let result = pcb_problem.solve(pcb_render_model.clone());
+    let config = SolverConfig {
+        cancel_token,
+        display_astar: DISPLAY_ASTAR,
+        wait_for_enter: true,
+        ..SolverConfig::default()
+    };
+    let result = pcb_problem.solve_with_config(&config, pcb_render_model.clone());
     match result {
-        Ok(_) => {
+        Ok(solution) if solution.is_complete() => {
             println!("PCB problem solved successfully");
         }
+        Ok(solution) => {
+            println!(
+                "PCB problem partially solved, {} connections unrouted",
+                solution.unrouted_connections.len()
+            );
+        }
         Err(e) => {
             println!("Failed to solve PCB problem: {}", e);
         }
@@ -0,0 +1,65 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    pcb_problem::{PcbProblem, PcbSolution, UnroutedReason},
+    pcb_render_model::PcbRenderModel,
+    routing_error::RoutingError,
+    solver_config::{SolverConfig, SolverStrategy},
+    test_pcb_problem::{pcb_problem_crossing_nets, pcb_problem_parallel_nets},
+};
+
+fn solve(
+    problem: &PcbProblem,
+    beam_width: usize,
+    allow_partial: bool,
+) -> Result<PcbSolution, RoutingError> {
+    let config = SolverConfig {
+        seed: Some(0),
+        strategy: SolverStrategy::BeamSearch { beam_width },
+        allow_partial,
+        // every depth of the beam fixes one more trace
+        max_solver_iterations: Some(2),
+        ..SolverConfig::default()
+    };
+    problem.solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+}
+
+#[test]
+fn the_beam_fixes_one_connection_per_depth() {
+    let problem = pcb_problem_parallel_nets();
+    let solution = solve(&problem, 3, false).unwrap();
+    assert!(solution.is_complete());
+    assert_eq!(solution.determined_traces.len(), 2);
+    let traces: Vec<_> = solution.determined_traces.values().collect();
+    assert!(!traces[0].trace_path.collides_with_clearance(
+        &traces[1].trace_path,
+        problem.clearance_between_nets(traces[0].net_id, traces[1].net_id),
+    ));
+    assert_eq!(
+        solution.determined_traces,
+        solve(&problem, 3, false).unwrap().determined_traces
+    );
+}
+
+#[test]
+fn the_beam_of_crossing_nets_routes_one_of_them() {
+    let problem = pcb_problem_crossing_nets();
+    assert!(matches!(
+        solve(&problem, 3, false),
+        Err(RoutingError::UnroutableConnections { .. })
+    ));
+    let solution = solve(&problem, 3, true).unwrap();
+    assert_eq!(solution.determined_traces.len(), 1);
+    assert_eq!(
+        solution.unrouted_connections.values().collect::<Vec<_>>(),
+        vec![&UnroutedReason::Conflicting]
+    );
+}
+
+#[test]
+fn a_beam_needs_a_width() {
+    assert!(matches!(
+        solve(&pcb_problem_parallel_nets(), 0, false),
+        Err(RoutingError::InvalidInput(_))
+    ));
+}
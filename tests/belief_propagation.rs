@@ -0,0 +1,165 @@
+use std::{
+    collections::BTreeMap,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use pcb_routing_wgpu3::{
+    hyperparameters::BP_UNROUTED_POTENTIAL,
+    inference_engine::{BeliefPropagation, BeliefPropagationMode, InferenceEngine},
+    pcb_problem::{ProbaModel, ProbaTrace},
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::{pcb_problem_crossing_nets, pcb_problem_parallel_nets},
+};
+
+/// the candidates of the two crossing connections after one sampling iteration
+fn crossing_model() -> (ProbaModel, Vec<Arc<ProbaTrace>>, Vec<Arc<ProbaTrace>>) {
+    let config = SolverConfig {
+        seed: Some(0),
+        num_iterations: NonZeroUsize::new(1).unwrap(),
+        ..SolverConfig::default()
+    };
+    let proba_model = ProbaModel::create_and_solve(
+        &pcb_problem_crossing_nets(),
+        &BTreeMap::new(),
+        &config,
+        &config.new_stop_condition(),
+        Arc::new(Mutex::new(PcbRenderModel::default())),
+    )
+    .unwrap();
+    let proba_traces = proba_model.probabilistic_traces();
+    let connection_ids: Vec<_> = proba_model.connection_to_traces.keys().cloned().collect();
+    assert_eq!(connection_ids.len(), 2);
+    let candidates = |index: usize| -> Vec<Arc<ProbaTrace>> {
+        proba_traces
+            .values()
+            .filter(|proba_trace| proba_trace.connection_id == connection_ids[index])
+            .cloned()
+            .collect()
+    };
+    let (horizontal, vertical) = (candidates(0), candidates(1));
+    assert!(!horizontal.is_empty() && !vertical.is_empty());
+    (proba_model, horizontal, vertical)
+}
+
+/// the unary potential of a candidate, None for leaving the connection unrouted
+fn potential(proba_trace: Option<&Arc<ProbaTrace>>) -> f64 {
+    match proba_trace {
+        Some(proba_trace) => {
+            proba_trace.get_normalized_prior().unwrap()
+                * proba_trace
+                    .trace_path
+                    .get_score(&proba_trace.length_limits)
+                    .unwrap()
+        }
+        None => BP_UNROUTED_POTENTIAL,
+    }
+}
+
+/// the product of the potentials of a joint assignment, 0.0 if its traces collide
+fn joint_potential(
+    proba_model: &ProbaModel,
+    first: Option<&Arc<ProbaTrace>>,
+    second: Option<&Arc<ProbaTrace>>,
+) -> f64 {
+    if let (Some(first), Some(second)) = (first, second)
+        && proba_model.collision_adjacency[&first.proba_trace_id].contains(&second.proba_trace_id)
+    {
+        return 0.0;
+    }
+    potential(first) * potential(second)
+}
+
+fn values(candidates: &[Arc<ProbaTrace>]) -> Vec<Option<&Arc<ProbaTrace>>> {
+    candidates.iter().map(Some).chain([None]).collect()
+}
+
+fn posterior(proba_trace: &ProbaTrace) -> f64 {
+    proba_trace.posterior.lock().unwrap().unwrap()
+}
+
+/// runs the messages to convergence, far beyond the default round cap and tolerance
+fn update_until_converged(proba_model: &ProbaModel, mode: BeliefPropagationMode) {
+    let engine = BeliefPropagation {
+        mode,
+        max_rounds: 10_000,
+        tolerance: 1e-12,
+        ..BeliefPropagation::default()
+    };
+    while engine.update_posteriors(proba_model).unwrap() > 1e-9 {}
+}
+
+#[test]
+fn sum_product_gives_the_exact_marginals_of_two_connections() {
+    let (proba_model, horizontal, vertical) = crossing_model();
+    // the traces cross, so some pairs are excluded
+    assert!(horizontal.iter().any(|proba_trace| {
+        !proba_model.collision_adjacency[&proba_trace.proba_trace_id].is_empty()
+    }));
+    // a conflict graph of two variables is a tree, on which the messages converge to the exact marginals
+    update_until_converged(&proba_model, BeliefPropagationMode::SumProduct);
+
+    let partition: f64 = values(&horizontal)
+        .iter()
+        .flat_map(|first| {
+            values(&vertical)
+                .into_iter()
+                .map(move |second| (*first, second))
+        })
+        .map(|(first, second)| joint_potential(&proba_model, first, second))
+        .sum();
+    for proba_trace in horizontal.iter() {
+        let marginal: f64 = values(&vertical)
+            .into_iter()
+            .map(|second| joint_potential(&proba_model, Some(proba_trace), second))
+            .sum::<f64>()
+            / partition;
+        assert!((posterior(proba_trace) - marginal).abs() < 1e-6);
+    }
+    for proba_trace in vertical.iter() {
+        let marginal: f64 = values(&horizontal)
+            .into_iter()
+            .map(|first| joint_potential(&proba_model, first, Some(proba_trace)))
+            .sum::<f64>()
+            / partition;
+        assert!((posterior(proba_trace) - marginal).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn max_product_gives_the_exact_max_marginals_of_two_connections() {
+    let (proba_model, horizontal, vertical) = crossing_model();
+    update_until_converged(&proba_model, BeliefPropagationMode::MaxProduct);
+
+    // the best joint potential with the value of one connection fixed, normalized over the values of that connection,
+    // the collision adjacency is symmetric, so the order of the connections does not matter
+    for (candidates, others) in [(&horizontal, &vertical), (&vertical, &horizontal)] {
+        let max_marginals: Vec<f64> = values(candidates)
+            .into_iter()
+            .map(|value| {
+                values(others)
+                    .into_iter()
+                    .map(|other| joint_potential(&proba_model, value, other))
+                    .fold(0.0, f64::max)
+            })
+            .collect();
+        let sum: f64 = max_marginals.iter().sum();
+        for (proba_trace, max_marginal) in candidates.iter().zip(max_marginals) {
+            assert!((posterior(proba_trace) - max_marginal / sum).abs() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn the_solver_config_selects_belief_propagation() {
+    let config = SolverConfig {
+        seed: Some(0),
+        inference_engine: Arc::new(BeliefPropagation::default()),
+        ..SolverConfig::default()
+    };
+    let solution = pcb_problem_parallel_nets()
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap();
+    assert!(solution.is_complete());
+}
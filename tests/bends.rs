@@ -0,0 +1,74 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    hyperparameters::{RIGHT_ANGLE_TURN_PENALTY, TURN_ANGLE_TOLERANCE, TURN_PENALTY},
+    pcb_problem::LengthLimits,
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem2,
+    trace_path::{TracePath, turn_angle, turn_penalty},
+    vec2::{FixedPoint, FixedVec2},
+};
+
+fn path(points: &[(f32, f32)]) -> TracePath {
+    let anchors = points
+        .iter()
+        .map(|(x, y)| FixedVec2::new(FixedPoint::from_num(*x), FixedPoint::from_num(*y)))
+        .collect();
+    // the same length for every path, so that only the bends tell them apart
+    TracePath {
+        length: 8.0,
+        ..TracePath::from_anchors(anchors, 0.5, 0.05).unwrap()
+    }
+}
+
+#[test]
+fn sharper_bends_score_lower() {
+    let limits = LengthLimits::default();
+    let score = |path: &TracePath| path.get_score(&limits).unwrap();
+    let straight = path(&[(0.0, 0.0), (8.0, 0.0)]);
+    let diagonal_bend = path(&[(0.0, 0.0), (4.0, 0.0), (8.0, 4.0)]);
+    let right_angle_bend = path(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)]);
+    // the score charges the bends like the search, a right angle as much as three 45° bends
+    let three_diagonal_bends = path(&[(0.0, 0.0), (2.0, 0.0), (4.0, 2.0), (4.0, 4.0), (2.0, 6.0)]);
+    assert_eq!(right_angle_bend.turn_cost(), Some(RIGHT_ANGLE_TURN_PENALTY));
+    assert_eq!(three_diagonal_bends.turn_cost(), Some(3.0 * TURN_PENALTY));
+    assert!(score(&straight) > score(&diagonal_bend));
+    assert!(score(&diagonal_bend) > score(&right_angle_bend));
+    assert_eq!(score(&right_angle_bend), score(&three_diagonal_bends));
+}
+
+#[test]
+fn acute_bends_are_rejected_like_in_the_search() {
+    let acute_bend = path(&[(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)]);
+    let angle = turn_angle(
+        acute_bend.segments[0].get_degree_angle(),
+        acute_bend.segments[1].get_degree_angle(),
+    );
+    assert_eq!(turn_penalty(angle), None);
+    assert_eq!(acute_bend.turn_cost(), None);
+    assert_eq!(acute_bend.get_score(&LengthLimits::default()).unwrap(), 0.0);
+}
+
+#[test]
+fn traces_never_bend_sharper_than_a_right_angle() {
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    let solution = pcb_problem2()
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap();
+    for (connection_id, trace) in solution.determined_traces.iter() {
+        for pair in trace.trace_path.segments.windows(2) {
+            let angle = turn_angle(pair[0].get_degree_angle(), pair[1].get_degree_angle());
+            assert!(
+                angle <= 90.0 + TURN_ANGLE_TOLERANCE,
+                "{:?}: a {}° bend at {:?}",
+                connection_id,
+                angle,
+                pair[0].end
+            );
+        }
+    }
+}
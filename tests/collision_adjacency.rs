@@ -0,0 +1,95 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+use pcb_routing_wgpu3::{
+    pcb_problem::{PcbProblem, ProbaModel, ProbaTraceID},
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::{pcb_problem_crossing_nets, pcb_problem_parallel_nets},
+};
+
+/// the adjacency of the pairwise test of every two traces of different nets
+fn pairwise_adjacency(
+    problem: &PcbProblem,
+    proba_model: &ProbaModel,
+) -> BTreeMap<ProbaTraceID, BTreeSet<ProbaTraceID>> {
+    let proba_traces = proba_model.probabilistic_traces();
+    proba_traces
+        .values()
+        .map(|proba_trace| {
+            let adjacent_trace_ids = proba_traces
+                .values()
+                .filter(|other_trace| {
+                    other_trace.net_id != proba_trace.net_id
+                        && proba_trace.trace_path.collides_with_clearance(
+                            &other_trace.trace_path,
+                            problem.clearance_between_nets(proba_trace.net_id, other_trace.net_id),
+                        )
+                })
+                .map(|other_trace| other_trace.proba_trace_id)
+                .collect();
+            (proba_trace.proba_trace_id, adjacent_trace_ids)
+        })
+        .collect()
+}
+
+#[test]
+fn the_incremental_adjacency_equals_the_pairwise_test() {
+    let problem = pcb_problem_crossing_nets();
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    let stop_condition = config.new_stop_condition();
+    // each of the iterations adds its new traces to the adjacency of the earlier ones
+    let proba_model = ProbaModel::create_and_solve(
+        &problem,
+        &BTreeMap::new(),
+        &config,
+        &stop_condition,
+        Arc::new(Mutex::new(PcbRenderModel::default())),
+    )
+    .unwrap();
+    assert!(proba_model.convergence_stats.len() > 1);
+    let adjacency = pairwise_adjacency(&problem, &proba_model);
+    // the nets cross, so some of their candidates collide
+    assert!(
+        adjacency
+            .values()
+            .any(|adjacent_trace_ids| !adjacent_trace_ids.is_empty())
+    );
+    assert_eq!(proba_model.collision_adjacency, adjacency);
+}
+
+#[test]
+fn far_apart_nets_have_no_adjacent_traces() {
+    let problem = pcb_problem_parallel_nets();
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    let stop_condition = config.new_stop_condition();
+    let proba_model = ProbaModel::create_and_solve(
+        &problem,
+        &BTreeMap::new(),
+        &config,
+        &stop_condition,
+        Arc::new(Mutex::new(PcbRenderModel::default())),
+    )
+    .unwrap();
+    assert_eq!(
+        proba_model.collision_adjacency.keys().collect::<Vec<_>>(),
+        proba_model
+            .probabilistic_traces()
+            .keys()
+            .collect::<Vec<_>>()
+    );
+    assert!(
+        proba_model
+            .collision_adjacency
+            .values()
+            .all(|adjacent_trace_ids| adjacent_trace_ids.is_empty())
+    );
+}
@@ -0,0 +1,87 @@
+use std::{
+    collections::BTreeMap,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use pcb_routing_wgpu3::{
+    pcb_problem::{ConvergenceStats, ProbaModel},
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_crossing_nets,
+};
+
+fn convergence_stats(config: &SolverConfig) -> Vec<ConvergenceStats> {
+    let stop_condition = config.new_stop_condition();
+    ProbaModel::create_and_solve(
+        &pcb_problem_crossing_nets(),
+        &BTreeMap::new(),
+        config,
+        &stop_condition,
+        Arc::new(Mutex::new(PcbRenderModel::default())),
+    )
+    .unwrap()
+    .convergence_stats
+}
+
+fn config(
+    num_iterations: usize,
+    posterior_tolerance: f64,
+    max_posterior_updates: usize,
+) -> SolverConfig {
+    SolverConfig {
+        seed: Some(0),
+        num_iterations: NonZeroUsize::new(num_iterations).unwrap(),
+        posterior_tolerance,
+        max_posterior_updates,
+        ..SolverConfig::default()
+    }
+}
+
+#[test]
+fn every_iteration_updates_until_the_posteriors_converge() {
+    let config = config(3, 1e-4, 50);
+    let stats = convergence_stats(&config);
+    assert_eq!(
+        stats
+            .iter()
+            .map(|stats| stats.iteration.get())
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    for stats in stats.iter() {
+        assert!((1..=config.max_posterior_updates).contains(&stats.num_updates));
+        // an iteration stops at the first update under the tolerance, or at the cap
+        assert_eq!(
+            stats.converged,
+            stats.max_posterior_change < config.posterior_tolerance
+        );
+        if !stats.converged {
+            assert_eq!(stats.num_updates, config.max_posterior_updates);
+        }
+    }
+    assert!(stats.iter().any(|stats| stats.converged), "{:?}", stats);
+}
+
+#[test]
+fn the_update_cap_ends_an_iteration_that_has_not_converged() {
+    // no change is under a zero tolerance
+    let stats = convergence_stats(&config(2, 0.0, 1));
+    assert_eq!(stats.len(), 2);
+    assert!(
+        stats
+            .iter()
+            .all(|stats| stats.num_updates == 1 && !stats.converged)
+    );
+}
+
+#[test]
+fn a_loose_tolerance_converges_after_one_update() {
+    let stats = convergence_stats(&config(2, 2.0, 50));
+    assert_eq!(stats.len(), 2);
+    assert!(
+        stats
+            .iter()
+            .all(|stats| stats.num_updates == 1 && stats.converged)
+    );
+}
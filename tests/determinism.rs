@@ -0,0 +1,33 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    pcb_problem::PcbSolution,
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem2,
+};
+
+/// solves pcb_problem2 with the seed, sampling the nets on a pool of num_threads threads
+fn solve_on_threads(seed: u64, num_threads: usize) -> PcbSolution {
+    let config = SolverConfig {
+        seed: Some(seed),
+        ..SolverConfig::default()
+    };
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .unwrap();
+    thread_pool.install(|| {
+        pcb_problem2()
+            .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+            .unwrap()
+    })
+}
+
+#[test]
+fn equal_seeds_give_equal_solutions_on_any_number_of_threads() {
+    let sequential_solution = solve_on_threads(7, 1);
+    let parallel_solution = solve_on_threads(7, 4);
+    assert!(sequential_solution.is_complete());
+    assert_eq!(sequential_solution, parallel_solution);
+}
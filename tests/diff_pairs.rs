@@ -0,0 +1,105 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_diff_pair,
+    trace_path::TraceSegment,
+    vec2::FloatVec2,
+};
+
+fn distance(position1: FloatVec2, position2: FloatVec2) -> f32 {
+    position1.sub(position2).magnitude2().sqrt()
+}
+
+/// the distance of the point from the infinite line through the segment
+fn distance_to_line(point: FloatVec2, segment: &TraceSegment) -> f32 {
+    let (start, end) = (segment.start.to_float(), segment.end.to_float());
+    let direction = end.sub(start).normalize();
+    let offset = point.sub(start);
+    (direction.x * offset.y - direction.y * offset.x).abs()
+}
+
+fn is_parallel(segment: &TraceSegment, other: &TraceSegment) -> bool {
+    let direction = segment.end.to_float().sub(segment.start.to_float()).normalize();
+    let other_direction = other.end.to_float().sub(other.start.to_float()).normalize();
+    direction.dot(other_direction) > 1.0 - 1e-3
+}
+
+#[test]
+fn the_traces_of_a_diff_pair_run_side_by_side_between_their_own_pads() {
+    let problem = pcb_problem_diff_pair();
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    let solution = problem
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap();
+    assert!(solution.is_complete());
+    let diff_pair = problem.diff_pairs.values().next().unwrap();
+    let positive_trace = &solution.determined_traces[&diff_pair.positive_connection_id];
+    let negative_trace = &solution.determined_traces[&diff_pair.negative_connection_id];
+
+    // each trace starts and ends on the pads of its own connection, the pair does not swap sides
+    for trace in [positive_trace, negative_trace] {
+        let connection = &problem.nets[&trace.net_id].connections[&trace.connection_id];
+        let anchors = &trace.trace_path.anchors.0;
+        assert!(distance(anchors[0].to_float(), connection.source.position) < 1e-2);
+        assert!(distance(anchors[anchors.len() - 1].to_float(), connection.sink.position) < 1e-2);
+    }
+
+    // between the breakouts at both ends, every segment has a parallel partner one pitch away
+    let pitch = 2.0 * diff_pair.half_pitch();
+    let positive_segments = &positive_trace.trace_path.segments;
+    let negative_segments = &negative_trace.trace_path.segments;
+    assert_eq!(positive_segments.len(), negative_segments.len());
+    assert!(positive_segments.len() > 2);
+    for (positive_segment, negative_segment) in positive_segments
+        .iter()
+        .zip(negative_segments.iter())
+        .skip(1)
+        .take(positive_segments.len() - 2)
+    {
+        assert!(is_parallel(positive_segment, negative_segment));
+        let segment_distance =
+            distance_to_line(positive_segment.start.to_float(), negative_segment);
+        assert!(
+            (segment_distance - pitch).abs() < 1e-2,
+            "the traces are {} apart instead of {}",
+            segment_distance,
+            pitch
+        );
+    }
+    for segment in positive_segments.iter().chain(negative_segments.iter()) {
+        assert_eq!(segment.width, diff_pair.trace_width);
+    }
+
+    // neither trace comes too close to the other one, the other traces, or the pads of other nets
+    assert!(!positive_trace.trace_path.collides_with_clearance(
+        &negative_trace.trace_path,
+        problem.clearance_between_nets(positive_trace.net_id, negative_trace.net_id),
+    ));
+    for pair_trace in [positive_trace, negative_trace] {
+        assert!(!problem.trace_collides_with_pads(
+            pair_trace.net_id,
+            &pair_trace.trace_path
+        ));
+        for other_trace in solution.determined_traces.values() {
+            if other_trace.net_id == positive_trace.net_id
+                || other_trace.net_id == negative_trace.net_id
+            {
+                continue;
+            }
+            assert!(
+                !pair_trace.trace_path.collides_with_clearance(
+                    &other_trace.trace_path,
+                    problem.clearance_between_nets(pair_trace.net_id, other_trace.net_id),
+                ),
+                "{:?} collides with {:?}",
+                pair_trace.connection_id,
+                other_trace.connection_id
+            );
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use pcb_routing_wgpu3::{
+    exact_selection::{ExactSelection, select_exactly},
+    pcb_problem::{ProbaModel, UnroutedReason},
+    pcb_render_model::PcbRenderModel,
+    routing_error::RoutingError,
+    solver_config::{SolverConfig, SolverStrategy},
+    test_pcb_problem::{pcb_problem_crossing_nets, pcb_problem_parallel_nets},
+};
+
+fn config(max_nodes: usize, allow_partial: bool) -> SolverConfig {
+    SolverConfig {
+        seed: Some(0),
+        strategy: SolverStrategy::Exact { max_nodes },
+        allow_partial,
+        ..SolverConfig::default()
+    }
+}
+
+#[test]
+fn crossing_nets_have_a_minimal_infeasibility_proof() {
+    let problem = pcb_problem_crossing_nets();
+    let config = config(1_000, false);
+    let proba_model = ProbaModel::create_and_solve(
+        &problem,
+        &BTreeMap::new(),
+        &config,
+        &config.new_stop_condition(),
+        Arc::new(Mutex::new(PcbRenderModel::default())),
+    )
+    .unwrap();
+    let selection =
+        select_exactly(&proba_model, 1_000, false, &config.new_stop_condition()).unwrap();
+    let ExactSelection::Infeasible(proof) = selection else {
+        panic!("expected an infeasibility proof, got {:?}", selection);
+    };
+    // each net routes on its own, only the two together collide
+    assert_eq!(proof.connection_ids.len(), 2);
+    assert!(proof.minimal);
+    assert!(proof.explored_nodes <= 1_000);
+
+    match problem.solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default()))) {
+        Err(RoutingError::UnroutableConnections { connection_ids, .. }) => {
+            assert_eq!(connection_ids, proof.connection_ids);
+        }
+        result => panic!(
+            "expected RoutingError::UnroutableConnections, got {:?}",
+            result
+        ),
+    }
+}
+
+#[test]
+fn the_selection_explores_at_most_its_node_budget() {
+    let problem = pcb_problem_parallel_nets();
+    let config = config(1, false);
+    let proba_model = ProbaModel::create_and_solve(
+        &problem,
+        &BTreeMap::new(),
+        &config,
+        &config.new_stop_condition(),
+        Arc::new(Mutex::new(PcbRenderModel::default())),
+    )
+    .unwrap();
+    for max_nodes in [0, 1, 2] {
+        let explored_nodes =
+            match select_exactly(&proba_model, max_nodes, false, &config.new_stop_condition())
+                .unwrap()
+            {
+                ExactSelection::Assignment { explored_nodes, .. } => explored_nodes,
+                ExactSelection::BudgetExhausted { explored_nodes } => explored_nodes,
+                ExactSelection::Infeasible(proof) => panic!("the nets fit, got {:?}", proof),
+            };
+        assert!(
+            explored_nodes <= max_nodes,
+            "{} nodes explored with a budget of {}",
+            explored_nodes,
+            max_nodes
+        );
+    }
+}
+
+#[test]
+fn an_exhausted_node_budget_returns_the_candidates_that_fit() {
+    // without any search node, the selection falls back to the candidates that fit
+    let solution = pcb_problem_parallel_nets()
+        .solve_with_config(
+            &config(0, true),
+            Arc::new(Mutex::new(PcbRenderModel::default())),
+        )
+        .unwrap();
+    assert_eq!(solution.determined_traces.len(), 2);
+    assert!(solution.is_complete());
+
+    match pcb_problem_crossing_nets().solve_with_config(
+        &config(0, false),
+        Arc::new(Mutex::new(PcbRenderModel::default())),
+    ) {
+        Err(RoutingError::SelectionBudgetExhausted {
+            explored_nodes,
+            partial_solution,
+        }) => {
+            assert_eq!(explored_nodes, 0);
+            // one of the crossing nets fits, the other one is reported
+            assert_eq!(partial_solution.determined_traces.len(), 1);
+            assert_eq!(
+                partial_solution
+                    .unrouted_connections
+                    .values()
+                    .collect::<Vec<_>>(),
+                vec![&UnroutedReason::SelectionBudgetExhausted]
+            );
+        }
+        result => panic!(
+            "expected RoutingError::SelectionBudgetExhausted, got {:?}",
+            result
+        ),
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    collections::BTreeMap,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use pcb_routing_wgpu3::{
+    pcb_problem::{ConnectionID, FixedTrace, PcbProblem, ProbaModel, ProbaTraceID, Traces},
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_parallel_nets,
+    trace_path::TracePath,
+    vec2::FloatVec2,
+};
+
+fn only_connection(problem: &PcbProblem, net_name: &str) -> ConnectionID {
+    let net_id = problem.net_by_name(net_name).unwrap();
+    *problem.nets[&net_id].connections.keys().next().unwrap()
+}
+
+fn bottom_trace(problem: &PcbProblem, anchors: &[(f32, f32)]) -> BTreeMap<ConnectionID, FixedTrace> {
+    let connection_id = only_connection(problem, "bottom");
+    let anchors: Vec<FloatVec2> = anchors.iter().map(|(x, y)| FloatVec2 { x: *x, y: *y }).collect();
+    BTreeMap::from([(connection_id, problem.locked_trace(connection_id, &anchors).unwrap())])
+}
+
+fn candidates(proba_model: &ProbaModel, connection_id: ConnectionID) -> Vec<&TracePath> {
+    match &proba_model.connection_to_traces[&connection_id] {
+        Traces::Probabilistic(trace_map) => trace_map.values().map(|proba_trace| &proba_trace.trace_path).collect(),
+        Traces::Fixed(_) => panic!("{:?} has a fixed trace", connection_id),
+    }
+}
+
+#[test]
+fn an_edited_fixed_trace_reroutes_the_frozen_connections_it_touches() {
+    let problem = pcb_problem_parallel_nets();
+    let config = SolverConfig {
+        seed: Some(0),
+        num_iterations: NonZeroUsize::new(1).unwrap(),
+        ..SolverConfig::default()
+    };
+    let stop_condition = config.new_stop_condition();
+    let render_model = || Arc::new(Mutex::new(PcbRenderModel::default()));
+    let top_connection_id = only_connection(&problem, "top");
+
+    let model = ProbaModel::create_and_solve(&problem, &BTreeMap::new(), &config, &stop_condition, render_model()).unwrap();
+    // the straight bottom trace is far from the top net, whose candidates are kept without sampling
+    let straight_traces = bottom_trace(&problem, &[(-5.0, -2.0), (5.0, -2.0)]);
+    let model = model.update_incrementally(&problem, &straight_traces, &config, &stop_condition, render_model()).unwrap();
+    assert!(model.frozen_connections.contains_key(&top_connection_id));
+    let frozen_candidates: Vec<TracePath> =
+        candidates(&model, top_connection_id).into_iter().cloned().collect();
+
+    // the edited bottom trace bends up through the route of the top net
+    let edited_traces = bottom_trace(&problem, &[(-5.0, -2.0), (-2.0, -2.0), (0.0, 2.0), (2.0, -2.0), (5.0, -2.0)]);
+    let edited_trace = &edited_traces.values().next().unwrap().trace_path;
+    assert!(frozen_candidates.iter().any(|candidate| candidate.collides_with(edited_trace)));
+    let model = model.update_incrementally(&problem, &edited_traces, &config, &stop_condition, render_model()).unwrap();
+
+    assert!(!model.frozen_connections.contains_key(&top_connection_id));
+    let top_candidates = candidates(&model, top_connection_id);
+    assert!(top_candidates.iter().all(|candidate| !candidate.collides_with(edited_trace)));
+    assert!(top_candidates.iter().any(|candidate| !frozen_candidates.contains(candidate)));
+}
+
+#[test]
+fn an_update_never_reuses_the_trace_ids_of_its_ancestors() {
+    let problem = pcb_problem_parallel_nets();
+    let config = SolverConfig {
+        seed: Some(0),
+        num_iterations: NonZeroUsize::new(1).unwrap(),
+        ..SolverConfig::default()
+    };
+    let stop_condition = config.new_stop_condition();
+    let render_model = || Arc::new(Mutex::new(PcbRenderModel::default()));
+    let model = ProbaModel::create_and_solve(&problem, &BTreeMap::new(), &config, &stop_condition, render_model()).unwrap();
+
+    // a straight trace drops the candidates of its net and freezes the other net,
+    // then a bent trace through the route of the other net samples that net again,
+    // the dropped candidates of one of the nets have the highest IDs of the first model
+    let fixed_traces = [
+        ("bottom", vec![(-5.0, -2.0), (5.0, -2.0)], vec![(-5.0, -2.0), (-2.0, -2.0), (0.0, 2.0), (2.0, -2.0), (5.0, -2.0)]),
+        ("top", vec![(-5.0, 2.0), (5.0, 2.0)], vec![(-5.0, 2.0), (-2.0, 2.0), (0.0, -2.0), (2.0, 2.0), (5.0, 2.0)]),
+    ];
+    for (net_name, straight_anchors, bent_anchors) in fixed_traces {
+        let connection_id = only_connection(&problem, net_name);
+        let fixed_trace = |anchors: &[(f32, f32)]| {
+            let anchors: Vec<FloatVec2> = anchors.iter().map(|(x, y)| FloatVec2 { x: *x, y: *y }).collect();
+            BTreeMap::from([(connection_id, problem.locked_trace(connection_id, &anchors).unwrap())])
+        };
+        let straight_model = model.update_incrementally(&problem, &fixed_trace(&straight_anchors), &config, &stop_condition, render_model()).unwrap();
+        let bent_model = straight_model.update_incrementally(&problem, &fixed_trace(&bent_anchors), &config, &stop_condition, render_model()).unwrap();
+        // every ID the models handed out, with the candidate it names
+        let mut handed_out: BTreeMap<ProbaTraceID, TracePath> = BTreeMap::new();
+        for proba_model in [&model, &straight_model] {
+            for (proba_trace_id, proba_trace) in proba_model.probabilistic_traces() {
+                handed_out.insert(proba_trace_id, proba_trace.trace_path.clone());
+            }
+        }
+        let bent_traces = bent_model.probabilistic_traces();
+        assert!(bent_traces.keys().any(|proba_trace_id| !handed_out.contains_key(proba_trace_id)));
+        for (proba_trace_id, proba_trace) in bent_traces {
+            if let Some(trace_path) = handed_out.get(&proba_trace_id) {
+                assert_eq!(*trace_path, proba_trace.trace_path, "{:?} is reused", proba_trace_id);
+            }
+            assert!(proba_trace_id < bent_model.next_proba_trace_id);
+        }
+    }
+}
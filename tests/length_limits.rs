@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    pcb_render_model::PcbRenderModel, solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_length_limits,
+};
+
+#[test]
+fn the_traces_keep_the_length_limits_of_their_connections() {
+    let problem = pcb_problem_length_limits();
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    let solution = problem
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap();
+    assert!(solution.is_complete());
+    let trace_length = |net_name: &str| {
+        let net_id = problem.net_by_name(net_name).unwrap();
+        let trace = solution
+            .determined_traces
+            .values()
+            .find(|trace| trace.net_id == net_id)
+            .unwrap();
+        let connection = &problem.nets[&net_id].connections[&trace.connection_id];
+        (trace.trace_path.length, connection.length_limits)
+    };
+    // clk goes around the pad of gpio, which is on its straight line, without exceeding its max length
+    let (clk_length, clk_limits) = trace_length("clk");
+    assert!(
+        clk_length <= clk_limits.max_length.unwrap() as f64,
+        "clk is {} long",
+        clk_length
+    );
+    // the straight delay line is 12.0 long and gets meanders up to its min length
+    let (delay_length, delay_limits) = trace_length("delay");
+    assert!(
+        delay_length >= delay_limits.min_length.unwrap() as f64,
+        "delay is {} long",
+        delay_length
+    );
+}
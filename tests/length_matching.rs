@@ -0,0 +1,53 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_length_matching,
+};
+
+#[test]
+fn the_meanders_match_the_lengths_of_a_group_without_collisions() {
+    let problem = pcb_problem_length_matching();
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    // the solve ends with match_lengths
+    let solution = problem
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap();
+    assert!(solution.is_complete());
+
+    let reports = problem.length_match_reports(&solution);
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(report.lengths.len(), 3);
+    assert!(report.is_within_tolerance(), "{}", report);
+    // dq2 is the shortest connection by 2.0, so its trace has meanders
+    let dq2_net_id = problem.net_by_name("dq2").unwrap();
+    let dq2_trace = solution
+        .determined_traces
+        .values()
+        .find(|trace| trace.net_id == dq2_net_id)
+        .unwrap();
+    assert!(dq2_trace.trace_path.length > 11.0);
+
+    for trace in solution.determined_traces.values() {
+        assert!(!problem.trace_collides_with_pads(trace.net_id, &trace.trace_path));
+        for other_trace in solution.determined_traces.values() {
+            if other_trace.net_id == trace.net_id {
+                continue;
+            }
+            assert!(
+                !trace.trace_path.collides_with_clearance(
+                    &other_trace.trace_path,
+                    problem.clearance_between_nets(trace.net_id, other_trace.net_id),
+                ),
+                "{:?} collides with {:?}",
+                trace.connection_id,
+                other_trace.connection_id
+            );
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use cgmath::Deg;
+use pcb_routing_wgpu3::{
+    pad::{Pad, PadShape},
+    pcb_problem::{ConnectionID, FixedTrace, PcbProblem, PcbSolution},
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_parallel_nets,
+    trace_file::{parse_traces, read_traces, traces_to_string, write_traces},
+    vec2::FloatVec2,
+};
+
+fn pad(x: f32, y: f32) -> Pad {
+    Pad {
+        position: FloatVec2 { x, y },
+        shape: PadShape::Circle { diameter: 0.6 },
+        rotation: Deg(0.0),
+        clearance: 0.1,
+    }
+}
+
+/// the parallel nets after an ECO that moved the right pad of top up by 1.0
+fn moved_pad_problem() -> PcbProblem {
+    let mut problem = PcbProblem::new(15.0, 15.0);
+    let bottom_net_id = problem.add_net("bottom").unwrap();
+    problem
+        .add_connection(bottom_net_id, pad(-5.0, -2.0), pad(5.0, -2.0), 0.5, 0.1)
+        .unwrap();
+    let top_net_id = problem.add_net("top").unwrap();
+    problem
+        .add_connection(top_net_id, pad(-5.0, 2.0), pad(5.0, 3.0), 0.5, 0.1)
+        .unwrap();
+    problem
+}
+
+fn only_connection(problem: &PcbProblem, net_name: &str) -> ConnectionID {
+    let net_id = problem.net_by_name(net_name).unwrap();
+    *problem.nets[&net_id].connections.keys().next().unwrap()
+}
+
+fn assert_routed_without_collisions(problem: &PcbProblem, solution: &PcbSolution) {
+    assert!(solution.is_complete());
+    let traces: Vec<_> = solution.determined_traces.values().collect();
+    assert_eq!(traces.len(), 2);
+    assert!(!traces[0].trace_path.collides_with_clearance(
+        &traces[1].trace_path,
+        problem.clearance_between_nets(traces[0].net_id, traces[1].net_id),
+    ));
+    for trace in traces.iter() {
+        assert!(!problem.trace_collides_with_pads(trace.net_id, &trace.trace_path));
+    }
+}
+
+fn solve(problem: &PcbProblem, locked_traces: &BTreeMap<ConnectionID, FixedTrace>) -> PcbSolution {
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    problem
+        .solve_with_locked_traces(
+            locked_traces,
+            &config,
+            Arc::new(Mutex::new(PcbRenderModel::default())),
+        )
+        .unwrap()
+}
+
+#[test]
+fn a_solution_survives_the_trace_file() {
+    let problem = pcb_problem_parallel_nets();
+    let solution = solve(&problem, &BTreeMap::new());
+    let path = std::env::temp_dir().join(format!("locked_traces_{}.tsv", std::process::id()));
+    write_traces(&problem, &solution.determined_traces, &path).unwrap();
+    // the file matches the traces by net name and pads, so a problem built again reads them back
+    let traces = read_traces(&pcb_problem_parallel_nets(), &path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(traces, solution.determined_traces);
+}
+
+#[test]
+fn the_trace_file_skips_the_connections_whose_pads_moved() {
+    let problem = pcb_problem_parallel_nets();
+    let solution = solve(&problem, &BTreeMap::new());
+    let text = traces_to_string(&problem, &solution.determined_traces).unwrap();
+    let moved_problem = moved_pad_problem();
+    let traces = parse_traces(&moved_problem, &text).unwrap();
+    let bottom_connection_id = only_connection(&moved_problem, "bottom");
+    assert_eq!(
+        traces.keys().collect::<Vec<_>>(),
+        vec![&bottom_connection_id]
+    );
+    assert_eq!(
+        traces[&bottom_connection_id].trace_path,
+        solution.determined_traces[&only_connection(&problem, "bottom")].trace_path
+    );
+}
+
+#[test]
+fn a_locked_trace_is_part_of_the_solution_as_given() {
+    let problem = pcb_problem_parallel_nets();
+    // a hand-routed detour below the straight line
+    let connection_id = only_connection(&problem, "bottom");
+    let anchors =
+        [(-5.0, -2.0), (-3.0, -4.0), (3.0, -4.0), (5.0, -2.0)].map(|(x, y)| FloatVec2 { x, y });
+    let locked_trace = problem.locked_trace(connection_id, &anchors).unwrap();
+    let solution = solve(
+        &problem,
+        &BTreeMap::from([(connection_id, locked_trace.clone())]),
+    );
+    assert_routed_without_collisions(&problem, &solution);
+    assert_eq!(solution.determined_traces[&connection_id], locked_trace);
+}
+
+#[test]
+fn the_eco_mode_reroutes_only_the_nets_whose_pads_moved() {
+    let previous_problem = pcb_problem_parallel_nets();
+    let previous_solution = solve(&previous_problem, &BTreeMap::new());
+    let problem = moved_pad_problem();
+    let traces = problem.translate_traces(&previous_problem, &previous_solution.determined_traces);
+    let bottom_connection_id = only_connection(&problem, "bottom");
+    assert_eq!(
+        problem
+            .eco_locked_traces(&traces)
+            .keys()
+            .collect::<Vec<_>>(),
+        vec![&bottom_connection_id]
+    );
+
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    let solution = problem
+        .solve_eco(
+            &previous_problem,
+            &previous_solution,
+            &config,
+            Arc::new(Mutex::new(PcbRenderModel::default())),
+        )
+        .unwrap();
+    assert_routed_without_collisions(&problem, &solution);
+    assert_eq!(
+        solution.determined_traces[&bottom_connection_id].trace_path,
+        previous_solution.determined_traces[&only_connection(&previous_problem, "bottom")]
+            .trace_path
+    );
+    // the new trace of top reaches the moved pad
+    let top_trace = &solution.determined_traces[&only_connection(&problem, "top")];
+    let end = top_trace.trace_path.anchors.0.last().unwrap().to_float();
+    assert_eq!((end.x, end.y), (5.0, 3.0));
+}
@@ -0,0 +1,120 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use cgmath::Deg;
+use pcb_routing_wgpu3::{
+    hyperparameters::MAX_ITERATION,
+    pad::{Pad, PadShape},
+    pcb_problem::{ConnectionID, LengthLimits, NetID, PcbProblem, ProbaTrace, ProbaTraceID},
+    pcb_render_model::PcbRenderModel,
+    routing_error::RoutingError,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_parallel_nets,
+    trace_path::TracePath,
+    vec2::{FixedPoint, FixedVec2, FloatVec2},
+};
+
+fn fixed(x: f32, y: f32) -> FixedVec2 {
+    FixedVec2::new(FixedPoint::from_num(x), FixedPoint::from_num(y))
+}
+
+fn solve(problem: &PcbProblem, config: &SolverConfig) -> Result<(), RoutingError> {
+    problem
+        .solve_with_config(config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .map(|_| ())
+}
+
+#[test]
+fn a_malformed_problem_is_an_error_instead_of_a_panic() {
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    // a board without area and a connection with a NaN trace width and a negative clearance
+    let mut problem = PcbProblem::new(0.0, 10.0);
+    let net_id = problem.add_net("broken").unwrap();
+    let pad = |x: f32| Pad {
+        position: FloatVec2 { x, y: 0.0 },
+        shape: PadShape::Circle { diameter: 0.6 },
+        rotation: Deg(0.0),
+        clearance: 0.1,
+    };
+    problem
+        .add_connection(net_id, pad(-3.0), pad(3.0), f32::NAN, -0.1)
+        .unwrap();
+    let Err(RoutingError::InvalidProblem(diagnostics)) = solve(&problem, &config) else {
+        panic!("the malformed problem was solved");
+    };
+    assert!(diagnostics.len() >= 2, "{:?}", diagnostics);
+
+    // more iterations than there are priors for
+    let config = SolverConfig {
+        num_iterations: NonZeroUsize::new(MAX_ITERATION.get() + 1).unwrap(),
+        ..config
+    };
+    assert!(matches!(
+        solve(&pcb_problem_parallel_nets(), &config),
+        Err(RoutingError::InvalidInput(_))
+    ));
+}
+
+#[test]
+fn a_locked_trace_with_a_repeated_anchor_is_an_error() {
+    let problem = pcb_problem_parallel_nets();
+    let connection_id = *problem.nets[&problem.net_by_name("bottom").unwrap()]
+        .connections
+        .keys()
+        .next()
+        .unwrap();
+    let anchors = [
+        FloatVec2 { x: -5.0, y: -2.0 },
+        FloatVec2 { x: -5.0, y: -2.0 },
+        FloatVec2 { x: 5.0, y: -2.0 },
+    ];
+    assert!(matches!(
+        problem.locked_trace(connection_id, &anchors),
+        Err(RoutingError::InvalidInput(_))
+    ));
+}
+
+#[test]
+fn the_trace_helpers_return_errors_instead_of_panicking() {
+    let repeated_anchors = vec![fixed(0.0, 0.0), fixed(0.0, 0.0), fixed(2.0, 0.0)];
+    assert!(matches!(
+        TracePath::from_anchors(repeated_anchors, 0.5, 0.1),
+        Err(RoutingError::InternalInvariant(_))
+    ));
+
+    let trace_path =
+        TracePath::from_anchors(vec![fixed(0.0, 0.0), fixed(2.0, 0.0)], 0.5, 0.1).unwrap();
+    let limits = LengthLimits::default();
+    assert!(trace_path.get_score(&limits).unwrap() > 0.0);
+    // a NaN length gives a NaN score, which is outside of [0, 1]
+    let nan_trace_path = TracePath {
+        length: f64::NAN,
+        ..trace_path.clone()
+    };
+    assert!(matches!(
+        nan_trace_path.get_score(&limits),
+        Err(RoutingError::InternalInvariant(_))
+    ));
+
+    // a trace of an iteration that has no prior
+    let proba_trace = ProbaTrace {
+        net_id: NetID(0),
+        connection_id: ConnectionID(0),
+        proba_trace_id: ProbaTraceID(0),
+        trace_path,
+        iteration: NonZeroUsize::new(MAX_ITERATION.get() + 1).unwrap(),
+        posterior: Mutex::new(None),
+        temp_posterior: Mutex::new(None),
+        length_limits: limits,
+    };
+    assert!(matches!(
+        proba_trace.get_normalized_prior(),
+        Err(RoutingError::InternalInvariant(_))
+    ));
+    assert!(proba_trace.get_posterior_with_fallback().is_err());
+}
@@ -0,0 +1,81 @@
+use std::sync::{Arc, Mutex};
+
+use cgmath::Deg;
+use pcb_routing_wgpu3::{
+    pad::{Pad, PadShape},
+    pcb_problem::{PcbProblem, PcbSolution},
+    pcb_render_model::PcbRenderModel,
+    routing_error::RoutingError,
+    solver_config::{SolverConfig, SolverStrategy},
+    vec2::FloatVec2,
+};
+
+fn pad(x: f32, y: f32) -> Pad {
+    Pad {
+        position: FloatVec2 { x, y },
+        shape: PadShape::Circle { diameter: 0.6 },
+        rotation: Deg(0.0),
+        clearance: 0.1,
+    }
+}
+
+/// the straight route of the vertical net crosses the horizontal net, it fits around the left end of it
+fn detour_problem() -> PcbProblem {
+    let mut problem = PcbProblem::new(10.0, 10.0);
+    let horizontal_net_id = problem.add_net("horizontal").unwrap();
+    problem
+        .add_connection(horizontal_net_id, pad(-3.0, 0.0), pad(4.0, 0.0), 0.25, 0.15)
+        .unwrap();
+    let vertical_net_id = problem.add_net("vertical").unwrap();
+    problem
+        .add_connection(vertical_net_id, pad(-1.5, -3.0), pad(-1.5, 3.0), 0.25, 0.15)
+        .unwrap();
+    problem
+}
+
+fn solve(problem: &PcbProblem, max_iterations: usize) -> Result<PcbSolution, RoutingError> {
+    let config = SolverConfig {
+        seed: Some(0),
+        strategy: SolverStrategy::Negotiation { max_iterations },
+        ..SolverConfig::default()
+    };
+    problem.solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+}
+
+#[test]
+fn the_negotiation_reroutes_the_overlapping_net_around_the_other_one() {
+    let problem = detour_problem();
+    // the first pass routes both nets through each other, which takes more than one iteration to resolve
+    assert!(matches!(
+        solve(&problem, 1),
+        Err(RoutingError::UnroutableConnections { .. })
+    ));
+    let solution = solve(&problem, 30).unwrap();
+    assert!(solution.is_complete());
+    let traces: Vec<_> = solution.determined_traces.values().collect();
+    assert_eq!(traces.len(), 2);
+    assert!(!traces[0].trace_path.collides_with_clearance(
+        &traces[1].trace_path,
+        problem.clearance_between_nets(traces[0].net_id, traces[1].net_id),
+    ));
+    for trace in traces.iter() {
+        assert!(!problem.trace_collides_with_pads(trace.net_id, &trace.trace_path));
+    }
+    // one of the nets leaves its straight line
+    let straight_lengths = [7.0, 6.0];
+    assert!(
+        traces
+            .iter()
+            .zip(straight_lengths)
+            .any(|(trace, straight_length)| trace.trace_path.length > straight_length + 1.0)
+    );
+}
+
+#[test]
+fn the_negotiation_is_deterministic() {
+    let problem = detour_problem();
+    assert_eq!(
+        solve(&problem, 30).unwrap().determined_traces,
+        solve(&problem, 30).unwrap().determined_traces
+    );
+}
@@ -0,0 +1,79 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_net_classes,
+    trace_path::TraceSegment,
+    vec2::FloatVec2,
+};
+
+fn point_to_segment_distance(point: FloatVec2, start: FloatVec2, end: FloatVec2) -> f32 {
+    let direction = end.sub(start);
+    let t = (point.sub(start).dot(direction) / direction.magnitude2()).clamp(0.0, 1.0);
+    let closest = FloatVec2 {
+        x: start.x + direction.x * t,
+        y: start.y + direction.y * t,
+    };
+    point.sub(closest).magnitude2().sqrt()
+}
+
+/// the distance between the copper of two segments, which are assumed not to cross
+fn copper_distance(segment: &TraceSegment, other: &TraceSegment) -> f32 {
+    let (start, end) = (segment.start.to_float(), segment.end.to_float());
+    let (other_start, other_end) = (other.start.to_float(), other.end.to_float());
+    let centerline_distance = [
+        point_to_segment_distance(start, other_start, other_end),
+        point_to_segment_distance(end, other_start, other_end),
+        point_to_segment_distance(other_start, start, end),
+        point_to_segment_distance(other_end, start, end),
+    ]
+    .into_iter()
+    .fold(f32::MAX, f32::min);
+    centerline_distance - segment.width / 2.0 - other.width / 2.0
+}
+
+#[test]
+fn nets_of_classes_with_a_larger_clearance_stay_that_far_apart() {
+    let problem = pcb_problem_net_classes();
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    let solution = problem
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap();
+    assert!(solution.is_complete());
+    let power_class = &problem.net_classes["Power"];
+    let is_power = |net_id| problem.net_class_of(net_id) == "Power";
+    for trace in solution.determined_traces.values() {
+        if !is_power(trace.net_id) {
+            continue;
+        }
+        // the width of the class, not the one pcb_problem2 gave the connection
+        assert!(trace
+            .trace_path
+            .segments
+            .iter()
+            .all(|segment| segment.width == power_class.trace_width));
+        for other_trace in solution.determined_traces.values() {
+            if is_power(other_trace.net_id) {
+                continue;
+            }
+            let required_clearance = problem.clearance_between_nets(trace.net_id, other_trace.net_id);
+            assert_eq!(required_clearance, 0.5);
+            for segment in trace.trace_path.segments.iter() {
+                for other_segment in other_trace.trace_path.segments.iter() {
+                    let distance = copper_distance(segment, other_segment);
+                    assert!(
+                        distance >= required_clearance - 1e-3,
+                        "{:?} and {:?} are {} apart",
+                        trace.connection_id,
+                        other_trace.connection_id,
+                        distance
+                    );
+                }
+            }
+        }
+    }
+}
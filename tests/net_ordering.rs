@@ -0,0 +1,121 @@
+use std::sync::{Arc, Mutex};
+
+use cgmath::Deg;
+use pcb_routing_wgpu3::{
+    net_class::NetClass,
+    net_ordering::NetOrdering,
+    pad::{Pad, PadShape},
+    pcb_problem::PcbProblem,
+    pcb_render_model::PcbRenderModel,
+    solver_config::{SolverConfig, SolverStrategy},
+    vec2::FloatVec2,
+};
+
+fn pad(x: f32, y: f32) -> Pad {
+    Pad {
+        position: FloatVec2 { x, y },
+        shape: PadShape::Circle { diameter: 0.6 },
+        rotation: Deg(0.0),
+        clearance: 0.1,
+    }
+}
+
+/// four nets that every ordering puts in another order
+fn ordering_problem() -> PcbProblem {
+    let mut problem = PcbProblem::new(20.0, 20.0);
+    let mut critical_class = NetClass::new("Critical");
+    critical_class.criticality = 2;
+    problem.add_net_class(critical_class).unwrap();
+    let mut medium_class = NetClass::new("Medium");
+    medium_class.criticality = 1;
+    problem.add_net_class(medium_class).unwrap();
+    // 16 long along the bottom edge, far from the others
+    let long_net_id = problem.add_net("long").unwrap();
+    problem
+        .add_connection(long_net_id, pad(-8.0, -8.0), pad(8.0, -8.0), 0.25, 0.1)
+        .unwrap();
+    problem.set_net_class(long_net_id, "Medium").unwrap();
+    // 2 long, inside the bounding box of wide
+    let short_net_id = problem.add_net("short").unwrap();
+    problem
+        .add_connection(short_net_id, pad(-1.0, 5.0), pad(1.0, 5.0), 0.25, 0.1)
+        .unwrap();
+    problem.set_net_priority(short_net_id, 5).unwrap();
+    // about 14.4 long, its bounding box contains the pads of short
+    let wide_net_id = problem.add_net("wide").unwrap();
+    problem
+        .add_connection(wide_net_id, pad(-6.0, -2.0), pad(6.0, 6.0), 0.25, 0.1)
+        .unwrap();
+    problem.set_net_priority(wide_net_id, -1).unwrap();
+    // 3 long, far from the others
+    let medium_net_id = problem.add_net("medium").unwrap();
+    problem
+        .add_connection(medium_net_id, pad(5.0, -5.0), pad(8.0, -5.0), 0.25, 0.1)
+        .unwrap();
+    problem.set_net_class(medium_net_id, "Critical").unwrap();
+    problem
+}
+
+fn ordered_net_names(problem: &PcbProblem, ordering: NetOrdering) -> Vec<&str> {
+    problem
+        .ordered_net_ids(ordering)
+        .iter()
+        .map(|net_id| problem.nets[net_id].name.as_str())
+        .collect()
+}
+
+#[test]
+fn every_ordering_sorts_the_nets_by_its_heuristic() {
+    let problem = ordering_problem();
+    let expected_orders = [
+        (NetOrdering::ById, ["long", "short", "wide", "medium"]),
+        (
+            NetOrdering::ShortestFirst,
+            ["short", "medium", "wide", "long"],
+        ),
+        // wide overlaps short and contains its two pads, short overlaps wide, the others constrain nothing
+        (
+            NetOrdering::MostConstrainedFirst,
+            ["wide", "short", "long", "medium"],
+        ),
+        (
+            NetOrdering::CriticalityFirst,
+            ["medium", "long", "short", "wide"],
+        ),
+        (
+            NetOrdering::PriorityFirst,
+            ["short", "long", "medium", "wide"],
+        ),
+    ];
+    assert_eq!(expected_orders.len(), NetOrdering::ALL.len());
+    for (ordering, expected_order) in expected_orders {
+        assert_eq!(
+            ordered_net_names(&problem, ordering),
+            expected_order,
+            "{:?}",
+            ordering
+        );
+    }
+}
+
+#[test]
+fn every_ordering_routes_the_nets_deterministically() {
+    let problem = ordering_problem();
+    for ordering in NetOrdering::ALL {
+        // the negotiation routes the nets one by one in the order
+        let config = SolverConfig {
+            seed: Some(0),
+            strategy: SolverStrategy::Negotiation { max_iterations: 10 },
+            net_ordering: ordering,
+            ..SolverConfig::default()
+        };
+        let solve = || {
+            problem
+                .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+                .unwrap()
+        };
+        let solution = solve();
+        assert!(solution.is_complete(), "{:?}", ordering);
+        assert_eq!(solution.determined_traces, solve().determined_traces);
+    }
+}
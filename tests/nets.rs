@@ -0,0 +1,58 @@
+use pcb_routing_wgpu3::{
+    net_class::{DEFAULT_NET_CLASS, NetClass},
+    pcb_problem::{Color, PcbProblem},
+    routing_error::RoutingError,
+};
+
+#[test]
+fn hundreds_of_nets_get_palette_colors_and_are_found_by_name() {
+    let mut problem = PcbProblem::new(100.0, 100.0);
+    let net_ids: Vec<_> = (0..300)
+        .map(|index| problem.add_net(&format!("NET_{}", index)).unwrap())
+        .collect();
+    for (index, net_id) in net_ids.iter().enumerate() {
+        let name = format!("NET_{}", index);
+        assert_eq!(problem.net_by_name(&name), Some(*net_id));
+        let net_info = &problem.nets[net_id];
+        assert_eq!(net_info.name, name);
+        assert_eq!(net_info.color, Color::from_palette(index));
+    }
+    // consecutive nets are told apart by their colors
+    for pair in net_ids.windows(2) {
+        assert_ne!(problem.nets[&pair[0]].color, problem.nets[&pair[1]].color);
+    }
+    assert_eq!(problem.net_by_name("NET_300"), None);
+}
+
+#[test]
+fn nets_may_share_a_color_but_not_a_name() {
+    let mut problem = PcbProblem::new(10.0, 10.0);
+    let black = Color { r: 0, g: 0, b: 0 };
+    let gnd_net_id = problem.add_net_with_color("GND", black.clone()).unwrap();
+    let vcc_net_id = problem.add_net_with_color("VCC_3V3", black).unwrap();
+    assert_ne!(gnd_net_id, vcc_net_id);
+    assert_eq!(
+        problem.nets[&gnd_net_id].color,
+        problem.nets[&vcc_net_id].color
+    );
+    assert!(matches!(
+        problem.add_net("GND"),
+        Err(RoutingError::InvalidInput(_))
+    ));
+    assert_eq!(problem.nets.len(), 2);
+}
+
+#[test]
+fn a_net_belongs_to_the_default_class_until_it_is_assigned_one() {
+    let mut problem = PcbProblem::new(10.0, 10.0);
+    let net_id = problem.add_net("USB_DP").unwrap();
+    assert_eq!(problem.net_class_of(net_id), DEFAULT_NET_CLASS);
+    assert!(problem.set_net_class(net_id, "HighSpeed").is_err());
+    problem.add_net_class(NetClass::new("HighSpeed")).unwrap();
+    problem.set_net_class(net_id, "HighSpeed").unwrap();
+    assert_eq!(problem.net_class_of(net_id), "HighSpeed");
+    assert_eq!(
+        problem.nets[&net_id].net_class.as_deref(),
+        Some("HighSpeed")
+    );
+}
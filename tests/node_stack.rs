@@ -0,0 +1,28 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    pcb_render_model::PcbRenderModel, routing_error::RoutingError, solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_crossing_nets,
+};
+
+#[test]
+fn the_node_stack_gives_up_on_crossing_nets() {
+    // the node with one fixed trace is updated once, then popped, instead of being updated again on every backtrack,
+    // the iteration limit only turns such a loop into a failure instead of a hang
+    let config = SolverConfig {
+        seed: Some(0),
+        max_solver_iterations: Some(20),
+        ..SolverConfig::default()
+    };
+    match pcb_problem_crossing_nets()
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+    {
+        Err(RoutingError::UnroutableConnections { connection_ids, .. }) => {
+            assert_eq!(connection_ids.len(), 1);
+        }
+        result => panic!(
+            "expected RoutingError::UnroutableConnections, got {:?}",
+            result
+        ),
+    }
+}
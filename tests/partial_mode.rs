@@ -0,0 +1,62 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    pcb_problem::{PcbSolution, UnroutedReason},
+    pcb_render_model::PcbRenderModel,
+    solver_config::{SolverConfig, SolverStrategy},
+    test_pcb_problem::pcb_problem_crossing_nets,
+};
+
+/// the crossing nets cannot both be routed, the partial mode returns one of them and explains the other
+fn solve_partially(strategy: SolverStrategy) -> PcbSolution {
+    let problem = pcb_problem_crossing_nets();
+    let config = SolverConfig {
+        seed: Some(0),
+        strategy,
+        allow_partial: true,
+        max_solver_iterations: Some(20),
+        ..SolverConfig::default()
+    };
+    let solution = problem
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap();
+    assert!(!solution.is_complete());
+    assert_eq!(solution.determined_traces.len(), 1);
+    assert_eq!(solution.unrouted_connections.len(), 1);
+    // every connection is either routed or unrouted
+    let mut connection_ids: Vec<_> = solution
+        .determined_traces
+        .keys()
+        .chain(solution.unrouted_connections.keys())
+        .cloned()
+        .collect();
+    connection_ids.sort();
+    let all_connection_ids: Vec<_> = problem
+        .nets
+        .values()
+        .flat_map(|net_info| net_info.connections.keys().cloned())
+        .collect();
+    assert_eq!(connection_ids, all_connection_ids);
+    for trace in solution.determined_traces.values() {
+        assert!(!problem.trace_collides_with_pads(trace.net_id, &trace.trace_path));
+    }
+    solution
+}
+
+#[test]
+fn the_node_stack_returns_the_connections_that_fit() {
+    let solution = solve_partially(SolverStrategy::NodeStack);
+    assert_eq!(
+        solution.unrouted_connections.values().next(),
+        Some(&UnroutedReason::Conflicting)
+    );
+}
+
+#[test]
+fn the_exact_selection_returns_the_connections_that_fit() {
+    let solution = solve_partially(SolverStrategy::Exact { max_nodes: 1000 });
+    assert_eq!(
+        solution.unrouted_connections.values().next(),
+        Some(&UnroutedReason::Conflicting)
+    );
+}
@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    astar::RoutingMode,
+    pcb_problem::{PcbProblem, PcbSolution},
+    pcb_render_model::PcbRenderModel,
+    routing_cost::{DefaultRoutingCost, RoutingCost},
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_parallel_nets,
+    trace_path::TracePath,
+    vec2::{FixedVec2, FloatVec2},
+};
+
+/// the region in the middle of the straight line of the bottom net
+fn in_penalty_region(position: FloatVec2) -> bool {
+    position.x.abs() < 1.5 && (position.y + 2.0).abs() < 1.5
+}
+
+/// the point at t between start and end
+fn lerp(start: FloatVec2, end: FloatVec2, t: f32) -> FloatVec2 {
+    FloatVec2 {
+        x: start.x + (end.x - start.x) * t,
+        y: start.y + (end.y - start.y) * t,
+    }
+}
+
+/// the default cost, with the length inside the penalty region charged 20 times, which keeps the default heuristic admissible
+struct PenaltyRegionCost;
+
+impl RoutingCost for PenaltyRegionCost {
+    fn segment_cost(&self, start: FixedVec2, end: FixedVec2) -> f64 {
+        let length = DefaultRoutingCost.segment_cost(start, end);
+        let (start, end) = (start.to_float(), end.to_float());
+        let samples = 20;
+        let inside_samples = (0..samples)
+            .filter(|sample| {
+                let t = (*sample as f32 + 0.5) / samples as f32;
+                in_penalty_region(lerp(start, end, t))
+            })
+            .count();
+        length + 20.0 * length * inside_samples as f64 / samples as f64
+    }
+
+    fn turn_cost(&self, position: FixedVec2, turn_angle: f32) -> Option<f64> {
+        DefaultRoutingCost.turn_cost(position, turn_angle)
+    }
+
+    fn estimated_cost(
+        &self,
+        position: FixedVec2,
+        end: FixedVec2,
+        routing_mode: RoutingMode,
+    ) -> f64 {
+        DefaultRoutingCost.estimated_cost(position, end, routing_mode)
+    }
+}
+
+fn solve(problem: &PcbProblem) -> PcbSolution {
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    problem
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap()
+}
+
+fn bottom_trace(problem: &PcbProblem, solution: &PcbSolution) -> TracePath {
+    let net_id = problem.net_by_name("bottom").unwrap();
+    solution
+        .determined_traces
+        .values()
+        .find(|trace| trace.net_id == net_id)
+        .unwrap()
+        .trace_path
+        .clone()
+}
+
+fn enters_penalty_region(trace_path: &TracePath) -> bool {
+    trace_path.segments.iter().any(|segment| {
+        let (start, end) = (segment.start.to_float(), segment.end.to_float());
+        (0..=100).any(|sample| in_penalty_region(lerp(start, end, sample as f32 / 100.0)))
+    })
+}
+
+#[test]
+fn a_custom_routing_cost_changes_the_route() {
+    let default_problem = pcb_problem_parallel_nets();
+    let default_solution = solve(&default_problem);
+    assert!(default_solution.is_complete());
+    let default_trace = bottom_trace(&default_problem, &default_solution);
+    assert!(enters_penalty_region(&default_trace));
+
+    let mut problem = pcb_problem_parallel_nets();
+    problem.set_routing_cost(Arc::new(PenaltyRegionCost));
+    let solution = solve(&problem);
+    assert!(solution.is_complete());
+    let trace = bottom_trace(&problem, &solution);
+    // the bottom trace goes around the region, which makes it longer
+    assert!(!enters_penalty_region(&trace), "{:?}", trace.anchors);
+    assert!(trace.length > default_trace.length);
+}
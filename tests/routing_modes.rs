@@ -0,0 +1,68 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    astar::RoutingMode,
+    pcb_problem::{PcbProblem, PcbSolution},
+    pcb_render_model::PcbRenderModel,
+    solver_config::SolverConfig,
+    test_pcb_problem::{pcb_problem_any_angle, pcb_problem_manhattan},
+};
+
+/// a seeded solve without the display and the Enter prompts
+fn solve(problem: &PcbProblem) -> PcbSolution {
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    problem
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap()
+}
+
+#[test]
+fn any_angle_traces_are_shorter_than_octilinear_ones() {
+    let any_angle_problem = pcb_problem_any_angle();
+    let mut octilinear_problem = pcb_problem_any_angle();
+    let rf_net_ids: Vec<_> = ["orange", "cyan"]
+        .iter()
+        .map(|name| any_angle_problem.net_by_name(name).unwrap())
+        .collect();
+    for net_id in rf_net_ids.iter() {
+        octilinear_problem
+            .set_net_routing_mode(*net_id, RoutingMode::Octilinear)
+            .unwrap();
+    }
+    let any_angle_solution = solve(&any_angle_problem);
+    let octilinear_solution = solve(&octilinear_problem);
+    assert!(any_angle_solution.is_complete() && octilinear_solution.is_complete());
+    for (connection_id, any_angle_trace) in any_angle_solution.determined_traces.iter() {
+        if !rf_net_ids.contains(&any_angle_trace.net_id) {
+            continue;
+        }
+        let octilinear_trace = &octilinear_solution.determined_traces[connection_id];
+        assert!(
+            any_angle_trace.trace_path.length < octilinear_trace.trace_path.length,
+            "{:?}: the any-angle trace is {} long, the octilinear one {}",
+            connection_id,
+            any_angle_trace.trace_path.length,
+            octilinear_trace.trace_path.length
+        );
+    }
+}
+
+#[test]
+fn manhattan_traces_are_axis_aligned() {
+    let solution = solve(&pcb_problem_manhattan());
+    assert!(solution.is_complete());
+    for (connection_id, trace) in solution.determined_traces.iter() {
+        for segment in trace.trace_path.segments.iter() {
+            assert!(
+                segment.is_axis_aligned(),
+                "{:?}: the segment from {:?} to {:?} is not axis aligned",
+                connection_id,
+                segment.start,
+                segment.end
+            );
+        }
+    }
+}
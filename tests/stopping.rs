@@ -0,0 +1,73 @@
+use std::sync::{Arc, Mutex};
+
+use pcb_routing_wgpu3::{
+    pcb_problem::{PcbSolution, UnroutedReason},
+    pcb_render_model::PcbRenderModel,
+    routing_error::RoutingError,
+    solver_config::SolverConfig,
+    stop_condition::{CancelToken, StopReason},
+    test_pcb_problem::pcb_problem_parallel_nets,
+};
+
+/// the node stack fixes one of the two traces in its first iteration and stops before the second
+fn solve_with_one_iteration(allow_partial: bool) -> Result<PcbSolution, RoutingError> {
+    let config = SolverConfig {
+        seed: Some(0),
+        max_solver_iterations: Some(1),
+        allow_partial,
+        ..SolverConfig::default()
+    };
+    pcb_problem_parallel_nets()
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+}
+
+fn assert_stopped_with_one_routed_trace(solution: &PcbSolution) {
+    assert_eq!(solution.determined_traces.len(), 1);
+    assert_eq!(solution.unrouted_connections.len(), 1);
+    for reason in solution.unrouted_connections.values() {
+        assert_eq!(
+            *reason,
+            UnroutedReason::Stopped(StopReason::IterationLimitReached)
+        );
+    }
+}
+
+#[test]
+fn an_iteration_limit_returns_the_traces_routed_so_far() {
+    let solution = solve_with_one_iteration(true).unwrap();
+    assert_stopped_with_one_routed_trace(&solution);
+}
+
+#[test]
+fn an_iteration_limit_without_the_partial_mode_carries_the_traces_routed_so_far() {
+    match solve_with_one_iteration(false) {
+        Err(RoutingError::Stopped {
+            reason,
+            partial_solution,
+        }) => {
+            assert_eq!(reason, StopReason::IterationLimitReached);
+            assert_stopped_with_one_routed_trace(&partial_solution);
+        }
+        result => panic!("expected RoutingError::Stopped, got {:?}", result),
+    }
+}
+
+#[test]
+fn a_cancelled_solve_stops_before_sampling() {
+    let cancel_token = CancelToken::new();
+    cancel_token.cancel();
+    let config = SolverConfig {
+        seed: Some(0),
+        cancel_token,
+        allow_partial: true,
+        ..SolverConfig::default()
+    };
+    let solution = pcb_problem_parallel_nets()
+        .solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+        .unwrap();
+    assert!(solution.determined_traces.is_empty());
+    assert_eq!(solution.unrouted_connections.len(), 2);
+    for reason in solution.unrouted_connections.values() {
+        assert_eq!(*reason, UnroutedReason::Stopped(StopReason::Cancelled));
+    }
+}
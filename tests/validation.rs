@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex};
+
+use cgmath::Deg;
+use pcb_routing_wgpu3::{
+    pad::{Pad, PadShape},
+    pcb_problem::PcbProblem,
+    pcb_render_model::PcbRenderModel,
+    problem_validation::{Diagnostic, DiagnosticSeverity},
+    routing_error::RoutingError,
+    solver_config::SolverConfig,
+    test_pcb_problem::pcb_problem_parallel_nets,
+    vec2::FloatVec2,
+};
+
+fn pad(x: f32, y: f32) -> Pad {
+    Pad {
+        position: FloatVec2 { x, y },
+        shape: PadShape::Circle { diameter: 0.6 },
+        rotation: Deg(0.0),
+        clearance: 0.1,
+    }
+}
+
+fn messages(diagnostics: &[Diagnostic], severity: DiagnosticSeverity) -> Vec<&str> {
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == severity)
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect()
+}
+
+#[test]
+fn a_valid_problem_has_no_diagnostics() {
+    assert!(pcb_problem_parallel_nets().validate().is_empty());
+}
+
+#[test]
+fn the_diagnostics_name_the_nets_and_connections_they_are_about() {
+    let mut problem = PcbProblem::new(10.0, 10.0);
+    let outside_net_id = problem.add_net("outside").unwrap();
+    // the sink is beyond the right edge of the board
+    let outside_connection_id = problem
+        .add_connection(outside_net_id, pad(-3.0, 3.0), pad(6.0, 3.0), 0.25, 0.1)
+        .unwrap();
+    let left_net_id = problem.add_net("left").unwrap();
+    let left_connection_id = problem
+        .add_connection(left_net_id, pad(-3.0, -3.0), pad(0.0, 0.0), 0.25, 0.1)
+        .unwrap();
+    // the source is 0.5 from the sink of left, closer than the pad diameters
+    let right_net_id = problem.add_net("right").unwrap();
+    let right_connection_id = problem
+        .add_connection(right_net_id, pad(0.5, 0.0), pad(3.0, -3.0), 0.25, 0.1)
+        .unwrap();
+    // the same pads again, in the other direction
+    let duplicate_connection_id = problem
+        .add_connection(right_net_id, pad(3.0, -3.0), pad(0.5, 0.0), 0.25, 0.1)
+        .unwrap();
+
+    let diagnostics = problem.validate();
+    assert_eq!(diagnostics.len(), 3, "{:?}", diagnostics);
+    let outside = &diagnostics[0];
+    assert!(outside.is_error());
+    assert_eq!(outside.message, "the pad at (6, 3) is not inside the board");
+    assert_eq!(outside.net_ids, vec![outside_net_id]);
+    assert_eq!(outside.connection_ids, vec![outside_connection_id]);
+
+    let duplicate = diagnostics
+        .iter()
+        .find(|diagnostic| !diagnostic.is_error())
+        .unwrap();
+    assert_eq!(duplicate.message, "the connections join the same pads");
+    assert_eq!(duplicate.net_ids, vec![right_net_id]);
+    assert_eq!(
+        duplicate.connection_ids,
+        vec![right_connection_id, duplicate_connection_id]
+    );
+
+    let overlap = diagnostics.last().unwrap();
+    assert!(overlap.is_error());
+    assert!(overlap.message.contains("overlap within their clearance"));
+    assert_eq!(overlap.net_ids, vec![left_net_id, right_net_id]);
+    assert_eq!(
+        overlap.connection_ids,
+        vec![left_connection_id, right_connection_id]
+    );
+
+    // the solve fails on the errors before routing, the warning is only printed
+    let config = SolverConfig {
+        seed: Some(0),
+        ..SolverConfig::default()
+    };
+    let Err(RoutingError::InvalidProblem(errors)) =
+        problem.solve_with_config(&config, Arc::new(Mutex::new(PcbRenderModel::default())))
+    else {
+        panic!("the invalid problem was solved");
+    };
+    assert_eq!(
+        messages(&errors, DiagnosticSeverity::Error),
+        messages(&diagnostics, DiagnosticSeverity::Error)
+    );
+    assert!(messages(&errors, DiagnosticSeverity::Warning).is_empty());
+}